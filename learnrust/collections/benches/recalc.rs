@@ -0,0 +1,116 @@
+// Sequential and parallel recalculation side by side, on sheets made up
+// to have lots of formulas that don't read each other. Run it with
+//
+//   cargo bench --bench recalc
+//
+// Every sheet is built twice, once computed each way, and the two have to
+// come out the same before any timings are printed.
+//
+// On a two core machine it prints about
+//
+//   sheet                         formulas   sequential     parallel  speedup
+//   100 rows x 300 columns           29700       72.6ms       87.0ms    0.83x
+//   300 rows x 100 columns           29900       84.1ms       80.1ms    1.05x
+//   fan of 1500 columns               4500       17.0ms       15.7ms    1.08x
+//
+// The times move around from run to run, it's the columns next to each
+// other that say anything.
+use collections::spreadsheet::{column_name, CellRef, Recalculation, Sheet, SpreadsheetCell};
+use std::time::{Duration, Instant};
+
+// Each timing is the best of this many
+const RUNS: usize = 5;
+
+fn formula(source: &str) -> SpreadsheetCell {
+    SpreadsheetCell::formula(source).expect("the benchmark's formulas are fine")
+}
+
+// Columns of formulas each reading the cell above: every row is a level
+// as wide as the sheet
+fn columns(rows: usize, cols: usize) -> Vec<(CellRef, SpreadsheetCell)> {
+    let mut cells = Vec::new();
+    for col in 0..cols {
+        cells.push((CellRef::new(0, col), SpreadsheetCell::Int(col as i64 % 97)));
+        for row in 1..rows {
+            let above = format!("{}{row}", column_name(col));
+            let source = format!("=ROUND({above}*1.0001+MAX({above},1)/(1+{above}*{above}),6)");
+            cells.push((CellRef::new(row, col), formula(&source)));
+        }
+    }
+    cells
+}
+
+// One row of numbers, a wide row of formulas on it, then rows summing
+// ranges of the row above: a few levels, lots of ranges
+fn fan(cols: usize) -> Vec<(CellRef, SpreadsheetCell)> {
+    let mut cells = Vec::new();
+    for col in 0..cols {
+        let name = column_name(col);
+        let next = column_name((col + 4).min(cols - 1));
+        cells.push((
+            CellRef::new(0, col),
+            SpreadsheetCell::Float(col as f64 / 7.0),
+        ));
+        let source = format!("=IF({name}1>10,{name}1*2,-{name}1)");
+        cells.push((CellRef::new(1, col), formula(&source)));
+        let source = format!("=SUM({name}2:{next}2)/AVERAGE({name}2:{next}2)");
+        cells.push((CellRef::new(2, col), formula(&source)));
+        let source = format!("=MAX({name}3:{next}3)-MIN({name}2:{next}3)");
+        cells.push((CellRef::new(3, col), formula(&source)));
+    }
+    cells
+}
+
+fn build(cells: &[(CellRef, SpreadsheetCell)], recalculation: Recalculation) -> Sheet {
+    let mut sheet = Sheet::new();
+    sheet.set_recalculation(recalculation);
+    sheet.set_many(cells.iter().cloned());
+    sheet
+}
+
+fn best(sheet: &mut Sheet) -> Duration {
+    (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            sheet.recalculate_all();
+            start.elapsed()
+        })
+        .min()
+        .unwrap()
+}
+
+fn main() {
+    // at least two, so the threaded path is what gets checked even on a
+    // machine with one core
+    let threads = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let threads = threads.max(2);
+    println!("{threads} threads, best of {RUNS}\n");
+    println!(
+        "{:<28} {:>9} {:>12} {:>12} {:>8}",
+        "sheet", "formulas", "sequential", "parallel", "speedup"
+    );
+    let sheets = [
+        ("100 rows x 300 columns", columns(100, 300)),
+        ("300 rows x 100 columns", columns(300, 100)),
+        ("fan of 1500 columns", fan(1500)),
+    ];
+    for (name, cells) in sheets {
+        let mut sequential = build(&cells, Recalculation::Sequential);
+        let mut parallel = build(&cells, Recalculation::Parallel(threads));
+        assert!(
+            sequential.cells().eq(parallel.cells()),
+            "{name}: parallel recalculation came out different"
+        );
+        let formulas = cells
+            .iter()
+            .filter(|(_, cell)| matches!(cell, SpreadsheetCell::Formula(_)))
+            .count();
+        let (one, many) = (best(&mut sequential), best(&mut parallel));
+        println!(
+            "{name:<28} {formulas:>9} {:>10.1}ms {:>10.1}ms {:>7.2}x",
+            one.as_secs_f64() * 1000.0,
+            many.as_secs_f64() * 1000.0,
+            one.as_secs_f64() / many.as_secs_f64()
+        );
+    }
+}
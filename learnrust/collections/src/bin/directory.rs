@@ -0,0 +1,61 @@
+// A company directory driven by text commands, one per line:
+//
+//   Add Sally to Engineering
+//   Move Sally to Sales
+//   Remove Amir
+//   List Engineering
+//   List
+//   Save company.txt
+//
+// Start it with a file to load that directory first: directory company.txt
+use collections::directory::{Command, Directory};
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+fn main() -> ExitCode {
+    let mut directory = match env::args().nth(1) {
+        Some(path) => match Directory::load(&path) {
+            Ok(directory) => directory,
+            Err(err) => {
+                eprintln!("error: {path}: {err}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Directory::new(),
+    };
+
+    println!("Commands: Add <name> to <department>, Move <name> to <department>,");
+    println!("Remove <name>, List [department], Save <file>, Load <file>, Quit");
+    let stdin = io::stdin();
+    loop {
+        print!("> ");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break, // <-- end of input
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("error: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line.eq_ignore_ascii_case("quit") || line.eq_ignore_ascii_case("exit") {
+            break;
+        }
+        match line
+            .parse::<Command>()
+            .and_then(|command| directory.execute(command))
+        {
+            Ok(message) => println!("{message}"),
+            Err(err) => println!("error: {err}"),
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,80 @@
+// Translate text to pig latin (or back with --reverse) line by line:
+//
+//   pig_latin story.txt
+//   echo "irst-fay" | pig_latin --reverse
+use collections::pig_latin;
+use std::{
+    env,
+    fs::File,
+    io::{self, BufRead, BufReader, Write},
+    process::ExitCode,
+};
+
+const USAGE: &str = "Usage: pig_latin [--reverse] [FILE]";
+
+fn main() -> ExitCode {
+    let mut reverse = false;
+    let mut path = None;
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "--reverse" | "-r" => reverse = true,
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+
+    let input: Box<dyn BufRead> = match &path {
+        Some(path) => match File::open(path) {
+            Ok(file) => Box::new(BufReader::new(file)),
+            Err(err) => {
+                eprintln!("error: {path}: {err}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Box::new(io::stdin().lock()),
+    };
+
+    // one line at a time, so a big file never has to fit in memory
+    let mut stdout = io::stdout().lock();
+    let mut untranslated = 0;
+    for (i, line) in input.lines().enumerate() {
+        let line = match line {
+            Ok(line) => line,
+            Err(err) => {
+                eprintln!("error: line {}: {err}", i + 1);
+                return ExitCode::FAILURE;
+            }
+        };
+        let translated = if reverse {
+            let (translated, skipped) = pig_latin::reverse_line(&line);
+            for (word, candidates) in &skipped {
+                match candidates.is_empty() {
+                    true => eprintln!("line {}: '{word}' isn't pig latin", i + 1),
+                    false => eprintln!(
+                        "line {}: '{word}' could be any of: {}",
+                        i + 1,
+                        candidates.join(", ")
+                    ),
+                }
+            }
+            untranslated += skipped.len();
+            translated
+        } else {
+            pig_latin::line(&line)
+        };
+        if writeln!(stdout, "{translated}").is_err() {
+            return ExitCode::FAILURE; // <-- stdout was closed, nobody is listening
+        }
+    }
+    if untranslated > 0 {
+        eprintln!("{untranslated} word(s) left untranslated");
+    }
+    ExitCode::SUCCESS
+}
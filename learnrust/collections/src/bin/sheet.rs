@@ -0,0 +1,76 @@
+// A spreadsheet at the prompt, one command per line:
+//
+//   set B2 =A2*1.2
+//   show A1:D10
+//   sort by C desc
+//   save out.csv
+//   undo
+//
+// Start it with a CSV file to work on that: sheet prices.csv
+use collections::spreadsheet::{Command, Error, Session, HELP};
+use std::{
+    env,
+    io::{self, BufRead, Write},
+    process::ExitCode,
+};
+
+const PROMPT: &str = "> ";
+
+fn main() -> ExitCode {
+    let mut session = match env::args().nth(1) {
+        Some(path) => match Session::load(&path) {
+            Ok(session) => session,
+            Err(err) => {
+                eprintln!("error: {path}: {err}");
+                return ExitCode::FAILURE;
+            }
+        },
+        None => Session::new(),
+    };
+
+    println!("{HELP}\n");
+    println!("{}", session.render());
+    let stdin = io::stdin();
+    loop {
+        print!("{PROMPT}");
+        let _ = io::stdout().flush();
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line) {
+            Ok(0) => break, // <-- end of input
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("error: {err}");
+                return ExitCode::FAILURE;
+            }
+        }
+        let line = line.trim_end();
+        if line.trim().is_empty() {
+            continue;
+        }
+        if line.trim().eq_ignore_ascii_case("quit") || line.trim().eq_ignore_ascii_case("exit") {
+            break;
+        }
+        let command = match line.parse::<Command>() {
+            Ok(command) => command,
+            Err(Error::Formula { column, message }) => {
+                // point at the mistake under the line that was typed, the
+                // formula starts at the first '='
+                let start = line.find('=').unwrap_or(0);
+                let indent = PROMPT.len() + line[..start].chars().count() + column - 1;
+                println!("{}^ {message}", " ".repeat(indent));
+                continue;
+            }
+            Err(err) => {
+                println!("error: {err}");
+                continue;
+            }
+        };
+        let changes_sheet = command.changes_sheet();
+        match session.execute(command) {
+            Ok(message) if changes_sheet => println!("{message}\n{}", session.render()),
+            Ok(message) => println!("{message}"),
+            Err(err) => println!("error: {err}"),
+        }
+    }
+    ExitCode::SUCCESS
+}
@@ -0,0 +1,63 @@
+// Summary statistics for integers read from a file or stdin:
+//
+//   stats numbers.txt
+//   echo 1 2 2 3 | stats --percentile 90 --percentile 99
+use collections::stats;
+use std::{env, fs, io, io::Read, process::ExitCode};
+
+const USAGE: &str = "Usage: stats [FILE] [--percentile P]...";
+
+fn main() -> ExitCode {
+    let mut path = None;
+    let mut percentiles = Vec::new();
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--percentile" | "-p" => match args.next().map(|p| p.parse::<f64>()) {
+                Some(Ok(p)) => percentiles.push(p),
+                _ => {
+                    eprintln!("--percentile needs a number\n{USAGE}");
+                    return ExitCode::FAILURE;
+                }
+            },
+            "--help" | "-h" => {
+                println!("{USAGE}");
+                return ExitCode::SUCCESS;
+            }
+            _ if path.is_none() => path = Some(arg),
+            _ => {
+                eprintln!("{USAGE}");
+                return ExitCode::FAILURE;
+            }
+        }
+    }
+    if percentiles.is_empty() {
+        percentiles = vec![25.0, 75.0, 90.0];
+    }
+
+    let input = match &path {
+        Some(path) => fs::read_to_string(path),
+        None => {
+            let mut input = String::new();
+            io::stdin().read_to_string(&mut input).map(|_| input)
+        }
+    };
+    let input = match input {
+        Ok(input) => input,
+        Err(err) => {
+            eprintln!("error: {}: {err}", path.as_deref().unwrap_or("stdin"));
+            return ExitCode::FAILURE;
+        }
+    };
+
+    match stats::parse(&input).and_then(|numbers| stats::summarize(&numbers, &percentiles)) {
+        Ok(summary) => {
+            println!("{summary}");
+            ExitCode::SUCCESS
+        }
+        Err(err) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,479 @@
+// The Book's company directory exercise: using a hash map and vectors,
+// let a user add employee names to a department ("Add Sally to
+// Engineering") and list everyone in a department or the whole company
+// sorted alphabetically. Each person is in exactly one department.
+use std::{collections::HashMap, fmt, fs, io, str::FromStr};
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Add { name: String, department: String },
+    Move { name: String, department: String },
+    Remove { name: String },
+    List { department: Option<String> },
+    Save { path: String },
+    Load { path: String },
+}
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownCommand(String),
+    Usage(&'static str),
+    UnknownPerson {
+        name: String,
+        suggestion: Option<String>,
+    },
+    UnknownDepartment {
+        department: String,
+        known: Vec<String>,
+    },
+    AlreadyThere {
+        name: String,
+        department: String,
+    },
+    BadLine {
+        line: usize,
+        text: String,
+    },
+    Io(io::Error),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::UnknownCommand(command) => write!(
+                f,
+                "unknown command '{command}', try Add, Move, Remove, List, Save or Load"
+            ),
+            Error::Usage(usage) => write!(f, "usage: {usage}"),
+            Error::UnknownPerson { name, suggestion } => {
+                write!(f, "nobody called {name} works here")?;
+                match suggestion {
+                    Some(suggestion) => write!(f, ", did you mean {suggestion}?"),
+                    None => Ok(()),
+                }
+            }
+            Error::UnknownDepartment { department, known } if known.is_empty() => {
+                write!(
+                    f,
+                    "there's no {department} department, the company is empty"
+                )
+            }
+            Error::UnknownDepartment { department, known } => write!(
+                f,
+                "there's no {department} department, there's {}",
+                known.join(", ")
+            ),
+            Error::AlreadyThere { name, department } => {
+                write!(f, "{name} is already in {department}")
+            }
+            Error::BadLine { line, text } => {
+                write!(
+                    f,
+                    "line {line}: expected 'department<TAB>name', got '{text}'"
+                )
+            }
+            Error::Io(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+// Split "Sally Smith to Human Resources" around the keyword, both sides
+// may contain spaces
+fn split_around<'a>(text: &'a str, keyword: &str) -> Option<(&'a str, &'a str)> {
+    let (at, _) = text.match_indices(' ').find(|(at, _)| {
+        let after = &text[at + 1..];
+        after
+            .get(..keyword.len())
+            .is_some_and(|word| word.eq_ignore_ascii_case(keyword))
+            && after[keyword.len()..].starts_with(' ')
+    })?;
+    let (left, right) = (text[..at].trim(), text[at + keyword.len() + 2..].trim());
+    if left.is_empty() || right.is_empty() {
+        None
+    } else {
+        Some((left, right))
+    }
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Command, Error> {
+        let line = line.trim();
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        let owned = |s: &str| s.to_owned();
+        match verb.to_lowercase().as_str() {
+            "add" => split_around(rest, "to")
+                .map(|(name, department)| Command::Add {
+                    name: owned(name),
+                    department: owned(department),
+                })
+                .ok_or(Error::Usage("Add <name> to <department>")),
+            "move" => split_around(rest, "to")
+                .map(|(name, department)| Command::Move {
+                    name: owned(name),
+                    department: owned(department),
+                })
+                .ok_or(Error::Usage("Move <name> to <department>")),
+            "remove" if !rest.is_empty() => Ok(Command::Remove { name: owned(rest) }),
+            "remove" => Err(Error::Usage("Remove <name>")),
+            "list" => Ok(Command::List {
+                department: Some(rest).filter(|d| !d.is_empty()).map(owned),
+            }),
+            "save" if !rest.is_empty() => Ok(Command::Save { path: owned(rest) }),
+            "save" => Err(Error::Usage("Save <file>")),
+            "load" if !rest.is_empty() => Ok(Command::Load { path: owned(rest) }),
+            "load" => Err(Error::Usage("Load <file>")),
+            _ => Err(Error::UnknownCommand(verb.to_owned())),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Directory {
+    departments: HashMap<String, Vec<String>>,
+}
+
+impl Directory {
+    pub fn new() -> Directory {
+        Directory::default()
+    }
+
+    // Which department a person is in
+    pub fn department_of(&self, name: &str) -> Option<&str> {
+        self.departments
+            .iter()
+            .find(|(_, people)| people.iter().any(|person| person == name))
+            .map(|(department, _)| department.as_str())
+    }
+
+    fn unknown_person(&self, name: &str) -> Error {
+        // a differently capitalized name is most likely a typo
+        let suggestion = self
+            .departments
+            .values()
+            .flatten()
+            .find(|person| person.eq_ignore_ascii_case(name))
+            .cloned();
+        Error::UnknownPerson {
+            name: name.to_owned(),
+            suggestion,
+        }
+    }
+
+    pub fn add(&mut self, name: &str, department: &str) -> Result<(), Error> {
+        if let Some(current) = self.department_of(name) {
+            return Err(Error::AlreadyThere {
+                name: name.to_owned(),
+                department: current.to_owned(),
+            });
+        }
+        self.departments
+            .entry(department.to_owned())
+            .or_default()
+            .push(name.to_owned());
+        Ok(())
+    }
+
+    pub fn remove(&mut self, name: &str) -> Result<String, Error> {
+        let department = self
+            .department_of(name)
+            .ok_or_else(|| self.unknown_person(name))?
+            .to_owned();
+        let people = self.departments.get_mut(&department).unwrap();
+        people.retain(|person| person != name);
+        // a department only exists while somebody works in it
+        if people.is_empty() {
+            self.departments.remove(&department);
+        }
+        Ok(department)
+    }
+
+    pub fn move_to(&mut self, name: &str, department: &str) -> Result<String, Error> {
+        match self.department_of(name) {
+            Some(current) if current == department => Err(Error::AlreadyThere {
+                name: name.to_owned(),
+                department: department.to_owned(),
+            }),
+            Some(_) => {
+                let previous = self.remove(name)?;
+                self.add(name, department)?;
+                Ok(previous)
+            }
+            None => Err(self.unknown_person(name)),
+        }
+    }
+
+    pub fn departments(&self) -> Vec<&String> {
+        let mut departments: Vec<&String> = self.departments.keys().collect();
+        departments.sort();
+        departments
+    }
+
+    // Everyone in a department, alphabetically
+    pub fn list(&self, department: &str) -> Result<Vec<&String>, Error> {
+        let people = self
+            .departments
+            .get(department)
+            .ok_or_else(|| Error::UnknownDepartment {
+                department: department.to_owned(),
+                known: self.departments().into_iter().cloned().collect(),
+            })?;
+        let mut people: Vec<&String> = people.iter().collect();
+        people.sort();
+        Ok(people)
+    }
+
+    // The whole company, by department, both sorted alphabetically
+    pub fn list_all(&self) -> Vec<(&String, Vec<&String>)> {
+        self.departments()
+            .into_iter()
+            .map(|department| (department, self.list(department).unwrap()))
+            .collect()
+    }
+
+    // One "department<TAB>name" line per person
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        let mut contents = String::new();
+        for (department, people) in self.list_all() {
+            for person in people {
+                contents.push_str(&format!("{department}\t{person}\n"));
+            }
+        }
+        fs::write(path, contents)?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Directory, Error> {
+        let mut directory = Directory::new();
+        for (i, line) in fs::read_to_string(path)?.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let bad_line = || Error::BadLine {
+                line: i + 1,
+                text: line.to_owned(),
+            };
+            let (department, name) = line.split_once('\t').ok_or_else(bad_line)?;
+            let (department, name) = (department.trim(), name.trim());
+            if department.is_empty() || name.is_empty() {
+                return Err(bad_line());
+            }
+            directory.add(name, department)?;
+        }
+        Ok(directory)
+    }
+
+    // Run a command and describe what happened
+    pub fn execute(&mut self, command: Command) -> Result<String, Error> {
+        match command {
+            Command::Add { name, department } => {
+                self.add(&name, &department)?;
+                Ok(format!("Added {name} to {department}"))
+            }
+            Command::Move { name, department } => {
+                let previous = self.move_to(&name, &department)?;
+                Ok(format!("Moved {name} from {previous} to {department}"))
+            }
+            Command::Remove { name } => {
+                let department = self.remove(&name)?;
+                Ok(format!("Removed {name} from {department}"))
+            }
+            Command::List {
+                department: Some(department),
+            } => {
+                let people = self.list(&department)?;
+                let people: Vec<&str> = people.iter().map(|p| p.as_str()).collect();
+                Ok(format!("{department}: {}", people.join(", ")))
+            }
+            Command::List { department: None } => {
+                let lines: Vec<String> = self
+                    .list_all()
+                    .iter()
+                    .map(|(department, people)| {
+                        let people: Vec<&str> = people.iter().map(|p| p.as_str()).collect();
+                        format!("{department}: {}", people.join(", "))
+                    })
+                    .collect();
+                match lines.is_empty() {
+                    true => Ok(String::from("Nobody works here yet")),
+                    false => Ok(lines.join("\n")),
+                }
+            }
+            Command::Save { path } => {
+                self.save(&path)?;
+                Ok(format!("Saved to {path}"))
+            }
+            Command::Load { path } => {
+                *self = Directory::load(&path)?;
+                Ok(format!("Loaded {path}"))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Command {
+        line.parse().unwrap()
+    }
+
+    fn add(name: &str, department: &str) -> Command {
+        Command::Add {
+            name: name.to_owned(),
+            department: department.to_owned(),
+        }
+    }
+
+    #[test]
+    fn commands_are_parsed() {
+        assert_eq!(
+            parse("Add Sally to Engineering"),
+            add("Sally", "Engineering")
+        );
+        assert_eq!(
+            parse("add Sally Smith TO Human Resources"),
+            add("Sally Smith", "Human Resources")
+        );
+        // only a whole word "to" splits the name from the department
+        assert_eq!(parse("Add Toto to Sales"), add("Toto", "Sales"));
+        assert_eq!(
+            parse("MOVE Amir to Sales"),
+            Command::Move {
+                name: String::from("Amir"),
+                department: String::from("Sales"),
+            }
+        );
+        assert_eq!(
+            parse("  Remove   Sally Smith "),
+            Command::Remove {
+                name: String::from("Sally Smith")
+            }
+        );
+        assert_eq!(parse("list"), Command::List { department: None });
+        assert_eq!(
+            parse("List Human Resources"),
+            Command::List {
+                department: Some(String::from("Human Resources"))
+            }
+        );
+        assert_eq!(
+            parse("save company.tsv"),
+            Command::Save {
+                path: String::from("company.tsv")
+            }
+        );
+    }
+
+    #[test]
+    fn bad_commands_say_how() {
+        let error = |line: &str| line.parse::<Command>().unwrap_err();
+        for line in ["Add Sally", "Add to Sales", "Add Sally to", "Move Sally"] {
+            assert!(matches!(error(line), Error::Usage(_)), "{line}");
+        }
+        assert!(matches!(error("Remove"), Error::Usage("Remove <name>")));
+        assert!(matches!(error("Load "), Error::Usage("Load <file>")));
+        assert!(matches!(error("Fire Bob"), Error::UnknownCommand(verb) if verb == "Fire"));
+        assert!(matches!(error(""), Error::UnknownCommand(verb) if verb.is_empty()));
+    }
+
+    fn company() -> Directory {
+        let mut directory = Directory::new();
+        for (name, department) in [
+            ("Sally", "Engineering"),
+            ("Amir", "Sales"),
+            ("Bob", "Engineering"),
+            ("Zoe", "Accounts"),
+            ("alice", "Engineering"),
+            ("Carl", "Sales"),
+        ] {
+            directory.add(name, department).unwrap();
+        }
+        directory
+    }
+
+    #[test]
+    fn listings_are_sorted() {
+        let directory = company();
+        assert_eq!(
+            directory.departments(),
+            ["Accounts", "Engineering", "Sales"]
+        );
+        // plain string order, so capitals come first
+        assert_eq!(
+            directory.list("Engineering").unwrap(),
+            ["Bob", "Sally", "alice"]
+        );
+        let all: Vec<(&String, Vec<&String>)> = directory.list_all();
+        assert_eq!(all.len(), 3);
+        assert_eq!(all[2].0, "Sales");
+        assert_eq!(all[2].1, ["Amir", "Carl"]);
+
+        let mut directory = directory;
+        assert_eq!(
+            directory.execute(parse("List")).unwrap(),
+            "Accounts: Zoe\nEngineering: Bob, Sally, alice\nSales: Amir, Carl"
+        );
+        assert_eq!(
+            directory.execute(parse("List Sales")).unwrap(),
+            "Sales: Amir, Carl"
+        );
+        match directory.list("Marketing") {
+            Err(err @ Error::UnknownDepartment { .. }) => assert_eq!(
+                err.to_string(),
+                "there's no Marketing department, there's Accounts, Engineering, Sales"
+            ),
+            other => panic!("expected an unknown department, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn moving_and_removing() {
+        let mut directory = company();
+        assert_eq!(
+            directory.execute(parse("Move Zoe to Sales")).unwrap(),
+            "Moved Zoe from Accounts to Sales"
+        );
+        // Accounts went with its last person
+        assert_eq!(directory.departments(), ["Engineering", "Sales"]);
+        assert!(matches!(
+            directory.add("Zoe", "Engineering"),
+            Err(Error::AlreadyThere { department, .. }) if department == "Sales"
+        ));
+        assert_eq!(directory.remove("Bob").unwrap(), "Engineering");
+        let err = directory.remove("ALICE").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "nobody called ALICE works here, did you mean alice?"
+        );
+        assert_eq!(
+            directory.execute(parse("List")).unwrap(),
+            "Engineering: Sally, alice\nSales: Amir, Carl, Zoe"
+        );
+        assert_eq!(
+            Directory::new().execute(parse("List")).unwrap(),
+            "Nobody works here yet"
+        );
+    }
+
+    #[test]
+    fn saved_and_loaded() {
+        let path = std::env::temp_dir().join(format!("directory-{}.tsv", std::process::id()));
+        let path = path.to_str().unwrap();
+        company().save(path).unwrap();
+        let loaded = Directory::load(path);
+        fs::remove_file(path).unwrap();
+        assert_eq!(loaded.unwrap().list_all(), company().list_all());
+    }
+}
@@ -0,0 +1,6 @@
+// The chapter 8 exercises built out into small tools, each one has a
+// binary of its own under src/bin.
+pub mod directory;
+pub mod pig_latin;
+pub mod spreadsheet;
+pub mod stats;
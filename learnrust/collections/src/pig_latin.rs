@@ -0,0 +1,291 @@
+// The Book's other chapter 8 exercise: convert strings to pig latin. The
+// first consonant sound of each word is moved to the end of the word and
+// "ay" is added, so "first" becomes "irst-fay". Words that start with a
+// vowel have "hay" added to the end instead ("apple" becomes "apple-hay").
+//
+// Everything works on chars rather than bytes, slicing a String at a byte
+// index in the middle of a multi-byte character would panic.
+
+fn is_vowel(c: char) -> bool {
+    c.to_lowercase()
+        .all(|c| "aeiouàáâãäåæèéêëìíîïòóôõöøœùúûüāēėęīįōūų".contains(c))
+}
+
+// Letters and apostrophes inside a word ("don't") belong to the word
+fn is_word_char(c: char) -> bool {
+    c.is_alphabetic() || c == '\''
+}
+
+// How many leading chars make up the consonant sound that moves to the end.
+// "qu" sticks together ("queen" -> "een-quay", "square" -> "are-squay") and
+// 'y' is a consonant at the start of a word but a vowel after one ("yellow"
+// -> "ellow-yay", "rhythm" -> "ythm-rhay").
+fn cluster_len(word: &[char]) -> usize {
+    let mut i = 0;
+    while i < word.len() {
+        let c = word[i].to_lowercase().next().unwrap_or(word[i]);
+        if is_vowel(c) || (c == 'y' && i > 0) {
+            break;
+        }
+        if c == 'q' && word.get(i + 1).is_some_and(|u| u.to_lowercase().eq(['u'])) {
+            i += 2;
+            continue;
+        }
+        i += 1;
+    }
+    i
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Case {
+    Lower,
+    Capitalized,
+    Upper,
+}
+
+fn case_of(word: &[char]) -> Case {
+    let letters: Vec<&char> = word.iter().filter(|c| c.is_alphabetic()).collect();
+    if letters.len() > 1 && letters.iter().all(|c| c.is_uppercase()) {
+        Case::Upper
+    } else if letters.first().is_some_and(|c| c.is_uppercase()) {
+        Case::Capitalized
+    } else {
+        Case::Lower
+    }
+}
+
+fn apply_case(word: &str, case: Case) -> String {
+    match case {
+        Case::Lower => word.to_lowercase(),
+        Case::Upper => word.to_uppercase(),
+        Case::Capitalized => {
+            let lower = word.to_lowercase();
+            let mut chars = lower.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => String::new(),
+            }
+        }
+    }
+}
+
+// A single word, without any surrounding punctuation
+pub fn word(word: &str) -> String {
+    let chars: Vec<char> = word.chars().collect();
+    let case = case_of(&chars);
+    let split = cluster_len(&chars);
+    let (cluster, rest): (String, String) = (
+        chars[..split].iter().collect(),
+        chars[split..].iter().collect(),
+    );
+    let translated = if split == 0 {
+        format!("{rest}-hay")
+    } else if rest.is_empty() {
+        format!("{cluster}-ay") // <-- no vowels to move the consonants behind ("shh")
+    } else {
+        format!("{rest}-{cluster}ay")
+    };
+    apply_case(&translated, case)
+}
+
+// Split a line into words and whatever is between them, keeping both so
+// punctuation and spacing come out exactly as they went in
+fn tokens(line: &str, is_word: impl Fn(&[char], usize) -> usize) -> Vec<(bool, String)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let len = is_word(&chars, i);
+        if len > 0 {
+            tokens.push((true, chars[i..i + len].iter().collect()));
+            i += len;
+        } else {
+            let start = i;
+            while i < chars.len() && is_word(&chars, i) == 0 {
+                i += 1;
+            }
+            tokens.push((false, chars[start..i].iter().collect()));
+        }
+    }
+    tokens
+}
+
+// Length of the plain word starting at `i`, 0 if there's none. A leading
+// apostrophe is treated as a quote mark rather than part of the word.
+fn plain_word(chars: &[char], i: usize) -> usize {
+    if !chars[i].is_alphabetic() {
+        return 0;
+    }
+    let mut end = i;
+    while end < chars.len() && is_word_char(chars[end]) {
+        end += 1;
+    }
+    // a trailing apostrophe is a closing quote too
+    while end > i && chars[end - 1] == '\'' {
+        end -= 1;
+    }
+    end - i
+}
+
+pub fn line(line: &str) -> String {
+    tokens(line, plain_word)
+        .into_iter()
+        .map(|(is_word, text)| if is_word { word(&text) } else { text })
+        .collect()
+}
+
+// Translating back
+//
+// "irst-fay" can only have been "first", but "apple-hay" could be "apple"
+// or "happle": the "h" is either a moved consonant or the "hay" ending. Only
+// unambiguous words are translated back, the rest are reported.
+
+#[derive(Debug, PartialEq)]
+pub enum Reverse {
+    Word(String),
+    Ambiguous(Vec<String>),
+    NotPigLatin,
+}
+
+pub fn reverse_word(word: &str) -> Reverse {
+    let chars: Vec<char> = word.chars().collect();
+    let case = case_of(&chars);
+    let Some((rest, suffix)) = word.rsplit_once('-') else {
+        return Reverse::NotPigLatin;
+    };
+    let lowered = suffix.to_lowercase();
+    let Some(cluster) = lowered.strip_suffix("ay") else {
+        return Reverse::NotPigLatin;
+    };
+    match cluster {
+        "h" => Reverse::Ambiguous(vec![
+            apply_case(rest, case),
+            apply_case(&format!("h{rest}"), case),
+        ]),
+        "" => Reverse::Word(apply_case(rest, case)),
+        _ => Reverse::Word(apply_case(&format!("{cluster}{rest}"), case)),
+    }
+}
+
+// Length of the "rest-clusteray" word starting at `i`, 0 if there's none
+fn pig_word(chars: &[char], i: usize) -> usize {
+    let rest = plain_word(chars, i);
+    if rest == 0 || chars.get(i + rest) != Some(&'-') {
+        return 0;
+    }
+    let suffix_start = i + rest + 1;
+    let suffix = chars[suffix_start..]
+        .iter()
+        .take_while(|c| c.is_alphabetic())
+        .count();
+    let ending: String = chars[suffix_start..suffix_start + suffix]
+        .iter()
+        .collect::<String>()
+        .to_lowercase();
+    if ending.ends_with("ay") {
+        rest + 1 + suffix
+    } else {
+        0
+    }
+}
+
+// Translate a line back. Words that can't be are left as they are and
+// returned alongside the line with what they could have been.
+pub fn reverse_line(line: &str) -> (String, Vec<(String, Vec<String>)>) {
+    let mut skipped = Vec::new();
+    let translated = tokens(line, pig_word)
+        .into_iter()
+        .map(|(is_word, text)| {
+            if !is_word {
+                return text;
+            }
+            match reverse_word(&text) {
+                Reverse::Word(word) => word,
+                Reverse::Ambiguous(candidates) => {
+                    skipped.push((text.clone(), candidates));
+                    text
+                }
+                Reverse::NotPigLatin => {
+                    skipped.push((text.clone(), Vec::new()));
+                    text
+                }
+            }
+        })
+        .collect();
+    (translated, skipped)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consonants_move_and_vowels_get_hay() {
+        assert_eq!(word("first"), "irst-fay");
+        assert_eq!(word("apple"), "apple-hay");
+        assert_eq!(word("queen"), "een-quay");
+        assert_eq!(word("square"), "are-squay");
+        assert_eq!(word("yellow"), "ellow-yay");
+        assert_eq!(word("rhythm"), "ythm-rhay");
+        assert_eq!(word("shh"), "shh-ay");
+    }
+
+    #[test]
+    fn accented_and_non_latin_words() {
+        assert_eq!(word("école"), "école-hay");
+        assert_eq!(word("über"), "über-hay");
+        assert_eq!(word("ñandú"), "andú-ñay");
+        // no vowels we know of, so the whole word is the cluster
+        assert_eq!(word("привет"), "привет-ay");
+        assert_eq!(word("中文"), "中文-ay");
+    }
+
+    #[test]
+    fn case_is_kept() {
+        assert_eq!(word("Hello"), "Ello-hay");
+        assert_eq!(word("HELLO"), "ELLO-HAY");
+        assert_eq!(word("I"), "I-hay");
+        assert_eq!(word("Éclair"), "Éclair-hay");
+        assert_eq!(word("Quiet"), "Iet-quay");
+    }
+
+    #[test]
+    fn punctuation_stays_put() {
+        assert_eq!(line("Hello, world!"), "Ello-hay, orld-way!");
+        assert_eq!(line("don't"), "on't-day");
+        assert_eq!(line("'quoted'"), "'oted-quay'");
+        assert_eq!(line("  two   spaces\t"), "  o-tway   aces-spay\t");
+        assert_eq!(line("42 ... ?"), "42 ... ?");
+    }
+
+    #[test]
+    fn translating_back() {
+        assert_eq!(
+            reverse_word("irst-fay"),
+            Reverse::Word(String::from("first"))
+        );
+        assert_eq!(reverse_word("shh-ay"), Reverse::Word(String::from("shh")));
+        assert_eq!(reverse_word("E-thay"), Reverse::Word(String::from("The")));
+        assert_eq!(
+            reverse_word("ELLO-HAY"),
+            Reverse::Ambiguous(vec![String::from("ELLO"), String::from("HELLO")])
+        );
+        assert_eq!(reverse_word("hello"), Reverse::NotPigLatin);
+        assert_eq!(reverse_word("well-done"), Reverse::NotPigLatin);
+
+        let sentence = "The quick brown fox, don't you think?";
+        let (back, skipped) = reverse_line(&line(sentence));
+        assert_eq!(back, sentence);
+        assert!(skipped.is_empty());
+
+        let (back, skipped) = reverse_line("Irst-fay, apple-hay!");
+        assert_eq!(back, "First, apple-hay!");
+        assert_eq!(
+            skipped,
+            [(
+                String::from("apple-hay"),
+                vec![String::from("apple"), String::from("happle")]
+            )]
+        );
+    }
+}
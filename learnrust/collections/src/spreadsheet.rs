@@ -0,0 +1,130 @@
+// A small spreadsheet engine built on the SpreadsheetCell enum from the
+// chapter 8 walkthrough: a grid of cells addressed A1-style, some of which
+// are formulas computed from other cells.
+mod address;
+mod binary;
+mod cell;
+mod chart;
+mod csv;
+mod date;
+mod format;
+mod formula;
+mod functions;
+mod graph;
+mod history;
+mod money;
+mod pivot;
+mod query;
+mod session;
+mod sheet;
+mod storage;
+mod value;
+mod workbook;
+
+pub use address::{column_index, column_name, CellRef, Range};
+pub use cell::SpreadsheetCell;
+pub use chart::{Chart, ChartKind, Series};
+pub use csv::CsvOptions;
+pub use date::Date;
+pub use format::Format;
+pub use formula::Formula;
+pub use history::{Edit, History, Transaction};
+pub use money::Money;
+pub use pivot::{Aggregate, Pivot};
+pub use session::{Command, Session, HELP};
+pub use sheet::{Recalculation, Sheet};
+pub use storage::{Dense, Sparse, Storage};
+pub use value::{CellError, Value};
+pub use workbook::Workbook;
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    BadAddress(String),
+    OutOfRange(String),
+    TypeMismatch {
+        expected: &'static str,
+        found: &'static str,
+    },
+    Formula {
+        column: usize, // <-- 1-based, counting the '='
+        message: String,
+    },
+    Query {
+        column: usize,
+        message: String,
+    },
+    Csv {
+        line: usize,
+        message: String,
+    },
+    BadDelimiter(char),
+    BadValue {
+        expected: &'static str,
+        text: String,
+    },
+    BadFormat(String),
+    Chart(String),
+    Io(String), // <-- the io::Error's message, so Error can stay Clone
+    UnknownCommand(String),
+    Usage(&'static str),
+    NothingToUndo,
+    NothingToRedo,
+    Transaction(&'static str),
+    NoSuchSheet(String),
+    SheetExists(String),
+    BadSheetName(String),
+    Damaged(String), // <-- a binary workbook file that doesn't read back
+    Version {
+        found: u16,
+        supported: u16,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::BadAddress(address) => write!(f, "'{address}' is not a cell address like B2"),
+            Error::OutOfRange(what) => write!(f, "{what} is outside of the sheet"),
+            Error::TypeMismatch { expected, found } => {
+                write!(f, "expected a {expected} cell, found {found}")
+            }
+            Error::Formula { column, message } => write!(f, "column {column}: {message}"),
+            Error::Query { column, message } => write!(f, "column {column}: {message}"),
+            Error::Csv { line, message } => write!(f, "line {line}: {message}"),
+            Error::BadDelimiter(c) => write!(f, "{c:?} can't be used as a delimiter"),
+            Error::BadValue { expected, text } => write!(f, "'{text}' isn't a {expected}"),
+            Error::BadFormat(format) => write!(
+                f,
+                "'{format}' isn't a format, try General, 0.00, #,##0, 0% or YYYY-MM-DD"
+            ),
+            Error::Chart(message) => write!(f, "{message}"),
+            Error::Io(message) => write!(f, "{message}"),
+            Error::UnknownCommand(command) => {
+                write!(f, "unknown command '{command}', try help")
+            }
+            Error::Usage(usage) => write!(f, "usage: {usage}"),
+            Error::NothingToUndo => write!(f, "there's nothing to undo"),
+            Error::NothingToRedo => write!(f, "there's nothing to redo"),
+            Error::Transaction(message) => write!(f, "{message}"),
+            Error::NoSuchSheet(name) => write!(f, "there's no sheet called '{name}'"),
+            Error::SheetExists(name) => write!(f, "there's already a sheet called '{name}'"),
+            Error::BadSheetName(name) => write!(
+                f,
+                "'{name}' can't be a sheet name, it has to have something in it and no ! or '"
+            ),
+            Error::Damaged(message) => write!(f, "the file is damaged: {message}"),
+            Error::Version { found, supported } if found > supported => write!(
+                f,
+                "the file is version {found}, from a newer program, only version {supported} can be read"
+            ),
+            Error::Version { found, supported } => write!(
+                f,
+                "the file is version {found}, which is too old, only version {supported} can be read"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
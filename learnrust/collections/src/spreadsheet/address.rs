@@ -0,0 +1,180 @@
+// Cells are addressed A1-style: letters for the column (A..Z, AA..AZ, ...)
+// and a 1-based row number. Internally rows and columns are 0-based.
+use super::Error;
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CellRef {
+    pub row: usize,
+    pub col: usize,
+}
+
+impl CellRef {
+    pub fn new(row: usize, col: usize) -> CellRef {
+        CellRef { row, col }
+    }
+}
+
+// 0 -> "A", 25 -> "Z", 26 -> "AA"
+pub fn column_name(mut col: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push((b'A' + (col % 26) as u8) as char);
+        if col < 26 {
+            break;
+        }
+        col = col / 26 - 1;
+    }
+    name.iter().rev().collect()
+}
+
+// "A" -> 0, "AA" -> 26, None for anything that isn't all letters
+pub fn column_index(name: &str) -> Option<usize> {
+    if name.is_empty() {
+        return None;
+    }
+    // counted from 1 in a u128, so the last usize column still fits
+    let mut col: u128 = 0;
+    for c in name.chars() {
+        if !c.is_ascii_alphabetic() {
+            return None;
+        }
+        let digit = (c.to_ascii_uppercase() as u8 - b'A') as u128 + 1;
+        col = col.checked_mul(26)?.checked_add(digit)?;
+    }
+    usize::try_from(col - 1).ok()
+}
+
+impl fmt::Display for CellRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", column_name(self.col), self.row + 1)
+    }
+}
+
+impl FromStr for CellRef {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<CellRef, Error> {
+        let bad = || Error::BadAddress(s.to_owned());
+        let split = s.find(|c: char| c.is_ascii_digit()).ok_or_else(bad)?;
+        let (letters, digits) = s.split_at(split);
+        let col = column_index(letters).ok_or_else(bad)?;
+        let row: usize = digits.parse().map_err(|_| bad())?;
+        if row == 0 {
+            return Err(bad()); // <-- rows start at 1
+        }
+        Ok(CellRef::new(row - 1, col))
+    }
+}
+
+// A rectangle of cells like A1:C10, always stored top left to bottom right
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Range {
+    pub start: CellRef,
+    pub end: CellRef,
+}
+
+impl Range {
+    pub fn new(a: CellRef, b: CellRef) -> Range {
+        Range {
+            start: CellRef::new(a.row.min(b.row), a.col.min(b.col)),
+            end: CellRef::new(a.row.max(b.row), a.col.max(b.col)),
+        }
+    }
+
+    pub fn contains(&self, cell: CellRef) -> bool {
+        (self.start.row..=self.end.row).contains(&cell.row)
+            && (self.start.col..=self.end.col).contains(&cell.col)
+    }
+
+    pub fn rows(&self) -> usize {
+        self.end.row - self.start.row + 1
+    }
+
+    pub fn cols(&self) -> usize {
+        self.end.col - self.start.col + 1
+    }
+
+    // Every cell in the range, row by row
+    pub fn cells(&self) -> impl Iterator<Item = CellRef> {
+        let (start, end) = (self.start, self.end);
+        (start.row..=end.row)
+            .flat_map(move |row| (start.col..=end.col).map(move |col| CellRef::new(row, col)))
+    }
+}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.start, self.end)
+    }
+}
+
+impl FromStr for Range {
+    type Err = Error;
+
+    // "A1:C3", a single cell "B2" is a one cell range
+    fn from_str(s: &str) -> Result<Range, Error> {
+        match s.split_once(':') {
+            Some((a, b)) => Ok(Range::new(a.trim().parse()?, b.trim().parse()?)),
+            None => {
+                let cell = s.trim().parse()?;
+                Ok(Range::new(cell, cell))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cell(s: &str) -> Result<CellRef, Error> {
+        s.parse()
+    }
+
+    #[test]
+    fn a1_addresses() {
+        assert_eq!(cell("A1").unwrap(), CellRef::new(0, 0));
+        assert_eq!(cell("b2").unwrap(), CellRef::new(1, 1));
+        assert_eq!(cell("aA10").unwrap(), CellRef::new(9, 26));
+        assert_eq!(CellRef::new(9, 26).to_string(), "AA10");
+        for bad in ["A0", "A", "1", "", "1A", "A1B", "A-1", "Ä1"] {
+            assert!(matches!(cell(bad), Err(Error::BadAddress(_))), "{bad}");
+        }
+    }
+
+    #[test]
+    fn columns_round_trip() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+        assert_eq!(column_index("zz"), Some(701));
+        for col in 0..20_000 {
+            assert_eq!(column_index(&column_name(col)), Some(col));
+        }
+        assert_eq!(column_index(&column_name(usize::MAX)), Some(usize::MAX));
+    }
+
+    #[test]
+    fn columns_too_far_right() {
+        // 14 letters is more columns than a usize can count
+        assert_eq!(column_index("ZZZZZZZZZZZZZZ"), None);
+        assert_eq!(column_index(&"Z".repeat(40)), None);
+        assert!(cell("ZZZZZZZZZZZZZZ1").is_err());
+        assert!(cell(&format!("A{}", u128::MAX)).is_err());
+    }
+
+    #[test]
+    fn ranges() {
+        let range: Range = "C3:a1".parse().unwrap();
+        assert_eq!(range.to_string(), "A1:C3");
+        assert_eq!((range.rows(), range.cols()), (3, 3));
+        assert!(range.contains(CellRef::new(2, 0)));
+        assert!(!range.contains(CellRef::new(3, 0)));
+        let single: Range = " B2 ".parse().unwrap();
+        assert_eq!(single.cells().collect::<Vec<_>>(), [CellRef::new(1, 1)]);
+        assert!("A1:".parse::<Range>().is_err());
+    }
+}
@@ -0,0 +1,534 @@
+// The compact binary file for workbooks, for when the text one (see
+// workbook.rs) gets too big. It's written and read as a stream, cell by
+// cell, so a big workbook is never held in memory a second time as bytes.
+//
+//   "SHWB"        the magic bytes, so other files are turned away early
+//   version       u16, little endian
+//   sheets        how many
+//     name        a string
+//     cells       how many, only the ones with something in them
+//       row       rows down from the last cell's row
+//       col       from the last cell's column on the same row, from A
+//                 on a new one
+//       type      a byte, then the cell: an Int, the 8 bytes of a Float,
+//                 a string for Text and for a formula's source, a byte
+//                 for a Bool, the day number for a Date, or the symbol
+//                 and then the cents for Currency
+//     formats     how many, oldest first
+//       start     row and col, both from A1
+//       end       the same
+//       format    a string, the way it's written in a cell
+//   checksum      CRC-32 of everything before it, u32 little endian
+//
+// Counts, lengths and positions are varints (LEB128): 7 bits to a byte,
+// the high bit set on every byte but the last, so small numbers take one
+// byte. Ints (and day numbers and cents) are zigzagged first so small
+// negative numbers are short too. Strings are a length and then that many bytes of UTF-8.
+//
+// Formula values aren't stored, they're worked out again on loading.
+use super::{
+    workbook::{check_name, same_sheet},
+    CellRef, Date, Error, Format, Money, Range, Sheet, SpreadsheetCell, Workbook,
+};
+use std::{
+    fs::File,
+    io::{self, BufReader, BufWriter, Read, Write},
+};
+
+const MAGIC: &[u8; 4] = b"SHWB";
+const VERSION: u16 = 1;
+
+// A damaged file shouldn't be able to ask for a grid bigger than this,
+// the same limits as the big spreadsheet programs
+const MAX_ROWS: usize = 1 << 20;
+const MAX_COLS: usize = 1 << 14;
+
+// The type bytes
+const INT: u8 = 0;
+const FLOAT: u8 = 1;
+const TEXT: u8 = 2;
+const FORMULA: u8 = 3;
+const BOOL: u8 = 4;
+const DATE: u8 = 5;
+const CURRENCY: u8 = 6;
+
+// CRC-32 as used by zip and PNG, a byte at a time from a table of the
+// remainders of every byte value
+const CRC_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0xEDB8_8320,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// A reader or writer that keeps the checksum of what went through it
+struct Checked<T> {
+    inner: T,
+    crc: u32,
+}
+
+impl<T> Checked<T> {
+    fn new(inner: T) -> Checked<T> {
+        Checked {
+            inner,
+            crc: 0xFFFF_FFFF,
+        }
+    }
+
+    fn update(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.crc = CRC_TABLE[((self.crc ^ *byte as u32) & 0xFF) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    fn checksum(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl<W: Write> Write for Checked<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.update(&buf[..written]);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<R: Read> Read for Checked<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.update(&buf[..read]);
+        Ok(read)
+    }
+}
+
+fn damaged(message: impl Into<String>) -> Error {
+    Error::Damaged(message.into())
+}
+
+fn io_error(err: io::Error) -> Error {
+    match err.kind() {
+        io::ErrorKind::UnexpectedEof => damaged("the file ends too soon"),
+        _ => Error::Io(err.to_string()),
+    }
+}
+
+fn write_varint(w: &mut impl Write, mut n: u64) -> io::Result<()> {
+    loop {
+        let byte = (n & 0x7F) as u8;
+        n >>= 7;
+        if n == 0 {
+            return w.write_all(&[byte]);
+        }
+        w.write_all(&[byte | 0x80])?;
+    }
+}
+
+fn read_varint(r: &mut impl Read) -> Result<u64, Error> {
+    let mut n: u64 = 0;
+    for shift in (0..64).step_by(7) {
+        let mut byte = [0];
+        r.read_exact(&mut byte).map_err(io_error)?;
+        n |= ((byte[0] & 0x7F) as u64) << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(n);
+        }
+    }
+    Err(damaged("a number is too long"))
+}
+
+// A count or position that has to fit, `limit` and up is damage
+fn read_size(r: &mut impl Read, limit: usize, what: &str) -> Result<usize, Error> {
+    usize::try_from(read_varint(r)?)
+        .ok()
+        .filter(|n| *n < limit)
+        .ok_or_else(|| damaged(format!("{what} is too big")))
+}
+
+fn write_int(w: &mut impl Write, n: i64) -> io::Result<()> {
+    write_varint(w, ((n << 1) ^ (n >> 63)) as u64) // <-- zigzag: 0, -1, 1, -2, ...
+}
+
+fn read_int(r: &mut impl Read) -> Result<i64, Error> {
+    let n = read_varint(r)?;
+    Ok((n >> 1) as i64 ^ -((n & 1) as i64))
+}
+
+fn write_string(w: &mut impl Write, text: &str) -> io::Result<()> {
+    write_varint(w, text.len() as u64)?;
+    w.write_all(text.as_bytes())
+}
+
+fn read_string(r: &mut impl Read) -> Result<String, Error> {
+    let len = read_varint(r)?;
+    // read through take() so a damaged length can't make us allocate it
+    let mut bytes = Vec::new();
+    r.take(len).read_to_end(&mut bytes).map_err(io_error)?;
+    if bytes.len() as u64 != len {
+        return Err(damaged("the file ends too soon"));
+    }
+    String::from_utf8(bytes).map_err(|_| damaged("some text isn't UTF-8"))
+}
+
+fn write_cell(w: &mut impl Write, cell: &SpreadsheetCell) -> io::Result<()> {
+    match cell {
+        SpreadsheetCell::Int(n) => {
+            w.write_all(&[INT])?;
+            write_int(w, *n)
+        }
+        SpreadsheetCell::Float(n) => {
+            w.write_all(&[FLOAT])?;
+            w.write_all(&n.to_le_bytes())
+        }
+        SpreadsheetCell::Text(text) => {
+            w.write_all(&[TEXT])?;
+            write_string(w, text)
+        }
+        SpreadsheetCell::Bool(b) => w.write_all(&[BOOL, *b as u8]),
+        SpreadsheetCell::Date(date) => {
+            w.write_all(&[DATE])?;
+            write_int(w, date.serial())
+        }
+        SpreadsheetCell::Currency(money) => {
+            w.write_all(&[CURRENCY])?;
+            write_varint(w, money.symbol() as u64)?;
+            write_int(w, money.minor())
+        }
+        SpreadsheetCell::Formula(formula) => {
+            w.write_all(&[FORMULA])?;
+            write_string(w, formula.source())
+        }
+        SpreadsheetCell::Empty => unreachable!("empty cells aren't written"),
+    }
+}
+
+fn read_cell(r: &mut impl Read) -> Result<SpreadsheetCell, Error> {
+    let mut kind = [0];
+    r.read_exact(&mut kind).map_err(io_error)?;
+    match kind[0] {
+        INT => Ok(SpreadsheetCell::Int(read_int(r)?)),
+        FLOAT => {
+            let mut bytes = [0; 8];
+            r.read_exact(&mut bytes).map_err(io_error)?;
+            match f64::from_le_bytes(bytes) {
+                n if n.is_finite() => Ok(SpreadsheetCell::Float(n)),
+                _ => Err(damaged("a number isn't finite")),
+            }
+        }
+        TEXT => Ok(SpreadsheetCell::Text(read_string(r)?)),
+        BOOL => {
+            let mut b = [0];
+            r.read_exact(&mut b).map_err(io_error)?;
+            match b[0] {
+                0 | 1 => Ok(SpreadsheetCell::Bool(b[0] == 1)),
+                _ => Err(damaged("a boolean isn't 0 or 1")),
+            }
+        }
+        DATE => Date::from_serial(read_int(r)?)
+            .map(SpreadsheetCell::Date)
+            .ok_or_else(|| damaged("a date is out of range")),
+        CURRENCY => {
+            let symbol = u32::try_from(read_varint(r)?).ok().and_then(char::from_u32);
+            let symbol = symbol.ok_or_else(|| damaged("a currency symbol isn't a character"))?;
+            Ok(SpreadsheetCell::Currency(Money::new(read_int(r)?, symbol)))
+        }
+        FORMULA => {
+            let source = read_string(r)?;
+            SpreadsheetCell::formula(&source)
+                .map_err(|_| damaged(format!("'{source}' isn't a formula")))
+        }
+        kind => Err(damaged(format!("unknown cell type {kind}"))),
+    }
+}
+
+fn write_sheet(w: &mut impl Write, name: &str, sheet: &Sheet) -> io::Result<()> {
+    write_string(w, name)?;
+    // gone through twice, once to count them
+    write_varint(w, sheet.cells().count() as u64)?;
+    let mut last = CellRef::new(0, 0);
+    for (at, cell) in sheet.cells() {
+        let col = match at.row == last.row {
+            true => at.col - last.col,
+            false => at.col,
+        };
+        write_varint(w, (at.row - last.row) as u64)?;
+        write_varint(w, col as u64)?;
+        write_cell(w, cell)?;
+        last = at;
+    }
+    write_varint(w, sheet.formats().count() as u64)?;
+    for (range, format) in sheet.formats() {
+        for at in [range.start, range.end] {
+            write_varint(w, at.row as u64)?;
+            write_varint(w, at.col as u64)?;
+        }
+        write_string(w, &format.to_string())?;
+    }
+    Ok(())
+}
+
+fn read_at(r: &mut impl Read) -> Result<CellRef, Error> {
+    Ok(CellRef::new(
+        read_size(r, MAX_ROWS, "a row")?,
+        read_size(r, MAX_COLS, "a column")?,
+    ))
+}
+
+// The cells are put straight into the sheet as they're read
+fn read_sheet(r: &mut impl Read) -> Result<(String, Sheet), Error> {
+    let name = read_string(r)?;
+    let count = read_size(r, MAX_ROWS * MAX_COLS, "a sheet's cell count")?;
+    let mut last = CellRef::new(0, 0);
+    let mut failed = None;
+    let mut read_one = || -> Result<(CellRef, SpreadsheetCell), Error> {
+        let rows = read_size(r, MAX_ROWS, "a row")?;
+        let col = read_size(r, MAX_COLS, "a column")?;
+        let at = match rows {
+            0 => CellRef::new(last.row, last.col + col),
+            _ => CellRef::new(last.row + rows, col),
+        };
+        if at.row >= MAX_ROWS || at.col >= MAX_COLS {
+            return Err(damaged(format!("cell {at} is too far out")));
+        }
+        last = at;
+        Ok((at, read_cell(r)?))
+    };
+    let cells = (0..count).map_while(|_| match read_one() {
+        Ok(cell) => Some(cell),
+        Err(err) => {
+            failed = Some(err);
+            None
+        }
+    });
+    let mut sheet = Sheet::new();
+    sheet.set_many(cells);
+    if let Some(err) = failed {
+        return Err(err);
+    }
+    let count = read_size(r, MAX_ROWS * MAX_COLS, "a sheet's format count")?;
+    let mut formats = Vec::new();
+    for _ in 0..count {
+        let start = read_at(r)?;
+        let end = read_at(r)?;
+        let format = read_string(r)?;
+        let format: Format = format
+            .parse()
+            .map_err(|_| damaged(format!("'{format}' isn't a format")))?;
+        formats.push((Range::new(start, end), format));
+    }
+    sheet.set_formats(formats);
+    Ok((name, sheet))
+}
+
+impl Workbook {
+    pub fn write_to(&self, w: impl Write) -> Result<(), Error> {
+        let mut w = Checked::new(w);
+        let mut write = || -> io::Result<()> {
+            w.write_all(MAGIC)?;
+            w.write_all(&VERSION.to_le_bytes())?;
+            write_varint(&mut w, self.names().count() as u64)?;
+            for (name, sheet) in self.sheets() {
+                write_sheet(&mut w, name, sheet)?;
+            }
+            let checksum = w.checksum();
+            w.inner.write_all(&checksum.to_le_bytes())?;
+            w.flush()
+        };
+        write().map_err(|err| Error::Io(err.to_string()))
+    }
+
+    pub fn read_from(r: impl Read) -> Result<Workbook, Error> {
+        let mut r = Checked::new(r);
+        let mut magic = [0; 4];
+        let mut version = [0; 2];
+        let header = r
+            .read_exact(&mut magic)
+            .and_then(|_| r.read_exact(&mut version));
+        if header.is_err() || magic != *MAGIC {
+            return Err(damaged("this isn't a workbook file"));
+        }
+        let version = u16::from_le_bytes(version);
+        if version != VERSION {
+            return Err(Error::Version {
+                found: version,
+                supported: VERSION,
+            });
+        }
+
+        let count = read_size(&mut r, usize::MAX, "the sheet count")?;
+        let mut sheets: Vec<(String, Sheet)> = Vec::new();
+        for _ in 0..count {
+            let (name, sheet) = read_sheet(&mut r)?;
+            check_name(&name).map_err(|_| damaged(format!("'{name}' isn't a sheet name")))?;
+            if sheets.iter().any(|(other, _)| same_sheet(other, &name)) {
+                return Err(damaged(format!("there are two sheets called '{name}'")));
+            }
+            sheets.push((name, sheet));
+        }
+
+        let expected = r.checksum();
+        let mut checksum = [0; 4];
+        r.inner.read_exact(&mut checksum).map_err(io_error)?;
+        if u32::from_le_bytes(checksum) != expected {
+            return Err(damaged("the checksum doesn't match"));
+        }
+        if r.inner.read(&mut [0]).map_err(io_error)? != 0 {
+            return Err(damaged("there's more after the end of the workbook"));
+        }
+        Ok(Workbook::from_sheets(sheets))
+    }
+
+    pub fn load_binary(path: &str) -> Result<Workbook, Error> {
+        let file = File::open(path).map_err(|err| Error::Io(err.to_string()))?;
+        Workbook::read_from(BufReader::new(file))
+    }
+
+    pub fn save_binary(&self, path: &str) -> Result<(), Error> {
+        let file = File::create(path).map_err(|err| Error::Io(err.to_string()))?;
+        self.write_to(BufWriter::new(file))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::Value;
+
+    fn at(address: &str) -> CellRef {
+        address.parse().unwrap()
+    }
+
+    // A cell of every type on two sheets, with formats
+    fn workbook() -> Workbook {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Data", Sheet::new()).unwrap();
+        workbook.add_sheet("Sums für März", Sheet::new()).unwrap();
+        for (address, text) in [
+            ("A1", "-42"),
+            ("B1", "3.25"),
+            ("C1", "'hello, world"),
+            ("D1", "TRUE"),
+            ("E1", "2024-03-15"),
+            ("F1", "-$1,234.50"),
+            ("A300", "=A1*2"),
+            ("XFD1", "far"),
+        ] {
+            let cell = text.parse::<SpreadsheetCell>().unwrap();
+            workbook.set("Data", at(address), cell).unwrap();
+        }
+        let total = SpreadsheetCell::formula("=SUM(Data!A1:B1)").unwrap();
+        workbook.set("Sums für März", at("A1"), total).unwrap();
+        let format = "DD/MM/YYYY".parse().unwrap();
+        workbook.set_format("Data", at("E1"), format).unwrap();
+        let money = "#,##0.00".parse().unwrap();
+        let column = "F1:F1048576".parse().unwrap();
+        workbook.format_range("Data", column, money).unwrap();
+        workbook
+    }
+
+    fn bytes(workbook: &Workbook) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        workbook.write_to(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn crc_check_value() {
+        let mut checked = Checked::new(());
+        checked.update(b"123456789");
+        assert_eq!(checked.checksum(), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let workbook = workbook();
+        let read = Workbook::read_from(bytes(&workbook).as_slice()).unwrap();
+        assert_eq!(read.to_text(), workbook.to_text());
+        assert_eq!(read.value("Data", at("A300")), Ok(Value::Int(-84)));
+        assert_eq!(
+            read.value("SUMS FÜR MÄRZ", at("A1")),
+            Ok(Value::Float(-38.75))
+        );
+        assert_eq!(read.sheet("Data").unwrap().display(at("E1")), "15/03/2024");
+    }
+
+    #[test]
+    fn damage_is_caught() {
+        let good = bytes(&workbook());
+        // every single changed byte, even in the checksum itself
+        for i in 0..good.len() {
+            let mut bad = good.clone();
+            bad[i] ^= 0x10;
+            assert!(
+                matches!(
+                    Workbook::read_from(bad.as_slice()),
+                    Err(Error::Damaged(_) | Error::Version { .. })
+                ),
+                "byte {i}"
+            );
+        }
+        for len in 0..good.len() {
+            assert!(Workbook::read_from(&good[..len]).is_err(), "{len} bytes");
+        }
+        let mut longer = good.clone();
+        longer.push(0);
+        assert!(Workbook::read_from(longer.as_slice()).is_err());
+        assert_eq!(
+            Workbook::read_from(&b"PK\x03\x04 not a workbook"[..]),
+            Err(damaged("this isn't a workbook file"))
+        );
+    }
+
+    // A date out past year 9999 is damage, not something to show
+    #[test]
+    fn dates_out_of_range_are_damage() {
+        for serial in [2958466, i64::MAX, i64::MIN] {
+            let mut bytes = vec![DATE];
+            write_int(&mut bytes, serial).unwrap();
+            assert_eq!(
+                read_cell(&mut bytes.as_slice()),
+                Err(damaged("a date is out of range")),
+                "{serial}"
+            );
+        }
+        let mut bytes = vec![DATE];
+        write_int(&mut bytes, 2958465).unwrap();
+        assert_eq!(
+            read_cell(&mut bytes.as_slice()),
+            Ok(SpreadsheetCell::Date(Date::new(9999, 12, 31).unwrap()))
+        );
+    }
+
+    #[test]
+    fn other_versions_are_turned_away() {
+        let mut bytes = bytes(&workbook());
+        for (version, message) in [(2u16, "from a newer program"), (0, "too old")] {
+            bytes[4..6].copy_from_slice(&version.to_le_bytes());
+            let err = Workbook::read_from(bytes.as_slice()).unwrap_err();
+            assert_eq!(
+                err,
+                Error::Version {
+                    found: version,
+                    supported: VERSION
+                }
+            );
+            assert!(err.to_string().contains(message), "{err}");
+        }
+    }
+}
@@ -0,0 +1,342 @@
+// The vector-of-enums idea from src/main.rs: one cell type that can hold
+// any of the kinds of data a spreadsheet needs.
+use super::{date::Date, format::ungroup, money::Money, value::Number, Error, Formula, Value};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum SpreadsheetCell {
+    #[default]
+    Empty,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+    Date(Date),
+    Currency(Money),
+    Formula(Formula),
+}
+
+impl SpreadsheetCell {
+    // A formula cell from text like "=A1*2", its value is worked out once
+    // it's put in a sheet
+    pub fn formula(source: &str) -> Result<SpreadsheetCell, Error> {
+        Formula::new(source).map(SpreadsheetCell::Formula)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        matches!(self, SpreadsheetCell::Empty)
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            SpreadsheetCell::Empty => "empty",
+            SpreadsheetCell::Int(_) => "int",
+            SpreadsheetCell::Float(_) => "float",
+            SpreadsheetCell::Text(_) => "text",
+            SpreadsheetCell::Bool(_) => "bool",
+            SpreadsheetCell::Date(_) => "date",
+            SpreadsheetCell::Currency(_) => "currency",
+            SpreadsheetCell::Formula(_) => "formula",
+        }
+    }
+
+    // Numbers are right aligned when printed in a table
+    pub fn is_number(&self) -> bool {
+        self.value().is_number()
+    }
+
+    // What the cell holds, or for a formula what it computed. Dates are
+    // their day number, that's how formulas use them.
+    pub fn value(&self) -> Value {
+        match self {
+            SpreadsheetCell::Empty => Value::Empty,
+            SpreadsheetCell::Int(n) => Value::Int(*n),
+            SpreadsheetCell::Float(n) => Value::Float(*n),
+            SpreadsheetCell::Text(s) => Value::Text(s.clone()),
+            SpreadsheetCell::Bool(b) => Value::Bool(*b),
+            SpreadsheetCell::Date(date) => Value::Int(date.serial()),
+            SpreadsheetCell::Currency(money) => Value::Money(*money),
+            SpreadsheetCell::Formula(formula) => formula.value().clone(),
+        }
+    }
+}
+
+impl fmt::Display for SpreadsheetCell {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SpreadsheetCell::Empty => Ok(()),
+            SpreadsheetCell::Int(n) => write!(f, "{n}"),
+            SpreadsheetCell::Float(n) => write!(f, "{n}"),
+            SpreadsheetCell::Text(s) => write!(f, "{s}"),
+            SpreadsheetCell::Bool(b) => write!(f, "{}", Value::Bool(*b)),
+            SpreadsheetCell::Date(date) => write!(f, "{date}"),
+            SpreadsheetCell::Currency(money) => write!(f, "{money}"),
+            SpreadsheetCell::Formula(formula) => write!(f, "{formula}"),
+        }
+    }
+}
+
+impl From<i64> for SpreadsheetCell {
+    fn from(n: i64) -> SpreadsheetCell {
+        SpreadsheetCell::Int(n)
+    }
+}
+
+impl From<i32> for SpreadsheetCell {
+    fn from(n: i32) -> SpreadsheetCell {
+        SpreadsheetCell::Int(n.into())
+    }
+}
+
+impl From<f64> for SpreadsheetCell {
+    fn from(n: f64) -> SpreadsheetCell {
+        SpreadsheetCell::Float(n)
+    }
+}
+
+impl From<bool> for SpreadsheetCell {
+    fn from(b: bool) -> SpreadsheetCell {
+        SpreadsheetCell::Bool(b)
+    }
+}
+
+impl From<Date> for SpreadsheetCell {
+    fn from(date: Date) -> SpreadsheetCell {
+        SpreadsheetCell::Date(date)
+    }
+}
+
+impl From<Money> for SpreadsheetCell {
+    fn from(money: Money) -> SpreadsheetCell {
+        SpreadsheetCell::Currency(money)
+    }
+}
+
+impl From<&str> for SpreadsheetCell {
+    fn from(s: &str) -> SpreadsheetCell {
+        SpreadsheetCell::Text(s.to_owned())
+    }
+}
+
+impl From<String> for SpreadsheetCell {
+    fn from(s: String) -> SpreadsheetCell {
+        SpreadsheetCell::Text(s)
+    }
+}
+
+// A number as it's typed, maybe with thousands separators
+fn number(input: &str) -> Option<Number> {
+    let (sign, digits) = match input.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", input),
+    };
+    let number = format!("{sign}{}", ungroup(digits)?);
+    if let Ok(n) = number.parse() {
+        return Some(Number::Int(n));
+    }
+    // "inf" and "NaN" parse as floats but aren't numbers to anyone
+    number
+        .parse::<f64>()
+        .ok()
+        .filter(|n| n.is_finite())
+        .map(Number::Float)
+}
+
+// What someone typed into a cell: "=..." is a formula, TRUE and FALSE are
+// booleans, "1,234.5" and "12%" are numbers (the second is 0.12), "$9.99"
+// is money and "2024-03-15" a date. Anything else is text. A leading
+// apostrophe keeps text that looks like something else as text, "'42" is
+// the text 42.
+impl FromStr for SpreadsheetCell {
+    type Err = Error;
+
+    fn from_str(input: &str) -> Result<SpreadsheetCell, Error> {
+        let input = input.trim();
+        if let Some(text) = input.strip_prefix('\'') {
+            return Ok(SpreadsheetCell::Text(text.to_owned()));
+        }
+        if input.is_empty() {
+            return Ok(SpreadsheetCell::Empty);
+        }
+        if input.starts_with('=') {
+            return SpreadsheetCell::formula(input);
+        }
+        if input.eq_ignore_ascii_case("true") || input.eq_ignore_ascii_case("false") {
+            return Ok(SpreadsheetCell::Bool(input.eq_ignore_ascii_case("true")));
+        }
+        if let Some(n) = number(input) {
+            return Ok(match n {
+                Number::Int(n) => SpreadsheetCell::Int(n),
+                Number::Float(n) => SpreadsheetCell::Float(n),
+                Number::Money(money) => SpreadsheetCell::Currency(money),
+            });
+        }
+        if let Some(n) = input.strip_suffix('%').and_then(number) {
+            return Ok(SpreadsheetCell::Float(n.as_f64() / 100.0));
+        }
+        if let Ok(money) = input.parse() {
+            return Ok(SpreadsheetCell::Currency(money));
+        }
+        if let Ok(date) = input.parse() {
+            return Ok(SpreadsheetCell::Date(date));
+        }
+        Ok(SpreadsheetCell::Text(input.to_owned()))
+    }
+}
+
+// A value computed by a formula, as a plain cell. There are no error
+// cells, so those become their text.
+impl From<Value> for SpreadsheetCell {
+    fn from(value: Value) -> SpreadsheetCell {
+        match value {
+            Value::Empty => SpreadsheetCell::Empty,
+            Value::Int(n) => SpreadsheetCell::Int(n),
+            Value::Float(n) => SpreadsheetCell::Float(n),
+            Value::Text(s) => SpreadsheetCell::Text(s),
+            Value::Bool(b) => SpreadsheetCell::Bool(b),
+            Value::Money(money) => SpreadsheetCell::Currency(money),
+            value => SpreadsheetCell::Text(value.to_string()),
+        }
+    }
+}
+
+fn mismatch(expected: &'static str, cell: &SpreadsheetCell) -> Error {
+    Error::TypeMismatch {
+        expected,
+        found: cell.type_name(),
+    }
+}
+
+// The typed getters: an Int can be read as a float, nothing else converts.
+// Formulas are read by what they computed.
+impl TryFrom<&SpreadsheetCell> for i64 {
+    type Error = Error;
+
+    fn try_from(cell: &SpreadsheetCell) -> Result<i64, Error> {
+        match cell.value() {
+            Value::Int(n) => Ok(n),
+            _ => Err(mismatch("int", cell)),
+        }
+    }
+}
+
+impl TryFrom<&SpreadsheetCell> for f64 {
+    type Error = Error;
+
+    fn try_from(cell: &SpreadsheetCell) -> Result<f64, Error> {
+        match cell.value() {
+            Value::Int(n) => Ok(n as f64),
+            Value::Float(n) => Ok(n),
+            Value::Money(money) => Ok(money.to_f64()),
+            _ => Err(mismatch("float", cell)),
+        }
+    }
+}
+
+impl TryFrom<&SpreadsheetCell> for String {
+    type Error = Error;
+
+    fn try_from(cell: &SpreadsheetCell) -> Result<String, Error> {
+        match cell.value() {
+            Value::Text(s) => Ok(s),
+            _ => Err(mismatch("text", cell)),
+        }
+    }
+}
+
+impl TryFrom<&SpreadsheetCell> for bool {
+    type Error = Error;
+
+    fn try_from(cell: &SpreadsheetCell) -> Result<bool, Error> {
+        match cell.value() {
+            Value::Bool(b) => Ok(b),
+            _ => Err(mismatch("bool", cell)),
+        }
+    }
+}
+
+// Dates are only read from cells that hold them, a formula's answer is a
+// plain number. Money is read from a formula that worked some out too.
+impl TryFrom<&SpreadsheetCell> for Date {
+    type Error = Error;
+
+    fn try_from(cell: &SpreadsheetCell) -> Result<Date, Error> {
+        match cell {
+            SpreadsheetCell::Date(date) => Ok(*date),
+            _ => Err(mismatch("date", cell)),
+        }
+    }
+}
+
+impl TryFrom<&SpreadsheetCell> for Money {
+    type Error = Error;
+
+    fn try_from(cell: &SpreadsheetCell) -> Result<Money, Error> {
+        match cell.value() {
+            Value::Money(money) => Ok(money),
+            _ => Err(mismatch("currency", cell)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn typed(input: &str) -> SpreadsheetCell {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn numbers_are_inferred() {
+        assert_eq!(typed("42"), SpreadsheetCell::Int(42));
+        assert_eq!(typed("  -7 "), SpreadsheetCell::Int(-7));
+        assert_eq!(typed("2.5"), SpreadsheetCell::Float(2.5));
+        assert_eq!(typed("1,234"), SpreadsheetCell::Int(1234));
+        assert_eq!(typed("1,234.5"), SpreadsheetCell::Float(1234.5));
+        assert_eq!(typed("-1,234,567"), SpreadsheetCell::Int(-1234567));
+        assert_eq!(typed("12%"), SpreadsheetCell::Float(0.12));
+        assert_eq!(typed("-50%"), SpreadsheetCell::Float(-0.5));
+        // commas in the wrong places, and floats nobody would type
+        for text in ["12,34", "1,234,5", "1.2,3", "inf", "NaN", "%"] {
+            assert_eq!(typed(text), SpreadsheetCell::Text(text.to_owned()));
+        }
+    }
+
+    #[test]
+    fn money_dates_and_booleans() {
+        assert_eq!(
+            typed("$9.99"),
+            SpreadsheetCell::Currency(Money::new(999, '$'))
+        );
+        assert_eq!(
+            typed("-$1,234.5"),
+            SpreadsheetCell::Currency(Money::new(-123450, '$'))
+        );
+        assert_eq!(
+            typed("2024-03-15"),
+            SpreadsheetCell::Date(Date::new(2024, 3, 15).unwrap())
+        );
+        assert_eq!(
+            typed("2024/2/29"),
+            SpreadsheetCell::Date(Date::new(2024, 2, 29).unwrap())
+        );
+        assert_eq!(
+            typed("2023-02-29"),
+            SpreadsheetCell::Text(String::from("2023-02-29"))
+        );
+        assert_eq!(typed("TRUE"), SpreadsheetCell::Bool(true));
+        assert_eq!(typed("false"), SpreadsheetCell::Bool(false));
+    }
+
+    #[test]
+    fn apostrophes_keep_text() {
+        assert_eq!(typed("'42"), SpreadsheetCell::Text(String::from("42")));
+        assert_eq!(typed("'TRUE"), SpreadsheetCell::Text(String::from("TRUE")));
+        assert_eq!(typed("'=1+2"), SpreadsheetCell::Text(String::from("=1+2")));
+        assert_eq!(typed("''"), SpreadsheetCell::Text(String::from("'")));
+        assert_eq!(typed("   "), SpreadsheetCell::Empty);
+        assert_eq!(typed("hello"), SpreadsheetCell::Text(String::from("hello")));
+        assert!(matches!(typed("=1+2"), SpreadsheetCell::Formula(..)));
+    }
+}
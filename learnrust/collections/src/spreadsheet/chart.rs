@@ -0,0 +1,732 @@
+// Charts of a range of the sheet, drawn as text for the terminal or as an
+// SVG file. The range is laid out the usual way:
+//
+//        A        B       C
+//   1  Month    Sales   Costs     <-- names, when the first row is all text
+//   2  Jan        120    -30.5
+//   3  Feb         80     45
+//
+// The first column labels the rows (for a scatter chart it's the x value)
+// and every other column is a series. A range of one column is a single
+// series labelled by row number. Ints, Floats, money and dates are all
+// numbers here, anything else in a series is a gap.
+//
+// The value axis is scaled to fit the numbers with round steps (1, 2 or 5
+// times a power of ten). Bars always start from zero, so a chart with
+// negative values has them going down from a zero line.
+use super::{column_name, CellRef, Error, Range, Sheet, SpreadsheetCell, Value};
+use std::{fmt, fs, str::FromStr};
+
+// Each series is drawn with one of these, in turn
+const GLYPHS: [char; 8] = ['#', '*', '+', 'o', 'x', '=', '@', '%'];
+const COLORS: [&str; 8] = [
+    "#4e79a7", "#f28e2b", "#e15759", "#76b7b2", "#59a14f", "#edc948", "#b07aa1", "#ff9da7",
+];
+
+// About how many steps an axis is split into
+const TICKS: usize = 5;
+
+// Numbers further out than this aren't charted, the axis around them would
+// overflow to infinity
+const LARGEST: f64 = 1e300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChartKind {
+    Bar,
+    Line,
+    Scatter,
+}
+
+impl fmt::Display for ChartKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            ChartKind::Bar => "bar",
+            ChartKind::Line => "line",
+            ChartKind::Scatter => "scatter",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl FromStr for ChartKind {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<ChartKind, Error> {
+        match s.to_lowercase().as_str() {
+            "bar" => Ok(ChartKind::Bar),
+            "line" => Ok(ChartKind::Line),
+            "scatter" => Ok(ChartKind::Scatter),
+            _ => Err(Error::Chart(format!(
+                "'{s}' isn't a kind of chart, try bar, line or scatter"
+            ))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Series {
+    pub name: String,
+    pub values: Vec<Option<f64>>, // <-- one for each row, None is a gap
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chart {
+    pub kind: ChartKind,
+    pub title: String,
+    pub x_label: String,
+    pub labels: Vec<String>, // <-- what's under each row's bars or point
+    pub xs: Vec<f64>,        // <-- each row's x value, for a scatter chart
+    pub series: Vec<Series>,
+}
+
+// An axis from `lo` to `hi` in steps of `step`, all of them round numbers
+#[derive(Debug, Clone, Copy)]
+struct Axis {
+    lo: f64,
+    hi: f64,
+    step: f64,
+}
+
+// The closest of 1, 2, 5 and 10 times a power of ten
+fn nice(x: f64, round: bool) -> f64 {
+    let power = 10f64.powf(x.log10().floor());
+    let fraction = x / power;
+    let nice = match round {
+        true if fraction < 1.5 => 1.0,
+        true if fraction < 3.0 => 2.0,
+        true if fraction < 7.0 => 5.0,
+        false if fraction <= 1.0 => 1.0,
+        false if fraction <= 2.0 => 2.0,
+        false if fraction <= 5.0 => 5.0,
+        _ => 10.0,
+    };
+    nice * power
+}
+
+impl Axis {
+    fn new(min: f64, max: f64, zero: bool) -> Axis {
+        // Sheet::chart turns bigger numbers away, this keeps a chart put
+        // together some other way from drawing with infinities
+        let (min, max) = (min.clamp(-LARGEST, LARGEST), max.clamp(-LARGEST, LARGEST));
+        let (mut min, mut max) = match zero {
+            true => (min.min(0.0), max.max(0.0)),
+            false => (min, max),
+        };
+        if min == max {
+            // a single value gets some room around it
+            let room = if min == 0.0 { 1.0 } else { min.abs() / 2.0 };
+            min -= room;
+            max += room;
+        }
+        let step = nice(nice(max - min, false) / (TICKS - 1) as f64, true);
+        Axis {
+            lo: (min / step).floor() * step,
+            hi: (max / step).ceil() * step,
+            step,
+        }
+    }
+
+    fn ticks(&self) -> Vec<f64> {
+        let count = ((self.hi - self.lo) / self.step).round() as usize;
+        (0..=count)
+            .map(|i| self.lo + i as f64 * self.step)
+            .collect()
+    }
+
+    // How far along the axis, 0 at the low end and 1 at the high end
+    fn fraction(&self, value: f64) -> f64 {
+        (value - self.lo) / (self.hi - self.lo)
+    }
+
+    // As many decimals as the step needs and no more
+    fn label(&self, value: f64) -> String {
+        // past a quadrillion the digits are mostly noise, an exponent is
+        // shorter
+        if self.step >= 1e15 {
+            return format!("{value:e}");
+        }
+        let decimals = (-self.step.log10()).ceil().max(0.0) as usize;
+        let text = format!("{value:.decimals$}");
+        match text
+            .trim_start_matches('-')
+            .chars()
+            .all(|c| c == '0' || c == '.')
+        {
+            true => text.trim_start_matches('-').to_owned(), // <-- no "-0"
+            false => text,
+        }
+    }
+}
+
+fn number(value: Value) -> Option<f64> {
+    match value {
+        Value::Int(n) => Some(n as f64),
+        Value::Float(n) => Some(n),
+        Value::Money(money) => Some(money.to_f64()),
+        _ => None,
+    }
+}
+
+fn shorten(text: &str, width: usize) -> String {
+    match text.chars().count() > width {
+        true => text.chars().take(width).collect(),
+        false => text.to_owned(),
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// Characters drawn on a grid, row 0 at the top
+struct Canvas {
+    cells: Vec<Vec<char>>,
+}
+
+impl Canvas {
+    fn new(width: usize, height: usize) -> Canvas {
+        Canvas {
+            cells: vec![vec![' '; width]; height],
+        }
+    }
+
+    fn put(&mut self, row: usize, col: usize, c: char) {
+        if let Some(cell) = self.cells.get_mut(row).and_then(|r| r.get_mut(col)) {
+            *cell = c;
+        }
+    }
+
+    // A line of dots between two points, under whatever is drawn later
+    fn line(&mut self, from: (usize, usize), to: (usize, usize)) {
+        let steps = from.0.abs_diff(to.0).max(from.1.abs_diff(to.1)).max(1);
+        for i in 0..=steps {
+            let t = i as f64 / steps as f64;
+            let row = from.0 as f64 + (to.0 as f64 - from.0 as f64) * t;
+            let col = from.1 as f64 + (to.1 as f64 - from.1 as f64) * t;
+            let (row, col) = (row.round() as usize, col.round() as usize);
+            if self.cells[row][col] == ' ' || self.cells[row][col] == '-' {
+                self.put(row, col, '.');
+            }
+        }
+    }
+}
+
+impl Chart {
+    fn value_axis(&self) -> Axis {
+        let values = self.series.iter().flat_map(|s| s.values.iter().flatten());
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(*v), hi.max(*v))
+        });
+        Axis::new(min, max, self.kind == ChartKind::Bar)
+    }
+
+    fn x_axis(&self) -> Axis {
+        let (min, max) = self
+            .xs
+            .iter()
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), x| {
+                (lo.min(*x), hi.max(*x))
+            });
+        Axis::new(min, max, false)
+    }
+
+    // Where each row goes across the plot, as a fraction of its width:
+    // the middle of its slot for bars and lines, its x for a scatter
+    fn positions(&self) -> Vec<f64> {
+        let rows = self.labels.len();
+        match self.kind {
+            ChartKind::Scatter => {
+                let axis = self.x_axis();
+                self.xs.iter().map(|x| axis.fraction(*x)).collect()
+            }
+            _ => (0..rows).map(|i| (i as f64 + 0.5) / rows as f64).collect(),
+        }
+    }
+
+    // The chart as text, with a plot area about `width` by `height`
+    // characters plus the axis labels and legend around it
+    pub fn to_ascii(&self, width: usize, height: usize) -> String {
+        let axis = self.value_axis();
+        let rows = self.labels.len();
+        let series = self.series.len();
+        // bars need a column each, and a gap between rows
+        let width = match self.kind {
+            ChartKind::Bar => width.max(rows * (series + 1)),
+            _ => width.max(rows),
+        };
+        // room for a label on every tick
+        let height = height.max(axis.ticks().len());
+        let row_of = |value: f64| {
+            let fraction = axis.fraction(value.clamp(axis.lo, axis.hi));
+            ((1.0 - fraction) * (height - 1) as f64).round() as usize
+        };
+        let col_of = |fraction: f64| (fraction * (width - 1) as f64).round() as usize;
+
+        let mut canvas = Canvas::new(width, height);
+        if axis.lo < 0.0 && axis.hi > 0.0 {
+            let zero = row_of(0.0);
+            for col in 0..width {
+                canvas.put(zero, col, '-');
+            }
+        }
+        let positions = self.positions();
+        match self.kind {
+            ChartKind::Bar => {
+                let slot = width / rows;
+                let bar = ((slot - 1) / series).max(1);
+                let zero = row_of(0.0);
+                for (s, line) in self.series.iter().enumerate() {
+                    for (i, value) in line.values.iter().enumerate() {
+                        let Some(value) = value else { continue };
+                        let top = row_of(*value);
+                        let start = i * slot + (slot - bar * series) / 2 + s * bar;
+                        for row in top.min(zero)..=top.max(zero) {
+                            for col in start..start + bar {
+                                canvas.put(row, col, GLYPHS[s % GLYPHS.len()]);
+                            }
+                        }
+                    }
+                }
+            }
+            _ => {
+                for (s, line) in self.series.iter().enumerate() {
+                    let points: Vec<Option<(usize, usize)>> = line
+                        .values
+                        .iter()
+                        .zip(&positions)
+                        .map(|(value, x)| value.map(|value| (row_of(value), col_of(*x))))
+                        .collect();
+                    if self.kind == ChartKind::Line {
+                        for pair in points.windows(2) {
+                            if let [Some(from), Some(to)] = pair {
+                                canvas.line(*from, *to);
+                            }
+                        }
+                    }
+                    for (row, col) in points.into_iter().flatten() {
+                        canvas.put(row, col, GLYPHS[s % GLYPHS.len()]);
+                    }
+                }
+            }
+        }
+
+        // the value labels down the left, at the rows the ticks land on
+        let mut labels = vec![String::new(); height];
+        for tick in axis.ticks() {
+            labels[row_of(tick)] = axis.label(tick);
+        }
+        let label_width = labels.iter().map(|l| l.len()).max().unwrap_or(0);
+        let indent = " ".repeat(label_width + 1);
+
+        let mut out = String::new();
+        let total = label_width + 2 + width;
+        out.push_str(&format!("{:^total$}\n", shorten(&self.title, total)));
+        for (label, row) in labels.iter().zip(&canvas.cells) {
+            let row: String = row.iter().collect();
+            out.push_str(&format!("{label:>label_width$} |{}\n", row.trim_end()));
+        }
+        out.push_str(&format!("{indent}+{}\n", "-".repeat(width)));
+
+        // and across the bottom, the rows' labels or the x ticks
+        let mut bottom = vec![' '; width + 1];
+        let mut write = |at: usize, text: &str| {
+            let at = at.min((width + 1).saturating_sub(text.chars().count()));
+            let free = bottom[at.saturating_sub(1)..(at + text.chars().count() + 1).min(width + 1)]
+                .iter()
+                .all(|c| *c == ' ');
+            if free {
+                for (i, c) in text.chars().enumerate() {
+                    bottom[at + i] = c;
+                }
+            }
+        };
+        match self.kind {
+            ChartKind::Scatter => {
+                let x_axis = self.x_axis();
+                for tick in x_axis.ticks() {
+                    let text = x_axis.label(tick);
+                    let at = col_of(x_axis.fraction(tick)) + 1;
+                    write(at.saturating_sub(text.len() / 2), &text);
+                }
+            }
+            _ => {
+                let slot = width / rows;
+                for (i, label) in self.labels.iter().enumerate() {
+                    let text = shorten(label, slot.saturating_sub(1).max(1));
+                    let at = i * slot + 1 + (slot.saturating_sub(text.chars().count())) / 2;
+                    write(at, &text);
+                }
+            }
+        }
+        let bottom: String = bottom.into_iter().collect();
+        out.push_str(&format!("{indent}{}\n", bottom.trim_end()));
+        out.push_str(&format!(
+            "{indent}{:^width$}\n",
+            shorten(&self.x_label, width)
+        ));
+
+        let legend: Vec<String> = self
+            .series
+            .iter()
+            .enumerate()
+            .map(|(s, line)| format!("{} {}", GLYPHS[s % GLYPHS.len()], line.name))
+            .collect();
+        out.push_str(&format!("{indent}{}", legend.join("   ")));
+        out
+    }
+
+    // The chart as a standalone SVG image, `width` by `height` pixels
+    pub fn to_svg(&self, width: usize, height: usize) -> String {
+        let (width, height) = (width.max(200) as f64, height.max(150) as f64);
+        let (left, right, top, bottom) = (64.0, 24.0, 40.0, 72.0);
+        let (plot_w, plot_h) = (width - left - right, height - top - bottom);
+        let axis = self.value_axis();
+        let y_of = |value: f64| top + (1.0 - axis.fraction(value.clamp(axis.lo, axis.hi))) * plot_h;
+        let x_of = |fraction: f64| left + fraction * plot_w;
+        let text = |x: f64, y: f64, anchor: &str, size: u32, content: &str| {
+            format!(
+                "<text x=\"{x:.1}\" y=\"{y:.1}\" text-anchor=\"{anchor}\" font-size=\"{size}\">{}</text>\n",
+                escape(content)
+            )
+        };
+
+        let mut out = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{width}\" height=\"{height}\" \
+             viewBox=\"0 0 {width} {height}\" font-family=\"sans-serif\">\n\
+             <rect width=\"100%\" height=\"100%\" fill=\"white\"/>\n"
+        );
+        out.push_str(&text(width / 2.0, 24.0, "middle", 16, &self.title));
+
+        // grid lines and the value labels
+        for tick in axis.ticks() {
+            let y = y_of(tick);
+            let stroke = if tick == 0.0 { "#888" } else { "#e0e0e0" };
+            out.push_str(&format!(
+                "<line x1=\"{left}\" y1=\"{y:.1}\" x2=\"{:.1}\" y2=\"{y:.1}\" stroke=\"{stroke}\"/>\n",
+                left + plot_w
+            ));
+            out.push_str(&text(left - 6.0, y + 4.0, "end", 11, &axis.label(tick)));
+        }
+        out.push_str(&format!(
+            "<line x1=\"{left}\" y1=\"{top}\" x2=\"{left}\" y2=\"{:.1}\" stroke=\"#444\"/>\n",
+            top + plot_h
+        ));
+        if self.series.len() == 1 {
+            let (x, y) = (16.0, top + plot_h / 2.0);
+            out.push_str(&format!(
+                "<text x=\"{x}\" y=\"{y:.1}\" text-anchor=\"middle\" font-size=\"12\" \
+                 transform=\"rotate(-90 {x} {y:.1})\">{}</text>\n",
+                escape(&self.series[0].name)
+            ));
+        }
+
+        let rows = self.labels.len();
+        let positions = self.positions();
+        match self.kind {
+            ChartKind::Bar => {
+                let slot = plot_w / rows as f64;
+                let bar = slot * 0.8 / self.series.len() as f64;
+                for (s, line) in self.series.iter().enumerate() {
+                    for (i, value) in line.values.iter().enumerate() {
+                        let Some(value) = value else { continue };
+                        let x = left + i as f64 * slot + slot * 0.1 + s as f64 * bar;
+                        let (y0, y1) = (y_of(*value), y_of(0.0));
+                        out.push_str(&format!(
+                            "<rect x=\"{x:.1}\" y=\"{:.1}\" width=\"{bar:.1}\" height=\"{:.1}\" fill=\"{}\"/>\n",
+                            y0.min(y1),
+                            (y1 - y0).abs(),
+                            COLORS[s % COLORS.len()]
+                        ));
+                    }
+                }
+            }
+            _ => {
+                for (s, line) in self.series.iter().enumerate() {
+                    let color = COLORS[s % COLORS.len()];
+                    let points: Vec<Option<(f64, f64)>> = line
+                        .values
+                        .iter()
+                        .zip(&positions)
+                        .map(|(value, x)| value.map(|value| (x_of(*x), y_of(value))))
+                        .collect();
+                    if self.kind == ChartKind::Line {
+                        // one line for each run of points, gaps break it
+                        for run in points.split(|point| point.is_none()) {
+                            let run: Vec<String> = run
+                                .iter()
+                                .flatten()
+                                .map(|(x, y)| format!("{x:.1},{y:.1}"))
+                                .collect();
+                            if run.len() > 1 {
+                                out.push_str(&format!(
+                                    "<polyline points=\"{}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"2\"/>\n",
+                                    run.join(" ")
+                                ));
+                            }
+                        }
+                    }
+                    for (x, y) in points.into_iter().flatten() {
+                        out.push_str(&format!(
+                            "<circle cx=\"{x:.1}\" cy=\"{y:.1}\" r=\"3\" fill=\"{color}\"/>\n"
+                        ));
+                    }
+                }
+            }
+        }
+
+        // the x axis along the bottom, or along zero when there are
+        // negative values
+        let base = y_of(axis.lo.max(0.0).min(axis.hi));
+        out.push_str(&format!(
+            "<line x1=\"{left}\" y1=\"{base:.1}\" x2=\"{:.1}\" y2=\"{base:.1}\" stroke=\"#444\"/>\n",
+            left + plot_w
+        ));
+        let label_y = top + plot_h + 16.0;
+        match self.kind {
+            ChartKind::Scatter => {
+                let x_axis = self.x_axis();
+                for tick in x_axis.ticks() {
+                    let x = x_of(x_axis.fraction(tick));
+                    out.push_str(&text(x, label_y, "middle", 11, &x_axis.label(tick)));
+                }
+            }
+            _ => {
+                // every label that fits, about 7 pixels to a character
+                let slot = plot_w / rows as f64;
+                let every = (60.0 / slot).ceil().max(1.0) as usize;
+                for (i, label) in self.labels.iter().enumerate().step_by(every) {
+                    let fits = ((slot * every as f64) / 7.0).max(3.0) as usize;
+                    let x = x_of(positions[i]);
+                    out.push_str(&text(x, label_y, "middle", 11, &shorten(label, fits)));
+                }
+            }
+        }
+        out.push_str(&text(
+            left + plot_w / 2.0,
+            label_y + 18.0,
+            "middle",
+            12,
+            &self.x_label,
+        ));
+
+        // the legend, in a row under everything
+        let mut x = left;
+        let y = height - 14.0;
+        for (s, line) in self.series.iter().enumerate() {
+            out.push_str(&format!(
+                "<rect x=\"{x:.1}\" y=\"{:.1}\" width=\"10\" height=\"10\" fill=\"{}\"/>\n",
+                y - 9.0,
+                COLORS[s % COLORS.len()]
+            ));
+            out.push_str(&text(x + 14.0, y, "start", 11, &line.name));
+            x += 14.0 + line.name.chars().count() as f64 * 7.0 + 16.0;
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    pub fn save_svg(&self, path: &str, width: usize, height: usize) -> Result<(), Error> {
+        fs::write(path, self.to_svg(width, height)).map_err(|err| Error::Io(err.to_string()))
+    }
+}
+
+impl Sheet {
+    // A chart of a range, see the top of this file for how it's read
+    pub fn chart(&self, kind: ChartKind, range: Range) -> Result<Chart, Error> {
+        let first_row = (range.start.col..=range.end.col)
+            .map(|col| self.get(CellRef::new(range.start.row, col)))
+            .filter(|cell| !cell.is_empty());
+        let header = range.rows() > 1
+            && first_row.clone().next().is_some()
+            && first_row
+                .clone()
+                .all(|cell| matches!(cell, SpreadsheetCell::Text(_)));
+        let name = |col: usize| match header {
+            true => self.display(CellRef::new(range.start.row, col)),
+            false => String::new(),
+        };
+        let name = |col: usize| Some(name(col)).filter(|n| !n.is_empty());
+
+        // with one column the rows are labelled by number
+        let labelled = range.cols() > 1;
+        let first = range.start.row + header as usize;
+        let series_cols = match labelled {
+            true => range.start.col + 1..=range.end.col,
+            false => range.start.col..=range.end.col,
+        };
+
+        let mut chart = Chart {
+            kind,
+            title: String::new(),
+            x_label: match labelled {
+                true => name(range.start.col).unwrap_or_else(|| column_name(range.start.col)),
+                false => String::from("Row"),
+            },
+            labels: Vec::new(),
+            xs: Vec::new(),
+            series: series_cols
+                .clone()
+                .map(|col| Series {
+                    name: name(col).unwrap_or_else(|| column_name(col)),
+                    values: Vec::new(),
+                })
+                .collect(),
+        };
+        for row in first..=range.end.row {
+            let label_at = CellRef::new(row, range.start.col);
+            let x = match labelled {
+                true => number(self.value(label_at)),
+                false => Some((row + 1) as f64),
+            };
+            // a scatter chart can't place a point without an x
+            let x = match (kind, x) {
+                (ChartKind::Scatter, None) => continue,
+                (_, x) => x.unwrap_or_default(),
+            };
+            chart.labels.push(match labelled {
+                true => self.display(label_at),
+                false => (row + 1).to_string(),
+            });
+            chart.xs.push(x);
+            for (series, col) in chart.series.iter_mut().zip(series_cols.clone()) {
+                series
+                    .values
+                    .push(number(self.value(CellRef::new(row, col))));
+            }
+        }
+
+        if chart
+            .series
+            .iter()
+            .all(|s| s.values.iter().all(Option::is_none))
+        {
+            return Err(Error::Chart(format!(
+                "there are no numbers to chart in {range}"
+            )));
+        }
+        let values = chart.series.iter().flat_map(|s| s.values.iter().flatten());
+        let xs = chart.xs.iter().filter(|_| kind == ChartKind::Scatter);
+        if let Some(n) = values.chain(xs).find(|n| n.abs() > LARGEST) {
+            return Err(Error::Chart(format!("{n:e} is too big to chart")));
+        }
+        let names: Vec<&str> = chart.series.iter().map(|s| s.name.as_str()).collect();
+        chart.title = format!("{} by {}", names.join(", "), chart.x_label);
+        Ok(chart)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ticks(min: f64, max: f64, zero: bool) -> Vec<f64> {
+        Axis::new(min, max, zero).ticks()
+    }
+
+    #[test]
+    fn steps_are_round() {
+        assert_eq!(nice(0.7, false), 1.0);
+        assert_eq!(nice(3.2, false), 5.0);
+        assert_eq!(nice(23.0, true), 20.0);
+        assert_eq!(nice(0.04, true), 0.05);
+        assert_eq!(
+            ticks(0.0, 100.0, true),
+            [0.0, 20.0, 40.0, 60.0, 80.0, 100.0]
+        );
+        // a single value has room around it
+        assert_eq!(ticks(4.0, 4.0, false), [2.0, 3.0, 4.0, 5.0, 6.0]);
+    }
+
+    #[test]
+    fn axes_take_in_negative_values() {
+        assert_eq!(ticks(-30.5, 120.0, true), [-50.0, 0.0, 50.0, 100.0, 150.0]);
+        // bars of nothing but negatives still go down from zero
+        assert_eq!(ticks(-80.0, -20.0, true), [-80.0, -60.0, -40.0, -20.0, 0.0]);
+        // lines don't have to show zero
+        assert_eq!(ticks(-80.0, -20.0, false), [-80.0, -60.0, -40.0, -20.0]);
+        let axis = Axis::new(-1.0, 1.0, false);
+        assert_eq!(axis.label(-0.01), "0.0"); // <-- not "-0.0"
+        assert_eq!(axis.label(-0.5), "-0.5");
+    }
+
+    // The sheet from the top of the file
+    fn sheet() -> Sheet {
+        let mut sheet = Sheet::new();
+        for (r, row) in [
+            ["Month", "Sales", "Costs"],
+            ["Jan", "120", "-30.5"],
+            ["Feb", "80", "45"],
+        ]
+        .iter()
+        .enumerate()
+        {
+            for (c, text) in row.iter().enumerate() {
+                sheet.set(CellRef::new(r, c), text.parse::<SpreadsheetCell>().unwrap());
+            }
+        }
+        sheet
+    }
+
+    #[test]
+    fn negative_bars_hang_from_zero() {
+        let chart = sheet()
+            .chart(ChartKind::Bar, "A1:C3".parse().unwrap())
+            .unwrap();
+        let ascii = chart.to_ascii(20, 10);
+        let plot: Vec<&str> = ascii.lines().skip(1).take(10).collect();
+        assert_eq!(
+            plot,
+            [
+                "150 |",
+                "    | ####",
+                "100 | ####",
+                "    | ####      ####",
+                "    | ####      ####",
+                " 50 | ####      ####****",
+                "    | ####      ####****",
+                "  0 |-####****--####****-",
+                "    |     ****",
+                "-50 |",
+            ]
+        );
+
+        // in the SVG the zero line is a quarter of the way up and the
+        // negative bar starts on it and goes down
+        let svg = chart.to_svg(400, 300);
+        assert!(svg.contains("<line x1=\"64\" y1=\"181.0\" x2=\"376.0\" y2=\"181.0\""));
+        assert!(svg.contains("<rect x=\"142.0\" y=\"181.0\" width=\"62.4\" height=\"28.7\""));
+    }
+
+    #[test]
+    fn huge_numbers_are_turned_away() {
+        let mut sheet = Sheet::new();
+        sheet.set(CellRef::new(0, 0), SpreadsheetCell::Float(1e308));
+        sheet.set(CellRef::new(1, 0), SpreadsheetCell::Float(-1e308));
+        let range = "A1:A2".parse().unwrap();
+        assert_eq!(
+            sheet.chart(ChartKind::Line, range),
+            Err(Error::Chart(String::from("1e308 is too big to chart")))
+        );
+
+        // and a chart made without a sheet is drawn within the largest
+        let chart = Chart {
+            kind: ChartKind::Line,
+            title: String::from("Huge"),
+            x_label: String::from("Row"),
+            labels: vec![String::from("1"), String::from("2")],
+            xs: vec![1.0, 2.0],
+            series: vec![Series {
+                name: String::from("A"),
+                values: vec![Some(1e308), Some(-1e308)],
+            }],
+        };
+        let axis = chart.value_axis();
+        assert!(axis.lo.is_finite() && axis.hi.is_finite() && axis.step.is_finite());
+        let ascii = chart.to_ascii(40, 10);
+        assert!(ascii.contains(" 1e300 |") && ascii.contains("-1e300 |"));
+        assert!(!chart.to_svg(400, 300).contains("NaN"));
+    }
+}
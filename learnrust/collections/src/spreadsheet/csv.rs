@@ -0,0 +1,398 @@
+// Reading and writing sheets as CSV (RFC 4180). Fields are separated by
+// a delimiter, records by a line break, and a field can be put in double
+// quotes to hold delimiters, line breaks or quotes (written twice: "").
+//
+// Unquoted fields that read back as exactly the same number become Int or
+// Float cells, and the same goes for TRUE and FALSE, dates like 2024-03-15
+// and money like $1234.50. Everything else is Text: "007" and "1.50" stay
+// text so saving the sheet again gives the file it came from. A quoted
+// field is always text, which is how a Text cell holding "42" is written
+// out.
+use super::{CellRef, Error, Sheet, SpreadsheetCell};
+use std::fs;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvOptions {
+    pub delimiter: char,
+    // the first row is column names, it's read as text and never inferred
+    pub header: bool,
+    // fields like "=A1*2" are read as formulas. A text cell that looks
+    // like a formula can't survive a round trip with this on.
+    pub formulas: bool,
+}
+
+impl Default for CsvOptions {
+    fn default() -> CsvOptions {
+        CsvOptions {
+            delimiter: ',',
+            header: false,
+            formulas: false,
+        }
+    }
+}
+
+// What a file had that the cells don't say, so writing a sheet read from
+// CSV gives back the same file: its line breaks, whether the last record
+// ends with one, and how many records and fields it had (the last ones
+// can be empty)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsvLayout {
+    pub line_break: &'static str,
+    pub final_break: bool,
+    pub height: usize,
+    pub width: usize,
+}
+
+// A sheet that wasn't read from CSV is written the RFC 4180 way
+impl Default for CsvLayout {
+    fn default() -> CsvLayout {
+        CsvLayout {
+            line_break: "\r\n",
+            final_break: true,
+            height: 0,
+            width: 0,
+        }
+    }
+}
+
+impl CsvLayout {
+    // The first line break outside of quotes decides for the whole file
+    fn of(text: &str, height: usize, width: usize) -> CsvLayout {
+        let mut quoted = false;
+        let mut line_break = "\r\n";
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => quoted = !quoted, // <-- a doubled "" flips it twice
+                '\r' if !quoted && chars.peek() == Some(&'\n') => break,
+                '\n' | '\r' if !quoted => {
+                    line_break = if c == '\n' { "\n" } else { "\r" };
+                    break;
+                }
+                _ => {}
+            }
+        }
+        CsvLayout {
+            line_break,
+            final_break: text.is_empty() || text.ends_with(['\n', '\r']),
+            height,
+            width,
+        }
+    }
+}
+
+pub struct Field {
+    pub text: String,
+    pub quoted: bool,
+}
+
+// Split the text into records, each with the line it starts on
+pub fn records(text: &str, delimiter: char) -> Result<Vec<(usize, Vec<Field>)>, Error> {
+    let error = |line: usize, message: String| Error::Csv { line, message };
+    let mut records = Vec::new();
+    let mut chars = text.chars().peekable();
+    let mut line = 1;
+    while chars.peek().is_some() {
+        let start = line;
+        let mut fields = Vec::new();
+        loop {
+            let mut field = Field {
+                text: String::new(),
+                quoted: false,
+            };
+            if chars.peek() == Some(&'"') {
+                chars.next();
+                field.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') if chars.peek() == Some(&'"') => {
+                            chars.next();
+                            field.text.push('"');
+                        }
+                        Some('"') => break,
+                        Some(c) => {
+                            if c == '\n' {
+                                line += 1; // <-- a line break inside quotes is part of the field
+                            }
+                            field.text.push(c);
+                        }
+                        None => {
+                            return Err(error(
+                                start,
+                                String::from("a quoted field is never closed"),
+                            ))
+                        }
+                    }
+                }
+            } else {
+                while let Some(&c) = chars.peek() {
+                    if c == delimiter || c == '\n' || c == '\r' {
+                        break;
+                    }
+                    if c == '"' {
+                        let message = "a quote inside an unquoted field, quote the field and double the quote";
+                        return Err(error(line, String::from(message)));
+                    }
+                    field.text.push(c);
+                    chars.next();
+                }
+            }
+            fields.push(field);
+            match chars.next() {
+                Some(c) if c == delimiter => continue,
+                Some('\r') if chars.peek() == Some(&'\n') => {
+                    chars.next();
+                }
+                Some('\n') | Some('\r') | None => {}
+                Some(c) => {
+                    return Err(error(
+                        line,
+                        format!("expected a delimiter after the closing quote, found '{c}'"),
+                    ));
+                }
+            }
+            line += 1;
+            break;
+        }
+        records.push((start, fields));
+    }
+    Ok(records)
+}
+
+// The cell a field stands for
+fn infer(field: Field, options: &CsvOptions) -> SpreadsheetCell {
+    if options.formulas && field.text.starts_with('=') {
+        if let Ok(SpreadsheetCell::Formula(formula)) = SpreadsheetCell::formula(&field.text) {
+            if formula.source() == field.text {
+                return SpreadsheetCell::Formula(formula);
+            }
+        }
+    }
+    if field.text.is_empty() && !field.quoted {
+        return SpreadsheetCell::Empty;
+    }
+    if !field.quoted {
+        if let Ok(n) = field.text.parse::<i64>() {
+            if n.to_string() == field.text {
+                return SpreadsheetCell::Int(n);
+            }
+        }
+        if let Ok(n) = field.text.parse::<f64>() {
+            if n.is_finite() && n.to_string() == field.text {
+                return SpreadsheetCell::Float(n);
+            }
+        }
+        let typed = field.text.parse::<SpreadsheetCell>();
+        if let Ok(
+            cell @ (SpreadsheetCell::Bool(_)
+            | SpreadsheetCell::Date(_)
+            | SpreadsheetCell::Currency(_)),
+        ) = typed
+        {
+            if written(&cell) == field.text {
+                return cell;
+            }
+        }
+    }
+    SpreadsheetCell::Text(field.text)
+}
+
+// A cell as it goes in a file, before any quoting. Money is written
+// without separators, with them it would have to be quoted and then it
+// would read back as text.
+fn written(cell: &SpreadsheetCell) -> String {
+    match cell {
+        SpreadsheetCell::Formula(formula) => formula.source().to_owned(),
+        SpreadsheetCell::Currency(money) => money.format(2, false),
+        cell => cell.to_string(),
+    }
+}
+
+// How a cell is written, text that would be read back as something else
+// is quoted along with anything holding delimiters, quotes or line breaks
+pub fn field(cell: &SpreadsheetCell, options: &CsvOptions) -> String {
+    let text = written(cell);
+    let ambiguous = match cell {
+        SpreadsheetCell::Text(text) => {
+            let field = Field {
+                text: text.clone(),
+                quoted: false,
+            };
+            !matches!(infer(field, options), SpreadsheetCell::Text(_))
+        }
+        _ => false,
+    };
+    let special = |c: char| c == options.delimiter || c == '"' || c == '\r' || c == '\n';
+    match ambiguous || text.contains(special) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text,
+    }
+}
+
+fn check_delimiter(delimiter: char) -> Result<(), Error> {
+    match delimiter {
+        '"' | '\r' | '\n' => Err(Error::BadDelimiter(delimiter)),
+        _ => Ok(()),
+    }
+}
+
+impl Sheet {
+    // Every record has to have as many fields as the first one
+    pub fn from_csv(text: &str, options: &CsvOptions) -> Result<Sheet, Error> {
+        check_delimiter(options.delimiter)?;
+        let records = records(text, options.delimiter)?;
+        let width = records.first().map_or(0, |(_, fields)| fields.len());
+        let mut sheet = Sheet::with_size(records.len(), width);
+        sheet.set_csv_layout(CsvLayout::of(text, records.len(), width));
+        let mut cells = Vec::new();
+        for (row, (line, fields)) in records.into_iter().enumerate() {
+            if fields.len() != width {
+                return Err(Error::Csv {
+                    line,
+                    message: format!(
+                        "expected {width} fields like the first row, found {}",
+                        fields.len()
+                    ),
+                });
+            }
+            for (col, field) in fields.into_iter().enumerate() {
+                let cell = match options.header && row == 0 {
+                    true => SpreadsheetCell::Text(field.text),
+                    false => infer(field, options),
+                };
+                if !cell.is_empty() {
+                    cells.push((CellRef::new(row, col), cell));
+                }
+            }
+        }
+        // formulas are worked out once everything they read is in
+        sheet.set_many(cells);
+        Ok(sheet)
+    }
+
+    // Everything from A1 to the last used cell, and at least as much as
+    // the file the sheet was read from. Formulas are written as their
+    // source so nothing is lost.
+    pub fn to_csv(&self, options: &CsvOptions) -> Result<String, Error> {
+        check_delimiter(options.delimiter)?;
+        let layout = self.csv_layout();
+        let (height, width) = match self.used_range() {
+            Some(used) => (
+                layout.height.max(used.end.row + 1),
+                layout.width.max(used.end.col + 1),
+            ),
+            None => (layout.height, layout.width),
+        };
+        let records: Vec<String> = (0..height)
+            .map(|row| {
+                let fields: Vec<String> = (0..width)
+                    .map(|col| field(self.get(CellRef::new(row, col)), options))
+                    .collect();
+                fields.join(&options.delimiter.to_string())
+            })
+            .collect();
+        let mut out = records.join(layout.line_break);
+        if layout.final_break && height > 0 {
+            out.push_str(layout.line_break);
+        }
+        Ok(out)
+    }
+
+    pub fn load_csv(path: &str, options: &CsvOptions) -> Result<Sheet, Error> {
+        let text = fs::read_to_string(path).map_err(|err| Error::Io(err.to_string()))?;
+        Sheet::from_csv(&text, options)
+    }
+
+    pub fn save_csv(&self, path: &str, options: &CsvOptions) -> Result<(), Error> {
+        fs::write(path, self.to_csv(options)?).map_err(|err| Error::Io(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(text: &str) -> String {
+        let options = CsvOptions::default();
+        Sheet::from_csv(text, &options)
+            .unwrap()
+            .to_csv(&options)
+            .unwrap()
+    }
+
+    #[test]
+    fn files_come_back_the_same() {
+        for text in [
+            "a,b\n1,2\n",
+            "a,b\r\n1,2",
+            "a,b,\r\n1,2,\r\n",
+            "a,b\r\n1,2\r\n,\r\n",
+            "a,,c\n,,\n",
+            "\"x,y\",\"say \"\"hi\"\"\"\r\n\"two\r\nlines\",3.5\r\n",
+            "\"a\nb\",c\r\nd,e\r\n",
+            "",
+        ] {
+            assert_eq!(round_trip(text), text, "{text:?}");
+        }
+    }
+
+    #[test]
+    fn layout_follows_columns() {
+        let options = CsvOptions::default();
+        let mut sheet = Sheet::from_csv("a,b,\n", &options).unwrap();
+        sheet.delete_col(0).unwrap();
+        assert_eq!(sheet.to_csv(&options).unwrap(), "b,\n");
+        sheet.insert_col(0).unwrap();
+        assert_eq!(sheet.to_csv(&options).unwrap(), ",b,\n");
+        let built = Sheet::from_csv("", &options).unwrap();
+        assert_eq!(built.to_csv(&options).unwrap(), "");
+    }
+
+    #[test]
+    fn quoting() {
+        let options = CsvOptions::default();
+        let quoted = |cell: SpreadsheetCell| field(&cell, &options);
+        assert_eq!(quoted(SpreadsheetCell::Text("plain".into())), "plain");
+        assert_eq!(quoted(SpreadsheetCell::Text("42".into())), "\"42\"");
+        assert_eq!(quoted(SpreadsheetCell::Text("TRUE".into())), "\"TRUE\"");
+        assert_eq!(quoted(SpreadsheetCell::Text("a,b".into())), "\"a,b\"");
+        assert_eq!(quoted(SpreadsheetCell::Text("a\"b".into())), "\"a\"\"b\"");
+        assert_eq!(quoted(SpreadsheetCell::Text("".into())), "\"\"");
+        assert_eq!(quoted(SpreadsheetCell::Int(42)), "42");
+
+        let sheet = Sheet::from_csv("\"42\",42,007,1.50\n", &options).unwrap();
+        assert_eq!(
+            sheet.get(CellRef::new(0, 0)),
+            &SpreadsheetCell::Text("42".into())
+        );
+        assert_eq!(sheet.get(CellRef::new(0, 1)), &SpreadsheetCell::Int(42));
+        assert_eq!(
+            sheet.get(CellRef::new(0, 2)),
+            &SpreadsheetCell::Text("007".into())
+        );
+        assert_eq!(
+            sheet.get(CellRef::new(0, 3)),
+            &SpreadsheetCell::Text("1.50".into())
+        );
+    }
+
+    #[test]
+    fn bad_rows_say_which_line() {
+        let options = CsvOptions::default();
+        let line = |text: &str| match Sheet::from_csv(text, &options) {
+            Err(Error::Csv { line, .. }) => line,
+            other => panic!("expected a CSV error, got {other:?}"),
+        };
+        assert_eq!(line("a,b\n1,2\n3\n"), 3);
+        // the quoted line break counts, the bad record starts on line 4
+        assert_eq!(line("a,b\n\"x\ny\",2\n1,2,3\n"), 4);
+        assert_eq!(line("a,b\n\"never closed,2\n"), 2);
+        assert_eq!(line("a,b\nx\"y,2\n"), 2);
+        assert_eq!(line("a,b\n\"x\"y,2\n"), 2);
+        let quotes = CsvOptions {
+            delimiter: '"',
+            ..CsvOptions::default()
+        };
+        assert_eq!(Sheet::from_csv("a", &quotes), Err(Error::BadDelimiter('"')));
+    }
+}
@@ -0,0 +1,175 @@
+// Calendar dates, kept as a day number the way other spreadsheets do: day
+// 1 is 1899-12-31, so 45000 is 2023-03-15. Formulas see a date as that
+// number, =A1+7 is a week later and =B1-A1 the days in between, and a date
+// format turns the number back into a date for display.
+//
+// The conversions are Howard Hinnant's days_from_civil and civil_from_days
+// for the proleptic Gregorian calendar.
+use super::Error;
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+// The day number of 1970-01-01, which is where the conversions count from
+const UNIX_EPOCH: i64 = 25569;
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date(i64);
+
+fn is_leap(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl Date {
+    // Years 1 to 9999 only, which is what four digits can show
+    pub fn new(year: i64, month: u32, day: u32) -> Option<Date> {
+        if !(1..=9999).contains(&year)
+            || !(1..=12).contains(&month)
+            || day < 1
+            || day > days_in_month(year, month)
+        {
+            return None;
+        }
+        // March first, so the leap day is the last day of the year
+        let y = if month <= 2 { year - 1 } else { year };
+        let era = y.div_euclid(400);
+        let year_of_era = y - era * 400;
+        let m = (month as i64 + 9) % 12;
+        let day_of_year = (153 * m + 2) / 5 + day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Some(Date(era * 146097 + day_of_era - 719468 + UNIX_EPOCH))
+    }
+
+    // Checked against the first and last days before ymd() ever sees it,
+    // the arithmetic in there overflows for numbers far enough out
+    pub fn from_serial(serial: i64) -> Option<Date> {
+        let first = Date::new(1, 1, 1)?;
+        let last = Date::new(9999, 12, 31)?;
+        (first.0..=last.0).contains(&serial).then_some(Date(serial))
+    }
+
+    pub fn serial(self) -> i64 {
+        self.0
+    }
+
+    pub fn ymd(self) -> (i64, u32, u32) {
+        let z = self.0 - UNIX_EPOCH + 719468;
+        let era = z.div_euclid(146097);
+        let day_of_era = z - era * 146097;
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let m = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * m + 2) / 5 + 1) as u32;
+        let month = if m < 10 { m + 3 } else { m - 9 } as u32;
+        let year = year_of_era + era * 400 + (month <= 2) as i64;
+        (year, month, day)
+    }
+
+    // 0 is Sunday
+    fn weekday(self) -> usize {
+        (self.0 - UNIX_EPOCH + 4).rem_euclid(7) as usize // <-- 1970-01-01 was a Thursday
+    }
+
+    // Write the date with a pattern like "DD/MM/YYYY" or "MMM D, YYYY".
+    // YYYY and YY are the year, M and MM the month as a number, MMM and
+    // MMMM its name, D and DD the day, DDD and DDDD the day of the week.
+    // Upper or lower case, anything else is copied as it is.
+    pub fn format(self, pattern: &str) -> String {
+        let (year, month, day) = self.ymd();
+        let chars: Vec<char> = pattern.chars().collect();
+        let mut out = String::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let letter = chars[i].to_ascii_uppercase();
+            let run = chars[i..]
+                .iter()
+                .take_while(|c| c.to_ascii_uppercase() == letter)
+                .count();
+            let month_name = MONTHS[month as usize - 1];
+            let weekday = WEEKDAYS[self.weekday()];
+            match (letter, run) {
+                ('Y', 1 | 2) => out.push_str(&format!("{:02}", year % 100)),
+                ('Y', _) => out.push_str(&format!("{year:04}")),
+                ('M', 1) => out.push_str(&month.to_string()),
+                ('M', 2) => out.push_str(&format!("{month:02}")),
+                ('M', 3) => out.push_str(&month_name[..3]),
+                ('M', _) => out.push_str(month_name),
+                ('D', 1) => out.push_str(&day.to_string()),
+                ('D', 2) => out.push_str(&format!("{day:02}")),
+                ('D', 3) => out.push_str(&weekday[..3]),
+                ('D', _) => out.push_str(weekday),
+                _ => out.extend(&chars[i..i + run]),
+            }
+            i += run;
+        }
+        out
+    }
+}
+
+// ISO 8601, 2024-03-15
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format("YYYY-MM-DD"))
+    }
+}
+
+// Year first, "2024-03-15" or "2024/3/15". Day first or month first
+// depends on where you are, so those aren't guessed at.
+impl FromStr for Date {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Date, Error> {
+        let bad = || Error::BadValue {
+            expected: "date, year first like 2024-03-15",
+            text: s.to_owned(),
+        };
+        let separator = if s.contains('/') { '/' } else { '-' };
+        let parts: Vec<&str> = s.trim().split(separator).collect();
+        let [year, month, day] = parts[..] else {
+            return Err(bad());
+        };
+        let digits = |part: &str, len: RangeInclusive<usize>| {
+            (len.contains(&part.len()) && part.bytes().all(|b| b.is_ascii_digit()))
+                .then(|| part.parse::<u32>().ok())
+                .flatten()
+                .ok_or_else(bad)
+        };
+        let year = digits(year, 4..=4)?;
+        let month = digits(month, 1..=2)?;
+        let day = digits(day, 1..=2)?;
+        Date::new(year as i64, month, day).ok_or_else(bad)
+    }
+}
@@ -0,0 +1,267 @@
+// How a cell is shown, apart from what's in it. A format is written the
+// way other spreadsheets write them:
+//
+//   General       the way the cell is, 1234.5 or 2024-03-15
+//   0.00          two decimal places
+//   #,##0         thousands separators, no decimals
+//   0.0%          a percentage, 0.125 is 12.5%
+//   DD/MM/YYYY    a number as a date, see date.rs for the letters
+//
+// Formats only change what's shown. The value formulas see is the same,
+// and text, booleans and errors are shown as they are whatever the format.
+use super::{date::Date, Error, SpreadsheetCell, Value};
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum Format {
+    #[default]
+    General,
+    Number {
+        decimals: usize,
+        thousands: bool,
+    },
+    Percent {
+        decimals: usize,
+    },
+    Date(String),
+}
+
+// Commas every three digits from the right, "1234567" is "1,234,567"
+pub fn group(digits: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(3) {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    out
+}
+
+// The other way, checking the commas are where group() would put them.
+// A number without any is fine too.
+pub fn ungroup(number: &str) -> Option<String> {
+    let (whole, fraction) = match number.split_once('.') {
+        Some((whole, fraction)) => (whole, Some(fraction)),
+        None => (number, None),
+    };
+    let groups: Vec<&str> = whole.split(',').collect();
+    let fits = groups.len() == 1
+        || (1..=3).contains(&groups[0].len()) && groups[1..].iter().all(|g| g.len() == 3);
+    if !fits || fraction.is_some_and(|f| f.contains(',')) {
+        return None;
+    }
+    let whole = groups.concat();
+    Some(match fraction {
+        Some(fraction) => format!("{whole}.{fraction}"),
+        None => whole,
+    })
+}
+
+// A number with a fixed number of decimals and maybe separators. Ints are
+// done with integers so a big one doesn't lose its last digits.
+fn number(value: &Value, decimals: usize, thousands: bool) -> Option<String> {
+    let text = match value {
+        Value::Int(n) if decimals == 0 => n.to_string(),
+        Value::Int(n) => format!("{n}.{}", "0".repeat(decimals)),
+        Value::Float(n) => format!("{n:.decimals$}"),
+        _ => return None,
+    };
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(digits) => ("-", digits),
+        None => ("", text.as_str()),
+    };
+    let (whole, fraction) = match digits.split_once('.') {
+        Some((whole, fraction)) => (whole, format!(".{fraction}")),
+        None => (digits, String::new()),
+    };
+    let whole = if thousands {
+        group(whole)
+    } else {
+        whole.to_owned()
+    };
+    // rounding can leave a "-0.00", which is just zero
+    let sign = match whole
+        .chars()
+        .chain(fraction.chars())
+        .any(|c| c.is_ascii_digit() && c != '0')
+    {
+        true => sign,
+        false => "",
+    };
+    Some(format!("{sign}{whole}{fraction}"))
+}
+
+impl Format {
+    // The text shown for a cell
+    pub fn apply(&self, cell: &SpreadsheetCell) -> String {
+        let value = cell.value();
+        let shown = match (self, &value) {
+            (Format::General, _) => None,
+            (
+                Format::Number {
+                    decimals,
+                    thousands,
+                },
+                Value::Money(money),
+            ) => Some(money.format(*decimals, *thousands)),
+            (
+                Format::Number {
+                    decimals,
+                    thousands,
+                },
+                _,
+            ) => number(&value, *decimals, *thousands),
+            (Format::Percent { decimals }, _) => {
+                let percent = match &value {
+                    Value::Int(n) => n.checked_mul(100).map(Value::Int),
+                    Value::Float(n) => Some(Value::Float(n * 100.0)),
+                    Value::Money(money) => Some(Value::Float(money.to_f64() * 100.0)),
+                    _ => None,
+                };
+                percent
+                    .and_then(|percent| number(&percent, *decimals, false))
+                    .map(|text| text + "%")
+            }
+            (Format::Date(pattern), _) => {
+                let serial = match &value {
+                    Value::Int(n) => Some(*n),
+                    Value::Float(n) if n.is_finite() => Some(n.floor() as i64),
+                    _ => None,
+                };
+                serial
+                    .and_then(Date::from_serial)
+                    .map(|date| date.format(pattern))
+            }
+        };
+        shown.unwrap_or_else(|| cell.to_string())
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let number = |decimals: usize, thousands: bool| {
+            let whole = if thousands { "#,##0" } else { "0" };
+            match decimals {
+                0 => whole.to_owned(),
+                _ => format!("{whole}.{}", "0".repeat(decimals)),
+            }
+        };
+        match self {
+            Format::General => write!(f, "General"),
+            Format::Number {
+                decimals,
+                thousands,
+            } => write!(f, "{}", number(*decimals, *thousands)),
+            Format::Percent { decimals } => write!(f, "{}%", number(*decimals, false)),
+            Format::Date(pattern) => write!(f, "{pattern}"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        let s = s.trim();
+        let bad = || Error::BadFormat(s.to_owned());
+        if s.is_empty() || s.eq_ignore_ascii_case("general") {
+            return Ok(Format::General);
+        }
+        let (percent, pattern) = match s.strip_suffix('%') {
+            Some(pattern) => (true, pattern),
+            None => (false, s),
+        };
+        let is_number = pattern.contains(['0', '#'])
+            && pattern.chars().all(|c| "#0,.".contains(c))
+            && pattern.matches('.').count() <= 1;
+        if is_number {
+            let (whole, fraction) = pattern.split_once('.').unwrap_or((pattern, ""));
+            if fraction.contains(',') || !fraction.chars().all(|c| c == '0') {
+                return Err(bad());
+            }
+            let (decimals, thousands) = (fraction.len(), whole.contains(','));
+            return Ok(match percent {
+                true => Format::Percent { decimals },
+                false => Format::Number {
+                    decimals,
+                    thousands,
+                },
+            });
+        }
+        let letters = || s.chars().filter(|c| c.is_alphabetic());
+        let is_date = !percent
+            && letters().next().is_some()
+            && letters().all(|c| "YMD".contains(c.to_ascii_uppercase()));
+        match is_date {
+            true => Ok(Format::Date(s.to_owned())),
+            false => Err(bad()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::Money;
+
+    #[test]
+    fn formats_read_back_the_way_theyre_written() {
+        for text in [
+            "General",
+            "0",
+            "0.00",
+            "#,##0",
+            "#,##0.000",
+            "0%",
+            "0.0%",
+            "DD/MM/YYYY",
+        ] {
+            let format: Format = text.parse().unwrap();
+            assert_eq!(format.to_string(), text);
+        }
+        assert_eq!("".parse(), Ok(Format::General));
+        for text in ["0.0#", "0,0.00.0", "#,##0.0,0", "abc", "DD%"] {
+            assert_eq!(
+                text.parse::<Format>(),
+                Err(Error::BadFormat(text.to_owned()))
+            );
+        }
+    }
+
+    #[test]
+    fn shown_with_a_format() {
+        let shown =
+            |format: &str, cell: SpreadsheetCell| format.parse::<Format>().unwrap().apply(&cell);
+        assert_eq!(
+            shown("#,##0.00", SpreadsheetCell::Int(1234567)),
+            "1,234,567.00"
+        );
+        assert_eq!(shown("0", SpreadsheetCell::Float(-0.4)), "0");
+        assert_eq!(shown("0.0%", SpreadsheetCell::Float(0.125)), "12.5%");
+        assert_eq!(shown("YYYY-MM-DD", SpreadsheetCell::Int(0)), "1899-12-30");
+        assert_eq!(
+            shown("YYYY-MM-DD", SpreadsheetCell::Int(-693593)),
+            "0001-01-01"
+        );
+        assert_eq!(
+            shown("YYYY-MM-DD", SpreadsheetCell::Int(2958465)),
+            "9999-12-31"
+        );
+        // past either end there's no date to show, just the number
+        assert_eq!(
+            shown("YYYY-MM-DD", SpreadsheetCell::Int(2958466)),
+            "2958466"
+        );
+        let far = SpreadsheetCell::Float(1e300);
+        assert_eq!(shown("YYYY-MM-DD", far.clone()), far.to_string());
+        assert_eq!(
+            shown("YYYY-MM-DD", SpreadsheetCell::Int(i64::MIN)),
+            i64::MIN.to_string()
+        );
+        let money = SpreadsheetCell::Currency(Money::new(123456789, '£'));
+        assert_eq!(shown("0", money.clone()), "£1234568");
+        assert_eq!(shown("General", money), "£1,234,567.89");
+        assert_eq!(shown("0.00", SpreadsheetCell::Text("n/a".into())), "n/a");
+    }
+}
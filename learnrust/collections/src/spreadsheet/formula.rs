@@ -0,0 +1,717 @@
+// Formulas like '=A1*2 + SUM(B1:B10)'. The text after '=' is tokenized,
+// parsed by recursive descent into an expression tree and evaluated
+// against whatever the referenced cells currently hold.
+//
+// A reference can name another sheet of a workbook, Sales!B2 or with
+// quotes for names that aren't a single word, 'Q1 Sales'!B2:B10.
+use super::{
+    functions,
+    value::{arithmetic, CellError, Number, Value},
+    workbook::{quote_sheet_name, same_sheet},
+    CellRef, Error, Range,
+};
+use std::{cmp::Ordering, fmt};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+    Ref(CellRef),
+    Range(Range),
+    SheetRef(String, CellRef), // <-- a cell on another sheet
+    SheetRange(String, Range),
+    RefError, // <-- a reference whose cell (or sheet) was deleted
+    Neg(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>), // <-- + - * / ^ and & to join text
+    Compare(&'static str, Box<Expr>, Box<Expr>),
+    Group(Box<Expr>), // <-- kept so the formula prints with its parentheses
+    Call(String, Vec<Expr>),
+}
+
+// How formulas look up the cells they reference
+pub trait Context {
+    fn value(&self, at: CellRef) -> Value;
+
+    // The cells in a range that have something in them, row by row, with
+    // their values. Functions skip blanks anyway, and this way a range
+    // over a mostly empty sheet costs what's in it rather than its area.
+    fn filled(&self, range: Range) -> Vec<(CellRef, Value)>;
+
+    // Cells on other sheets, only a workbook has those
+    fn sheet_value(&self, _sheet: &str, _at: CellRef) -> Value {
+        Value::Error(CellError::Ref)
+    }
+
+    fn sheet_filled(&self, _sheet: &str, range: Range) -> Vec<(CellRef, Value)> {
+        vec![(range.start, Value::Error(CellError::Ref))]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Name(String),  // <-- function names and cell references
+    Sheet(String), // <-- a sheet name and the '!' after it
+    RefError,      // <-- written out in place of deleted references
+    Op(char),
+    Compare(&'static str),
+    End,
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let error = |column: usize, message: String| Error::Formula { column, message };
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (c, column) = (chars[i], i + 1);
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let token = match text.parse() {
+                Ok(n) => Token::Int(n),
+                Err(_) => Token::Float(
+                    text.parse()
+                        .map_err(|_| error(column, format!("'{text}' is not a number")))?,
+                ),
+            };
+            tokens.push((token, column));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            let name: String = chars[start..i].iter().collect();
+            match chars.get(i) {
+                Some('!') => {
+                    tokens.push((Token::Sheet(name), column));
+                    i += 1;
+                }
+                _ => tokens.push((Token::Name(name), column)),
+            }
+        } else if c == '\'' {
+            // 'Q1 Sales'! quotes a sheet name that isn't a single word
+            let end = chars[i + 1..]
+                .iter()
+                .position(|c| *c == '\'')
+                .map(|len| i + 1 + len)
+                .ok_or_else(|| error(column, String::from("unclosed quote")))?;
+            if chars.get(end + 1) != Some(&'!') {
+                return Err(error(
+                    end + 2,
+                    String::from("expected '!' after the sheet name"),
+                ));
+            }
+            tokens.push((Token::Sheet(chars[i + 1..end].iter().collect()), column));
+            i = end + 2;
+        } else if chars[i..].starts_with(&['#', 'R', 'E', 'F', '!']) {
+            tokens.push((Token::RefError, column));
+            i += 5;
+        } else if c == '"' {
+            // a "" inside the quotes is a quote character
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some('"'), Some('"')) => {
+                        text.push('"');
+                        i += 2;
+                    }
+                    (Some('"'), _) => break,
+                    (Some(c), _) => {
+                        text.push(*c);
+                        i += 1;
+                    }
+                    (None, _) => return Err(error(column, String::from("unclosed quote"))),
+                }
+            }
+            tokens.push((Token::Text(text), column));
+            i += 1;
+        } else if "<>=".contains(c) {
+            let op = match (c, chars.get(i + 1)) {
+                ('<', Some('=')) => "<=",
+                ('<', Some('>')) => "<>",
+                ('>', Some('=')) => ">=",
+                ('<', _) => "<",
+                ('>', _) => ">",
+                _ => "=",
+            };
+            tokens.push((Token::Compare(op), column));
+            i += op.len();
+        } else if "+-*/^(),:&".contains(c) {
+            tokens.push((Token::Op(c), column));
+            i += 1;
+        } else {
+            return Err(error(column, format!("unexpected character '{c}'")));
+        }
+    }
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.pos].clone();
+        if token.0 != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Formula {
+            column: self.tokens[self.pos].1,
+            message: message.into(),
+        }
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), Error> {
+        if *self.peek() == Token::Op(op) {
+            self.next();
+            Ok(())
+        } else {
+            Err(self.error(format!("expected '{op}'")))
+        }
+    }
+
+    // compare := concat (('=' | '<>' | '<' | '<=' | '>' | '>=') concat)?
+    fn compare(&mut self) -> Result<Expr, Error> {
+        let left = self.concat()?;
+        if let Token::Compare(op) = *self.peek() {
+            self.next();
+            return Ok(Expr::Compare(op, Box::new(left), Box::new(self.concat()?)));
+        }
+        Ok(left)
+    }
+
+    // concat := expr ('&' expr)*
+    fn concat(&mut self) -> Result<Expr, Error> {
+        let mut left = self.expr()?;
+        while *self.peek() == Token::Op('&') {
+            self.next();
+            left = Expr::Binary('&', Box::new(left), Box::new(self.expr()?));
+        }
+        Ok(left)
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let mut left = self.term()?;
+        while let Token::Op(op @ ('+' | '-')) = *self.peek() {
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?));
+        }
+        Ok(left)
+    }
+
+    // term := unary (('*' | '/') unary)*
+    fn term(&mut self) -> Result<Expr, Error> {
+        let mut left = self.unary()?;
+        while let Token::Op(op @ ('*' | '/')) = *self.peek() {
+            self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?));
+        }
+        Ok(left)
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if *self.peek() == Token::Op('-') {
+            self.next();
+            return Ok(Expr::Neg(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    // power := primary ('^' unary)?
+    fn power(&mut self) -> Result<Expr, Error> {
+        let base = self.primary()?;
+        if *self.peek() == Token::Op('^') {
+            self.next();
+            return Ok(Expr::Binary('^', Box::new(base), Box::new(self.unary()?)));
+        }
+        Ok(base)
+    }
+
+    // A cell, or a range when there's a ':' after it. The name is the
+    // next token.
+    fn cell_or_range(&mut self, name: &str) -> Result<Expr, Error> {
+        let start: CellRef = name
+            .parse()
+            .map_err(|_| self.error(format!("'{name}' is not a cell or a function")))?;
+        self.next();
+        if *self.peek() != Token::Op(':') {
+            return Ok(Expr::Ref(start));
+        }
+        self.next();
+        let end = match self.next() {
+            (Token::Name(end), _) => end.parse().ok(),
+            _ => None,
+        };
+        match end {
+            Some(end) => Ok(Expr::Range(Range::new(start, end))),
+            None => {
+                self.pos -= 1;
+                Err(self.error("expected the cell at the end of the range"))
+            }
+        }
+    }
+
+    // primary := number | text | TRUE | FALSE | [sheet '!'] cell [':' cell]
+    //          | name '(' args ')' | '(' compare ')'
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.peek().clone() {
+            Token::Text(text) => {
+                self.next();
+                Ok(Expr::Text(text))
+            }
+            Token::Int(n) => {
+                self.next();
+                Ok(Expr::Int(n))
+            }
+            Token::Float(n) => {
+                self.next();
+                Ok(Expr::Float(n))
+            }
+            Token::Name(name) => {
+                let (_, column) = self.next();
+                if *self.peek() == Token::Op('(') {
+                    self.next();
+                    let mut args = Vec::new();
+                    if *self.peek() != Token::Op(')') {
+                        args.push(self.compare()?);
+                        while *self.peek() == Token::Op(',') {
+                            self.next();
+                            args.push(self.compare()?);
+                        }
+                    }
+                    self.expect(')')?;
+                    let name = name.to_uppercase();
+                    // unknown functions are left for #NAME?, known ones
+                    // have to be given the right number of arguments
+                    if let Some(expected) = functions::check_arity(&name, args.len()) {
+                        return Err(Error::Formula {
+                            column,
+                            message: format!("{name} takes {expected}, got {}", args.len()),
+                        });
+                    }
+                    return Ok(Expr::Call(name, args));
+                }
+                match name.to_uppercase().as_str() {
+                    "TRUE" => return Ok(Expr::Bool(true)),
+                    "FALSE" => return Ok(Expr::Bool(false)),
+                    _ => {}
+                }
+                self.pos -= 1; // <-- point errors at the name itself
+                self.cell_or_range(&name)
+            }
+            Token::Sheet(sheet) => {
+                self.next();
+                let Token::Name(name) = self.peek().clone() else {
+                    return Err(self.error("expected a cell after the sheet name"));
+                };
+                match self.cell_or_range(&name)? {
+                    Expr::Ref(at) => Ok(Expr::SheetRef(sheet, at)),
+                    Expr::Range(range) => Ok(Expr::SheetRange(sheet, range)),
+                    _ => unreachable!("cell_or_range only gives references"),
+                }
+            }
+            Token::RefError => {
+                self.next();
+                Ok(Expr::RefError)
+            }
+            Token::Op('(') => {
+                self.next();
+                let inner = self.compare()?;
+                self.expect(')')?;
+                Ok(Expr::Group(Box::new(inner)))
+            }
+            Token::End => Err(self.error("unexpected end of formula")),
+            Token::Op(op) => Err(self.error(format!("unexpected '{op}'"))),
+            Token::Compare(op) => Err(self.error(format!("unexpected '{op}'"))),
+        }
+    }
+}
+
+fn parse_expr(source: &str) -> Result<Expr, Error> {
+    let mut parser = Parser {
+        tokens: tokenize(source)?,
+        pos: 0,
+    };
+    let expr = parser.compare()?;
+    match parser.peek() {
+        Token::End => Ok(expr),
+        _ => Err(parser.error("unexpected input after the formula")),
+    }
+}
+
+// Parse a formula, with or without its leading '='. Error columns count
+// the '=' so they line up with what was typed.
+pub fn parse(source: &str) -> Result<Expr, Error> {
+    match source.strip_prefix('=') {
+        Some(rest) => parse_expr(rest).map_err(|err| match err {
+            Error::Formula { column, message } => Error::Formula {
+                column: column + 1,
+                message,
+            },
+            err => err,
+        }),
+        None => parse_expr(source),
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expr::Int(n) => write!(f, "{n}"),
+            Expr::Float(n) => write!(f, "{n}"),
+            Expr::Text(text) => write!(f, "\"{}\"", text.replace('"', "\"\"")),
+            Expr::Bool(true) => write!(f, "TRUE"),
+            Expr::Bool(false) => write!(f, "FALSE"),
+            Expr::Ref(at) => write!(f, "{at}"),
+            Expr::Range(range) => write!(f, "{range}"),
+            Expr::SheetRef(sheet, at) => write!(f, "{}!{at}", quote_sheet_name(sheet)),
+            Expr::SheetRange(sheet, range) => write!(f, "{}!{range}", quote_sheet_name(sheet)),
+            Expr::RefError => write!(f, "{}", CellError::Ref),
+            Expr::Neg(inner) => write!(f, "-{inner}"),
+            Expr::Binary(op, left, right) => write!(f, "{left}{op}{right}"),
+            Expr::Compare(op, left, right) => write!(f, "{left}{op}{right}"),
+            Expr::Group(inner) => write!(f, "({inner})"),
+            Expr::Call(name, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{name}({})", args.join(","))
+            }
+        }
+    }
+}
+
+// A row or column being inserted or deleted, references past it move.
+// A formula that's moved somewhere else (by sorting) has every reference
+// moved along with it, the way relative references work in spreadsheets.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Shift {
+    InsertRow(usize),
+    DeleteRow(usize),
+    InsertCol(usize),
+    DeleteCol(usize),
+    Move { rows: isize, cols: isize },
+}
+
+impl Shift {
+    // Where a cell ends up, None if it was deleted
+    pub fn cell(self, at: CellRef) -> Option<CellRef> {
+        let (row, col) = (at.row, at.col);
+        match self {
+            // a reference moved off the top or the left of the sheet is gone
+            Shift::Move { rows, cols } => Some(CellRef::new(
+                row.checked_add_signed(rows)?,
+                col.checked_add_signed(cols)?,
+            )),
+            Shift::InsertRow(r) if row >= r => Some(CellRef::new(row + 1, col)),
+            Shift::InsertCol(c) if col >= c => Some(CellRef::new(row, col + 1)),
+            Shift::DeleteRow(r) if row == r => None,
+            Shift::DeleteRow(r) if row > r => Some(CellRef::new(row - 1, col)),
+            Shift::DeleteCol(c) if col == c => None,
+            Shift::DeleteCol(c) if col > c => Some(CellRef::new(row, col - 1)),
+            _ => Some(at),
+        }
+    }
+
+    // Ranges stretch when a row or column is inserted inside of them and
+    // shrink when one is deleted, they're only gone once nothing is left
+    pub fn range(self, range: Range) -> Option<Range> {
+        let (start, end) = (range.start, range.end);
+        let (first, last, at, insert) = match self {
+            Shift::Move { .. } => return Some(Range::new(self.cell(start)?, self.cell(end)?)),
+            Shift::InsertRow(r) => (start.row, end.row, r, true),
+            Shift::DeleteRow(r) => (start.row, end.row, r, false),
+            Shift::InsertCol(c) => (start.col, end.col, c, true),
+            Shift::DeleteCol(c) => (start.col, end.col, c, false),
+        };
+        let (first, last) = match insert {
+            true if at <= first => (first + 1, last + 1),
+            true if at <= last => (first, last + 1),
+            false if at < first => (first - 1, last - 1),
+            false if at <= last && first == last => return None,
+            false if at <= last => (first, last - 1),
+            _ => (first, last),
+        };
+        Some(match self {
+            Shift::InsertRow(_) | Shift::DeleteRow(_) => {
+                Range::new(CellRef::new(first, start.col), CellRef::new(last, end.col))
+            }
+            _ => Range::new(CellRef::new(start.row, first), CellRef::new(end.row, last)),
+        })
+    }
+}
+
+impl Expr {
+    // Every cell and range the expression reads on its own sheet
+    pub fn references(&self, cells: &mut Vec<CellRef>, ranges: &mut Vec<Range>) {
+        match self {
+            Expr::Ref(at) => cells.push(*at),
+            Expr::Range(range) => ranges.push(*range),
+            Expr::Neg(inner) | Expr::Group(inner) => inner.references(cells, ranges),
+            Expr::Binary(_, left, right) | Expr::Compare(_, left, right) => {
+                left.references(cells, ranges);
+                right.references(cells, ranges);
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    arg.references(cells, ranges);
+                }
+            }
+            Expr::SheetRef(..)
+            | Expr::SheetRange(..)
+            | Expr::Int(_)
+            | Expr::Float(_)
+            | Expr::Text(_)
+            | Expr::Bool(_)
+            | Expr::RefError => {}
+        }
+    }
+
+    // And the ones it reads on other sheets, with the sheet's name
+    pub fn sheet_references<'a>(
+        &'a self,
+        cells: &mut Vec<(&'a str, CellRef)>,
+        ranges: &mut Vec<(&'a str, Range)>,
+    ) {
+        match self {
+            Expr::SheetRef(sheet, at) => cells.push((sheet, *at)),
+            Expr::SheetRange(sheet, range) => ranges.push((sheet, *range)),
+            Expr::Neg(inner) | Expr::Group(inner) => inner.sheet_references(cells, ranges),
+            Expr::Binary(_, left, right) | Expr::Compare(_, left, right) => {
+                left.sheet_references(cells, ranges);
+                right.sheet_references(cells, ranges);
+            }
+            Expr::Call(_, args) => {
+                for arg in args {
+                    arg.sheet_references(cells, ranges);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Move the references for a shift, returns whether anything changed.
+    // `sheet` says which references the shift is for: the ones to the
+    // formula's own sheet (None) or the ones naming a sheet. Moving the
+    // formula itself moves every one of them.
+    pub fn shift(&mut self, shift: Shift, sheet: Option<&str>) -> bool {
+        let applies = |named: Option<&str>| match (named, sheet) {
+            _ if matches!(shift, Shift::Move { .. }) => true,
+            (None, None) => true,
+            (Some(named), Some(sheet)) => same_sheet(named, sheet),
+            _ => false,
+        };
+        // Some(whether it moved), None when it's gone
+        let moved = match self {
+            Expr::Ref(at) if applies(None) => shift
+                .cell(*at)
+                .map(|moved| std::mem::replace(at, moved) != moved),
+            Expr::SheetRef(named, at) if applies(Some(named.as_str())) => shift
+                .cell(*at)
+                .map(|moved| std::mem::replace(at, moved) != moved),
+            Expr::Range(range) if applies(None) => shift
+                .range(*range)
+                .map(|moved| std::mem::replace(range, moved) != moved),
+            Expr::SheetRange(named, range) if applies(Some(named.as_str())) => shift
+                .range(*range)
+                .map(|moved| std::mem::replace(range, moved) != moved),
+            Expr::Neg(inner) | Expr::Group(inner) => Some(inner.shift(shift, sheet)),
+            Expr::Binary(_, left, right) | Expr::Compare(_, left, right) => {
+                // both sides have to be shifted, so no short circuiting ||
+                let left = left.shift(shift, sheet);
+                Some(right.shift(shift, sheet) | left)
+            }
+            Expr::Call(_, args) => Some(
+                args.iter_mut()
+                    .fold(false, |changed, arg| arg.shift(shift, sheet) | changed),
+            ),
+            _ => Some(false),
+        };
+        moved.unwrap_or_else(|| {
+            *self = Expr::RefError;
+            true
+        })
+    }
+
+    // Point the references to a sheet at its new name, or at nothing when
+    // it's deleted. Returns whether anything changed.
+    pub fn rename_sheet(&mut self, from: &str, to: Option<&str>) -> bool {
+        match self {
+            Expr::SheetRef(sheet, _) | Expr::SheetRange(sheet, _) if same_sheet(sheet, from) => {
+                match to {
+                    Some(to) => *sheet = to.to_owned(),
+                    None => *self = Expr::RefError,
+                }
+                true
+            }
+            Expr::Neg(inner) | Expr::Group(inner) => inner.rename_sheet(from, to),
+            Expr::Binary(_, left, right) | Expr::Compare(_, left, right) => {
+                let left = left.rename_sheet(from, to);
+                right.rename_sheet(from, to) | left
+            }
+            Expr::Call(_, args) => args
+                .iter_mut()
+                .fold(false, |changed, arg| arg.rename_sheet(from, to) | changed),
+            _ => false,
+        }
+    }
+
+    pub fn eval(&self, ctx: &dyn Context) -> Value {
+        match self {
+            Expr::Int(n) => Value::Int(*n),
+            Expr::Float(n) => Value::Float(*n),
+            Expr::Text(text) => Value::Text(text.clone()),
+            Expr::Bool(b) => Value::Bool(*b),
+            // blanks are passed on as they are, "" & A1 shouldn't turn into "0"
+            Expr::Ref(at) => ctx.value(*at),
+            Expr::SheetRef(sheet, at) => ctx.sheet_value(sheet, *at),
+            // a bare range outside of a function has no single value
+            Expr::Range(_) | Expr::SheetRange(..) => Value::Error(CellError::Value),
+            Expr::RefError => Value::Error(CellError::Ref),
+            Expr::Group(inner) => inner.eval(ctx),
+            Expr::Neg(inner) => inner
+                .eval(ctx)
+                .to_number()
+                .and_then(|n| arithmetic('*', n, Number::Int(-1)))
+                .into(),
+            Expr::Binary('&', left, right) => {
+                let (left, right) = (left.eval(ctx).to_text(), right.eval(ctx).to_text());
+                match left.and_then(|l| Ok(l + &right?)) {
+                    Ok(text) => Value::Text(text),
+                    Err(err) => Value::Error(err),
+                }
+            }
+            Expr::Binary(op, left, right) => {
+                let left = left.eval(ctx).to_number();
+                let right = right.eval(ctx).to_number();
+                left.and_then(|l| arithmetic(*op, l, right?)).into()
+            }
+            Expr::Compare(op, left, right) => {
+                let ordering = left.eval(ctx).compare(&right.eval(ctx));
+                match ordering {
+                    Ok(ordering) => Value::Bool(match *op {
+                        "=" => ordering == Ordering::Equal,
+                        "<>" => ordering != Ordering::Equal,
+                        "<" => ordering == Ordering::Less,
+                        "<=" => ordering != Ordering::Greater,
+                        ">" => ordering == Ordering::Greater,
+                        _ => ordering != Ordering::Less,
+                    }),
+                    Err(err) => Value::Error(err),
+                }
+            }
+            Expr::Call(name, args) => functions::call(name, args, ctx),
+        }
+    }
+}
+
+// The cells and ranges a formula reads on other sheets, by sheet name
+pub type SheetReferences<'a> = (Vec<(&'a str, CellRef)>, Vec<(&'a str, Range)>);
+
+// A formula cell: what was typed, the parsed expression and the value it
+// computed last time the sheet recalculated
+#[derive(Debug, Clone, PartialEq)]
+pub struct Formula {
+    source: String,
+    expr: Expr,
+    value: Value,
+}
+
+impl Formula {
+    pub fn new(source: &str) -> Result<Formula, Error> {
+        let source = source.trim();
+        Ok(Formula {
+            expr: parse(source)?,
+            source: format!("={}", source.strip_prefix('=').unwrap_or(source)),
+            value: Value::Empty, // <-- filled in by the sheet
+        })
+    }
+
+    // The formula as typed, '=' included
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn expr(&self) -> &Expr {
+        &self.expr
+    }
+
+    pub fn value(&self) -> &Value {
+        &self.value
+    }
+
+    // A formula showing nothing would look like an empty cell, so a
+    // formula that's just a reference to a blank is 0 like elsewhere
+    pub fn evaluate(&self, ctx: &dyn Context) -> Value {
+        match self.expr.eval(ctx) {
+            Value::Empty => Value::Int(0),
+            value => value,
+        }
+    }
+
+    pub fn set_value(&mut self, value: Value) {
+        self.value = value;
+    }
+
+    // Move the references when rows or columns are inserted or deleted,
+    // the source is rewritten from the expression only if something moved
+    pub fn shift(&mut self, shift: Shift) {
+        if self.expr.shift(shift, None) {
+            self.source = format!("={}", self.expr);
+        }
+    }
+
+    // The same for rows or columns of another sheet, Sales!B5 is Sales!B6
+    // after a row is inserted above it on Sales
+    pub fn shift_sheet(&mut self, sheet: &str, shift: Shift) {
+        if self.expr.shift(shift, Some(sheet)) {
+            self.source = format!("={}", self.expr);
+        }
+    }
+
+    pub fn rename_sheet(&mut self, from: &str, to: Option<&str>) {
+        if self.expr.rename_sheet(from, to) {
+            self.source = format!("={}", self.expr);
+        }
+    }
+
+    pub fn references(&self) -> (Vec<CellRef>, Vec<Range>) {
+        let (mut cells, mut ranges) = (Vec::new(), Vec::new());
+        self.expr.references(&mut cells, &mut ranges);
+        (cells, ranges)
+    }
+
+    pub fn sheet_references(&self) -> SheetReferences<'_> {
+        let (mut cells, mut ranges) = (Vec::new(), Vec::new());
+        self.expr.sheet_references(&mut cells, &mut ranges);
+        (cells, ranges)
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
@@ -0,0 +1,463 @@
+// The functions formulas can call. Arguments are coerced by the rules at
+// the top of value.rs, with one difference for the functions that work
+// over lists of values (SUM, AVERAGE, MIN, MAX, COUNT, AND, OR): values
+// that come from cells are only used if they're the right type, so
+// SUM(A1:A10) skips any text in the range while SUM("x") is #VALUE!.
+use super::{
+    formula::{Context, Expr},
+    value::{arithmetic, CellError, Number, Value},
+    CellRef, Money, Range,
+};
+use std::cmp::Ordering;
+
+// Name, fewest arguments and most arguments (None for no limit)
+pub const FUNCTIONS: [(&str, usize, Option<usize>); 12] = [
+    ("SUM", 1, None),
+    ("AVERAGE", 1, None),
+    ("MIN", 1, None),
+    ("MAX", 1, None),
+    ("COUNT", 1, None),
+    ("IF", 2, Some(3)),
+    ("AND", 1, None),
+    ("OR", 1, None),
+    ("CONCAT", 1, None),
+    ("LEN", 1, Some(1)),
+    ("ROUND", 1, Some(2)),
+    ("VLOOKUP", 3, Some(4)),
+];
+
+// What a known function expects if it's given the wrong number of
+// arguments, None if the count is fine or the function is unknown
+pub fn check_arity(name: &str, count: usize) -> Option<String> {
+    let (_, least, most) = FUNCTIONS.iter().find(|(known, _, _)| *known == name)?;
+    let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+    match most {
+        Some(most) if count < *least || count > *most => Some(match most == least {
+            true => format!("{least} {}", plural(*least)),
+            false => format!("{least} to {most} arguments"),
+        }),
+        None if count < *least => Some(format!("at least {least} {}", plural(*least))),
+        _ => None,
+    }
+}
+
+// An argument, either values read from cells or a value worked out.
+// Blank cells aren't read at all, every function over cells skips them.
+enum Arg {
+    Cells(Vec<Value>),
+    Value(Value),
+}
+
+fn values(filled: Vec<(CellRef, Value)>) -> Vec<Value> {
+    filled.into_iter().map(|(_, value)| value).collect()
+}
+
+fn arg(expr: &Expr, ctx: &dyn Context) -> Arg {
+    match expr {
+        Expr::Range(range) => Arg::Cells(values(ctx.filled(*range))),
+        Expr::Ref(at) => Arg::Cells(vec![ctx.value(*at)]),
+        Expr::SheetRange(sheet, range) => Arg::Cells(values(ctx.sheet_filled(sheet, *range))),
+        Expr::SheetRef(sheet, at) => Arg::Cells(vec![ctx.sheet_value(sheet, *at)]),
+        _ => Arg::Value(expr.eval(ctx)),
+    }
+}
+
+// Every number in the arguments, for the aggregates
+fn numbers(args: &[Expr], ctx: &dyn Context) -> Result<Vec<Number>, CellError> {
+    let mut numbers = Vec::new();
+    for expr in args {
+        match arg(expr, ctx) {
+            Arg::Cells(values) => {
+                for value in values {
+                    match value {
+                        Value::Int(_) | Value::Float(_) | Value::Money(_) => {
+                            numbers.push(value.to_number()?)
+                        }
+                        Value::Error(err) => return Err(err),
+                        _ => {}
+                    }
+                }
+            }
+            Arg::Value(value) => numbers.push(value.to_number()?),
+        }
+    }
+    Ok(numbers)
+}
+
+fn bools(args: &[Expr], ctx: &dyn Context) -> Result<Vec<bool>, CellError> {
+    let mut bools = Vec::new();
+    for expr in args {
+        match arg(expr, ctx) {
+            Arg::Cells(values) => {
+                for value in values {
+                    match value {
+                        Value::Empty | Value::Text(_) => {}
+                        value => bools.push(value.to_bool()?),
+                    }
+                }
+            }
+            Arg::Value(value) => bools.push(value.to_bool()?),
+        }
+    }
+    Ok(bools)
+}
+
+pub fn sum(numbers: &[Number]) -> Result<Number, CellError> {
+    numbers
+        .iter()
+        .try_fold(Number::Int(0), |total, n| arithmetic('+', total, *n))
+}
+
+pub fn extreme(numbers: Vec<Number>, keep: Ordering) -> Number {
+    numbers
+        .into_iter()
+        .reduce(
+            |best, n| match n.as_f64().total_cmp(&best.as_f64()) == keep {
+                true => n,
+                false => best,
+            },
+        )
+        .unwrap_or(Number::Int(0)) // <-- MAX of an empty range is 0, not -infinity
+}
+
+// ROUND(x, digits): halves round away from zero, and digits can be
+// negative to round to tens, hundreds and so on
+fn round(x: Number, digits: i32) -> Result<Number, CellError> {
+    // whole numbers stay whole, going through a float would lose the last
+    // digits of anything past 2^53
+    if let Number::Int(n) = x {
+        if digits >= 0 {
+            return Ok(x);
+        }
+        let Some(scale) = 10i128.checked_pow(digits.unsigned_abs()) else {
+            return Ok(Number::Int(0)); // <-- far more digits than an i64 has
+        };
+        let n = n as i128;
+        let rounded = (n + n.signum() * (scale / 2)) / scale * scale;
+        return i64::try_from(rounded)
+            .map(Number::Int)
+            .map_err(|_| CellError::Value);
+    }
+    // Shifting the decimal point by rewriting "2.675" as "2.675e2" rounds
+    // what the user sees, multiplying by 100 would give 267.49999...
+    let shift = |n: f64, by: i32| format!("{n}e{by}").parse::<f64>();
+    let rounded = shift(x.as_f64(), digits)
+        .and_then(|scaled| shift(scaled.round(), -digits))
+        .map_err(|_| CellError::Value)?;
+    match x {
+        Number::Int(n) => Ok(Number::Int(n)),
+        Number::Float(_) => Ok(Number::Float(rounded)),
+        Number::Money(money) if digits >= 2 => Ok(Number::Money(money)),
+        Number::Money(money) => {
+            let cents = (rounded * 100.0).round() as i64;
+            Ok(Number::Money(Money::new(cents, money.symbol())))
+        }
+    }
+}
+
+// VLOOKUP(key, range, column, approximate): find the key in the first
+// column of the range and return the value in the given column of that
+// row. An exact lookup wants an equal value, an approximate one expects
+// the first column to be sorted and takes the last row that's not bigger.
+fn vlookup(args: &[Expr], ctx: &dyn Context) -> Result<Value, CellError> {
+    let key = args[0].eval(ctx);
+    if let Value::Error(err) = key {
+        return Err(err);
+    }
+    let (sheet, range) = match &args[1] {
+        Expr::Range(range) => (None, *range),
+        Expr::Ref(at) => (None, Range::new(*at, *at)),
+        Expr::SheetRange(sheet, range) => (Some(sheet), *range),
+        Expr::SheetRef(sheet, at) => (Some(sheet), Range::new(*at, *at)),
+        _ => return Err(CellError::Value),
+    };
+    let value = |at: CellRef| match sheet {
+        Some(sheet) => ctx.sheet_value(sheet, at),
+        None => ctx.value(at),
+    };
+    // a blank is never the same kind as the key, so only the filled cells
+    // of the first column are looked at
+    let first_column = Range::new(range.start, CellRef::new(range.end.row, range.start.col));
+    let first_column = match sheet {
+        Some(sheet) => ctx.sheet_filled(sheet, first_column),
+        None => ctx.filled(first_column),
+    };
+    let column = args[2].eval(ctx).to_number()?.as_f64().trunc();
+    if column < 1.0 {
+        return Err(CellError::Value);
+    }
+    if column > range.cols() as f64 {
+        return Err(CellError::Ref);
+    }
+    let approximate = match args.get(3) {
+        Some(expr) => expr.eval(ctx).to_bool()?,
+        None => true,
+    };
+
+    let same_kind = |a: &Value, b: &Value| {
+        a.is_number() && b.is_number()
+            || matches!(
+                (a, b),
+                (Value::Text(_), Value::Text(_)) | (Value::Bool(_), Value::Bool(_))
+            )
+    };
+    let mut found = None;
+    for (at, first) in first_column {
+        let row = at.row;
+        if !same_kind(&first, &key) {
+            continue;
+        }
+        match first.compare(&key)? {
+            Ordering::Equal => {
+                found = Some(row);
+                break;
+            }
+            Ordering::Less if approximate => found = Some(row),
+            Ordering::Greater if approximate => break,
+            _ => {}
+        }
+    }
+    let row = found.ok_or(CellError::NotAvailable)?;
+    let at = CellRef::new(row, range.start.col + column as usize - 1);
+    Ok(value(at))
+}
+
+fn result(value: Result<Value, CellError>) -> Value {
+    value.unwrap_or_else(Value::Error)
+}
+
+// Call a function, the parser has already checked how many arguments it has
+pub fn call(name: &str, args: &[Expr], ctx: &dyn Context) -> Value {
+    match name {
+        "SUM" => numbers(args, ctx).and_then(|numbers| sum(&numbers)).into(),
+        "AVERAGE" => numbers(args, ctx)
+            .and_then(|numbers| {
+                let count = Number::Int(numbers.len() as i64);
+                arithmetic('/', sum(&numbers)?, count) // <-- nothing to average is #DIV/0!
+            })
+            .into(),
+        "MIN" => numbers(args, ctx)
+            .map(|numbers| extreme(numbers, Ordering::Less))
+            .into(),
+        "MAX" => numbers(args, ctx)
+            .map(|numbers| extreme(numbers, Ordering::Greater))
+            .into(),
+        // COUNT never fails, it counts what is a number and ignores the rest
+        "COUNT" => {
+            let count = args
+                .iter()
+                .map(|expr| match arg(expr, ctx) {
+                    Arg::Cells(values) => values.iter().filter(|v| v.is_number()).count(),
+                    Arg::Value(value) => value.to_number().is_ok() as usize,
+                })
+                .sum::<usize>();
+            Value::Int(count as i64)
+        }
+        // only the branch that's taken is evaluated, so IF(B1=0, 0, A1/B1)
+        // doesn't fail when B1 is 0
+        "IF" => match args[0].eval(ctx).to_bool() {
+            Ok(true) => args[1].eval(ctx),
+            Ok(false) => args
+                .get(2)
+                .map_or(Value::Bool(false), |expr| expr.eval(ctx)),
+            Err(err) => Value::Error(err),
+        },
+        "AND" | "OR" => result(bools(args, ctx).and_then(|bools| match bools.is_empty() {
+            true => Err(CellError::Value),
+            false if name == "AND" => Ok(Value::Bool(bools.iter().all(|b| *b))),
+            false => Ok(Value::Bool(bools.iter().any(|b| *b))),
+        })),
+        "CONCAT" => {
+            let mut text = String::new();
+            for expr in args {
+                let values = match arg(expr, ctx) {
+                    Arg::Cells(values) => values,
+                    Arg::Value(value) => vec![value],
+                };
+                for value in values {
+                    match value.to_text() {
+                        Ok(part) => text.push_str(&part),
+                        Err(err) => return Value::Error(err),
+                    }
+                }
+            }
+            Value::Text(text)
+        }
+        "LEN" => match args[0].eval(ctx).to_text() {
+            Ok(text) => Value::Int(text.chars().count() as i64),
+            Err(err) => Value::Error(err),
+        },
+        "ROUND" => {
+            let digits = match args.get(1) {
+                Some(expr) => expr.eval(ctx).to_number().map(|n| n.as_f64() as i32),
+                None => Ok(0),
+            };
+            let x = args[0].eval(ctx).to_number();
+            x.and_then(|x| round(x, digits?)).into()
+        }
+        "VLOOKUP" => result(vlookup(args, ctx)),
+        _ => Value::Error(CellError::Name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::{Sheet, SpreadsheetCell};
+
+    // A sheet with a small table in A1:B4 and a few cells to aggregate
+    fn sheet() -> Sheet {
+        let mut sheet = Sheet::new();
+        for (at, input) in [
+            ("A1", "10"),
+            ("B1", "ten"),
+            ("A2", "20"),
+            ("B2", "twenty"),
+            ("A3", "30"),
+            ("B3", "thirty"),
+            ("A4", "apple"),
+            ("B4", "fruit"),
+            ("C1", "yes"),
+            ("C2", "no"),
+            ("D1", "TRUE"),
+            ("D2", "x"),
+            ("E1", "=1/0"),
+            ("E2", "5"),
+            ("G1", "$1,234.56"),
+            ("G2", "-$2.50"),
+        ] {
+            sheet.set(
+                at.parse().unwrap(),
+                input.parse::<SpreadsheetCell>().unwrap(),
+            );
+        }
+        sheet
+    }
+
+    fn eval(sheet: &mut Sheet, source: &str) -> Value {
+        let at: CellRef = "Z99".parse().unwrap();
+        sheet.set(at, SpreadsheetCell::formula(source).unwrap());
+        sheet.value(at)
+    }
+
+    fn text(s: &str) -> Value {
+        Value::Text(s.to_owned())
+    }
+
+    fn money(cents: i64) -> Value {
+        Value::Money(Money::new(cents, '$'))
+    }
+
+    #[test]
+    fn vlookup_exact_and_approximate() {
+        let mut sheet = sheet();
+        let mut lookup = |source: &str| eval(&mut sheet, source);
+        assert_eq!(lookup("=VLOOKUP(20,A1:B4,2,FALSE)"), text("twenty"));
+        assert_eq!(lookup("=VLOOKUP(\"apple\",A1:B4,2,FALSE)"), text("fruit"));
+        assert_eq!(
+            lookup("=VLOOKUP(25,A1:B4,2,FALSE)"),
+            Value::Error(CellError::NotAvailable)
+        );
+        // approximate takes the last row that isn't bigger, skipping text
+        assert_eq!(lookup("=VLOOKUP(25,A1:B4,2)"), text("twenty"));
+        assert_eq!(lookup("=VLOOKUP(99,A1:B4,2,TRUE)"), text("thirty"));
+        assert_eq!(
+            lookup("=VLOOKUP(5,A1:B4,2)"),
+            Value::Error(CellError::NotAvailable)
+        );
+        assert_eq!(lookup("=VLOOKUP(20,A1:B4,1)"), Value::Int(20));
+        assert_eq!(lookup("=VLOOKUP(20,A1:B4,3)"), Value::Error(CellError::Ref));
+        assert_eq!(
+            lookup("=VLOOKUP(20,A1:B4,0)"),
+            Value::Error(CellError::Value)
+        );
+        assert_eq!(
+            lookup("=VLOOKUP(E1,A1:B4,2)"),
+            Value::Error(CellError::Div0)
+        );
+    }
+
+    #[test]
+    fn and_or_need_a_boolean() {
+        let mut sheet = sheet();
+        let mut logic = |source: &str| eval(&mut sheet, source);
+        assert_eq!(logic("=AND(C1:C2)"), Value::Error(CellError::Value));
+        assert_eq!(logic("=OR(C1:C2,F1:F9)"), Value::Error(CellError::Value));
+        assert_eq!(logic("=AND(D1:D2)"), Value::Bool(true));
+        assert_eq!(logic("=AND(TRUE,0)"), Value::Bool(false));
+        assert_eq!(logic("=OR(FALSE,2)"), Value::Bool(true));
+        assert_eq!(logic("=OR(\"yes\")"), Value::Error(CellError::Value));
+    }
+
+    #[test]
+    fn concat_and_len_take_anything() {
+        let mut sheet = sheet();
+        let mut call = |source: &str| eval(&mut sheet, source);
+        assert_eq!(call("=CONCAT(A1:B2)"), text("10ten20twenty"));
+        assert_eq!(call("=CONCAT(\"a\",1,2.5,TRUE)"), text("a12.5TRUE"));
+        assert_eq!(call("=CONCAT(F1,\"!\")"), text("!"));
+        assert_eq!(call("=CONCAT(\"a\",E1)"), Value::Error(CellError::Div0));
+        assert_eq!(call("=LEN(12345)"), Value::Int(5));
+        assert_eq!(call("=LEN(B3)"), Value::Int(6));
+        assert_eq!(call("=LEN(F1)"), Value::Int(0));
+        assert_eq!(call("=LEN(E1)"), Value::Error(CellError::Div0));
+    }
+
+    #[test]
+    fn errors_pass_through_the_aggregates() {
+        let mut sheet = sheet();
+        let mut call = |source: &str| eval(&mut sheet, source);
+        for function in ["SUM", "AVERAGE", "MIN", "MAX"] {
+            assert_eq!(
+                call(&format!("={function}(E1:E2)")),
+                Value::Error(CellError::Div0),
+                "{function}"
+            );
+            assert_eq!(
+                call(&format!("={function}(E2,E1)")),
+                Value::Error(CellError::Div0),
+                "{function}"
+            );
+        }
+        // COUNT only counts, and the rest skip text in ranges
+        assert_eq!(call("=COUNT(E1:E2)"), Value::Int(1));
+        assert_eq!(call("=SUM(A1:A4)"), Value::Int(60));
+        assert_eq!(call("=SUM(\"x\")"), Value::Error(CellError::Value));
+        assert_eq!(call("=AVERAGE(F1:F9)"), Value::Error(CellError::Div0));
+        assert_eq!(call("=MAX(F1:F9)"), Value::Int(0));
+    }
+
+    #[test]
+    fn round_keeps_money_and_whole_numbers_exact() {
+        let mut sheet = sheet();
+        let mut round = |source: &str| eval(&mut sheet, source);
+        assert_eq!(round("=ROUND(G1,1)"), money(123460));
+        assert_eq!(round("=ROUND(G1)"), money(123500));
+        assert_eq!(round("=ROUND(G1,-2)"), money(120000));
+        assert_eq!(round("=ROUND(G1,3)"), money(123456));
+        assert_eq!(round("=ROUND(G2)"), money(-300));
+        assert_eq!(round("=ROUND(2.675,2)"), Value::Float(2.68));
+
+        assert_eq!(round("=ROUND(15,-1)"), Value::Int(20));
+        assert_eq!(round("=ROUND(-15,-1)"), Value::Int(-20));
+        assert_eq!(round("=ROUND(149,-2)"), Value::Int(100));
+        assert_eq!(round("=ROUND(7,2)"), Value::Int(7));
+        assert_eq!(
+            round("=ROUND(9007199254740993,-1)"),
+            Value::Int(9007199254740990)
+        );
+        assert_eq!(
+            round("=ROUND(9007199254740985,-1)"),
+            Value::Int(9007199254740990)
+        );
+        assert_eq!(
+            round("=ROUND(1234567890123456789,-2)"),
+            Value::Int(1234567890123456800)
+        );
+        assert_eq!(round("=ROUND(123,-40)"), Value::Int(0));
+        assert_eq!(
+            round("=ROUND(9223372036854775807,-1)"),
+            Value::Error(CellError::Value)
+        );
+    }
+}
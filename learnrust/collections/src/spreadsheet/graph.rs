@@ -0,0 +1,301 @@
+// Which formulas read which cells. Changing a cell only recomputes the
+// formulas downstream of it, ordered so every formula is computed after
+// the cells it reads (a topological sort, Kahn's algorithm).
+//
+// A sheet's graph is over its cells, a workbook's over the cells of all
+// of its sheets, which is what the Node trait is for.
+//
+// The order can also be had in levels, for computing formulas side by
+// side: every formula in a level only reads cells that were worked out in
+// the levels before it.
+use super::{CellRef, Range};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Debug,
+    hash::Hash,
+};
+
+// Something a formula can be at and read, with the kind of range that
+// covers a block of them. Formulas reading ranges are looked up by the
+// columns the ranges cover, so a node has to say which column it's in.
+pub trait Node: Copy + Eq + Hash + Ord + Debug {
+    type Range: Copy + PartialEq + Debug;
+    type Column: Copy + Eq + Hash + Debug;
+
+    fn within(self, range: &Self::Range) -> bool;
+    fn column(self) -> Self::Column;
+    fn columns(range: &Self::Range) -> impl Iterator<Item = Self::Column>;
+}
+
+impl Node for CellRef {
+    type Range = Range;
+    type Column = usize;
+
+    fn within(self, range: &Range) -> bool {
+        range.contains(self)
+    }
+
+    fn column(self) -> usize {
+        self.col
+    }
+
+    fn columns(range: &Range) -> impl Iterator<Item = usize> {
+        range.start.col..=range.end.col
+    }
+}
+
+// What Graph::edges works out
+type Edges<N> = (HashMap<N, usize>, HashMap<N, Vec<N>>);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Graph<N: Node = CellRef> {
+    // what each formula reads, ranges are kept whole rather than expanded
+    // so SUM(A1:A100000) is one entry instead of a hundred thousand
+    precedents: HashMap<N, (Vec<N>, Vec<N::Range>)>,
+    // the reverse of the single cell references, for quick lookups
+    dependents: HashMap<N, HashSet<N>>,
+    // the formulas reading ranges, under every column their ranges cover,
+    // so finding what reads a cell through a range only searches the
+    // formulas that read its column
+    ranged: HashMap<N::Column, HashSet<N>>,
+}
+
+// Not derived, that would want N to be Default too
+impl<N: Node> Default for Graph<N> {
+    fn default() -> Graph<N> {
+        Graph {
+            precedents: HashMap::new(),
+            dependents: HashMap::new(),
+            ranged: HashMap::new(),
+        }
+    }
+}
+
+impl<N: Node> Graph<N> {
+    pub fn new() -> Graph<N> {
+        Graph::default()
+    }
+
+    // Record what the formula at `at` reads, replacing what it read before
+    pub fn set(&mut self, at: N, cells: Vec<N>, ranges: Vec<N::Range>) {
+        self.remove(at);
+        for cell in &cells {
+            self.dependents.entry(*cell).or_default().insert(at);
+        }
+        for column in ranges.iter().flat_map(N::columns) {
+            self.ranged.entry(column).or_default().insert(at);
+        }
+        self.precedents.insert(at, (cells, ranges));
+    }
+
+    // The cell no longer holds a formula
+    pub fn remove(&mut self, at: N) {
+        let Some((cells, ranges)) = self.precedents.remove(&at) else {
+            return;
+        };
+        for column in ranges.iter().flat_map(N::columns) {
+            if let Some(formulas) = self.ranged.get_mut(&column) {
+                formulas.remove(&at);
+                if formulas.is_empty() {
+                    self.ranged.remove(&column);
+                }
+            }
+        }
+        for cell in cells {
+            if let Some(dependents) = self.dependents.get_mut(&cell) {
+                dependents.remove(&at);
+                if dependents.is_empty() {
+                    self.dependents.remove(&cell);
+                }
+            }
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.precedents.clear();
+        self.dependents.clear();
+        self.ranged.clear();
+    }
+
+    pub fn formulas(&self) -> impl Iterator<Item = N> + '_ {
+        self.precedents.keys().copied()
+    }
+
+    // The formulas reading `at` directly, through a reference or a range
+    fn dependents_of(&self, at: N) -> HashSet<N> {
+        let mut found = self.dependents.get(&at).cloned().unwrap_or_default();
+        for formula in self.ranged.get(&at.column()).into_iter().flatten() {
+            let (_, ranges) = &self.precedents[formula];
+            if ranges.iter().any(|range| at.within(range)) {
+                found.insert(*formula);
+            }
+        }
+        found
+    }
+
+    // Every formula that has to be recomputed when `at` changes: the ones
+    // reading it, the ones reading those, and so on. A formula at `at`
+    // itself is included.
+    pub fn affected(&self, at: N) -> HashSet<N> {
+        let mut affected = HashSet::new();
+        if self.precedents.contains_key(&at) {
+            affected.insert(at);
+        }
+        let mut todo = vec![at];
+        while let Some(cell) = todo.pop() {
+            for dependent in self.dependents_of(cell) {
+                if affected.insert(dependent) {
+                    todo.push(dependent);
+                }
+            }
+        }
+        affected
+    }
+
+    // How many of the formulas each one waits on, and which formulas each
+    // one is waited on by
+    fn edges(&self, formulas: &HashSet<N>) -> Edges<N> {
+        let mut waiting_on: HashMap<N, usize> =
+            formulas.iter().map(|formula| (*formula, 0)).collect();
+        let mut edges: HashMap<N, Vec<N>> = HashMap::new();
+        for formula in formulas {
+            for dependent in self.dependents_of(*formula) {
+                if let Some(count) = waiting_on.get_mut(&dependent) {
+                    *count += 1;
+                    edges.entry(*formula).or_default().push(dependent);
+                }
+            }
+        }
+        (waiting_on, edges)
+    }
+
+    // The formulas that aren't waiting on anything, sorted so
+    // recalculation happens in the same order every time
+    fn ready(waiting_on: &HashMap<N, usize>) -> Vec<N> {
+        let mut ready: Vec<N> = waiting_on
+            .iter()
+            .filter(|(_, count)| **count == 0)
+            .map(|(formula, _)| *formula)
+            .collect();
+        ready.sort();
+        ready
+    }
+
+    // The ones still waiting after the sort
+    fn cyclic(waiting_on: HashMap<N, usize>) -> Vec<N> {
+        let mut cyclic: Vec<N> = waiting_on
+            .into_iter()
+            .filter(|(_, count)| *count > 0)
+            .map(|(formula, _)| formula)
+            .collect();
+        cyclic.sort();
+        cyclic
+    }
+
+    // Sort the formulas so each comes after the ones it reads. Formulas
+    // that never become ready are part of a cycle (or read one) and are
+    // returned separately.
+    pub fn order(&self, formulas: &HashSet<N>) -> (Vec<N>, Vec<N>) {
+        let (mut waiting_on, edges) = self.edges(formulas);
+        let mut ready = VecDeque::from(Graph::ready(&waiting_on));
+        let mut order = Vec::new();
+        while let Some(formula) = ready.pop_front() {
+            order.push(formula);
+            for dependent in edges.get(&formula).into_iter().flatten() {
+                let count = waiting_on.get_mut(dependent).unwrap();
+                *count -= 1;
+                if *count == 0 {
+                    ready.push_back(*dependent);
+                }
+            }
+        }
+
+        (order, Graph::cyclic(waiting_on))
+    }
+
+    // The same sort a level at a time: the first level is every formula
+    // that's ready straight away, each level after that is what the one
+    // before it made ready
+    pub fn levels(&self, formulas: &HashSet<N>) -> (Vec<Vec<N>>, Vec<N>) {
+        let (mut waiting_on, edges) = self.edges(formulas);
+        let mut levels = Vec::new();
+        let mut level = Graph::ready(&waiting_on);
+        while !level.is_empty() {
+            let mut next = Vec::new();
+            for formula in &level {
+                for dependent in edges.get(formula).into_iter().flatten() {
+                    let count = waiting_on.get_mut(dependent).unwrap();
+                    *count -= 1;
+                    if *count == 0 {
+                        next.push(*dependent);
+                    }
+                }
+            }
+            next.sort();
+            levels.push(std::mem::replace(&mut level, next));
+        }
+        (levels, Graph::cyclic(waiting_on))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> CellRef {
+        s.parse().unwrap()
+    }
+
+    fn range(s: &str) -> Range {
+        s.parse().unwrap()
+    }
+
+    fn sorted(cells: HashSet<CellRef>) -> Vec<CellRef> {
+        let mut cells: Vec<CellRef> = cells.into_iter().collect();
+        cells.sort();
+        cells
+    }
+
+    #[test]
+    fn ranges_are_found_by_column() {
+        let mut graph = Graph::new();
+        graph.set(at("D1"), vec![], vec![range("A1:B10")]);
+        graph.set(at("D2"), vec![at("D1")], vec![range("B5:C5")]);
+        graph.set(at("D3"), vec![at("A1")], vec![]);
+
+        assert_eq!(
+            sorted(graph.affected(at("A1"))),
+            [at("D1"), at("D2"), at("D3")]
+        );
+        assert_eq!(sorted(graph.affected(at("B5"))), [at("D1"), at("D2")]);
+        assert_eq!(sorted(graph.affected(at("C5"))), [at("D2")]);
+        assert_eq!(sorted(graph.affected(at("C6"))), []);
+
+        // a formula that changes what it reads leaves its old columns
+        graph.set(at("D1"), vec![], vec![range("C1:C2")]);
+        assert_eq!(sorted(graph.affected(at("A1"))), [at("D3")]);
+        assert_eq!(sorted(graph.affected(at("C1"))), [at("D1"), at("D2")]);
+        graph.remove(at("D2"));
+        graph.remove(at("D1"));
+        assert!(graph.ranged.is_empty());
+        assert_eq!(sorted(graph.affected(at("C5"))), []);
+    }
+
+    #[test]
+    fn levels_and_cycles() {
+        let mut graph = Graph::new();
+        graph.set(at("B1"), vec![at("A1")], vec![]);
+        graph.set(at("B2"), vec![at("A2")], vec![]);
+        graph.set(at("C1"), vec![], vec![range("B1:B2")]);
+        graph.set(at("E1"), vec![at("E2")], vec![]);
+        graph.set(at("E2"), vec![], vec![range("E1:E1")]);
+        let formulas: HashSet<CellRef> = graph.formulas().collect();
+
+        let (levels, cyclic) = graph.levels(&formulas);
+        assert_eq!(levels, [vec![at("B1"), at("B2")], vec![at("C1")]]);
+        assert_eq!(cyclic, [at("E1"), at("E2")]);
+        let (order, cyclic) = graph.order(&formulas);
+        assert_eq!(order, [at("B1"), at("B2"), at("C1")]);
+        assert_eq!(cyclic, [at("E1"), at("E2")]);
+    }
+}
@@ -0,0 +1,519 @@
+// Undo and redo. Every change to a sheet is made through an Edit, which
+// keeps enough of what was there before to put it back. Edits are grouped
+// into transactions, a single edit on its own is a transaction of one, and
+// undo and redo always work on a whole transaction.
+//
+// Undoing puts cells back with Sheet::set and friends, so the formulas
+// reading them are recomputed as usual and end up with their old values.
+use super::{column_name, CellRef, Error, Format, Range, Sheet, SpreadsheetCell};
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    Set {
+        at: CellRef,
+        before: SpreadsheetCell,
+        after: SpreadsheetCell,
+    },
+    InsertRow(usize),
+    InsertCol(usize),
+    // deleting rewrites the references to what was deleted as #REF!, so
+    // every formula is kept as it was to undo that too. The format layers
+    // are kept whole, there are only ever a few of them.
+    DeleteRow {
+        at: usize,
+        cells: Vec<(usize, SpreadsheetCell)>, // <-- the filled ones, by column
+        formulas: Vec<(CellRef, SpreadsheetCell)>,
+        formats: Vec<(Range, Format)>,
+    },
+    DeleteCol {
+        at: usize,
+        cells: Vec<(usize, SpreadsheetCell)>, // <-- and by row
+        formulas: Vec<(CellRef, SpreadsheetCell)>,
+        formats: Vec<(Range, Format)>,
+    },
+    Sort {
+        range: Range,
+        by: usize,
+        descending: bool,
+        before: Vec<(CellRef, SpreadsheetCell)>, // <-- the filled cells of the range
+        formats: Vec<(Range, Format)>,
+    },
+    Format {
+        range: Range,
+        before: Vec<(Range, Format)>,
+        after: Format,
+    },
+    // a whole new sheet, like a file being loaded over the old one
+    Replace {
+        before: Box<Sheet>,
+        after: Box<Sheet>,
+    },
+}
+
+// Every formula outside of a row or column, with where it is
+fn formulas_outside(
+    sheet: &Sheet,
+    outside: impl Fn(CellRef) -> bool,
+) -> Vec<(CellRef, SpreadsheetCell)> {
+    sheet
+        .cells()
+        .filter(|(at, cell)| outside(*at) && matches!(cell, SpreadsheetCell::Formula(_)))
+        .map(|(at, cell)| (at, cell.clone()))
+        .collect()
+}
+
+// Every format layer, to put back afterwards
+fn formats(sheet: &Sheet) -> Vec<(Range, Format)> {
+    sheet
+        .formats()
+        .map(|(range, format)| (range, format.clone()))
+        .collect()
+}
+
+// Making the changes. Each one changes the sheet and hands back the edit
+// to record.
+impl Edit {
+    pub fn set(sheet: &mut Sheet, at: CellRef, cell: SpreadsheetCell) -> Edit {
+        let before = sheet.set(at, cell.clone());
+        Edit::Set {
+            at,
+            before,
+            after: cell,
+        }
+    }
+
+    pub fn insert_row(sheet: &mut Sheet, at: usize) -> Result<Edit, Error> {
+        sheet.insert_row(at)?;
+        Ok(Edit::InsertRow(at))
+    }
+
+    pub fn insert_col(sheet: &mut Sheet, at: usize) -> Result<Edit, Error> {
+        sheet.insert_col(at)?;
+        Ok(Edit::InsertCol(at))
+    }
+
+    pub fn delete_row(sheet: &mut Sheet, at: usize) -> Result<Edit, Error> {
+        let formulas = formulas_outside(sheet, |cell| cell.row != at);
+        let formats = formats(sheet);
+        let cells = sheet.delete_row(at)?;
+        Ok(Edit::DeleteRow {
+            at,
+            cells,
+            formulas,
+            formats,
+        })
+    }
+
+    pub fn delete_col(sheet: &mut Sheet, at: usize) -> Result<Edit, Error> {
+        let formulas = formulas_outside(sheet, |cell| cell.col != at);
+        let formats = formats(sheet);
+        let cells = sheet.delete_col(at)?;
+        Ok(Edit::DeleteCol {
+            at,
+            cells,
+            formulas,
+            formats,
+        })
+    }
+
+    pub fn sort(
+        sheet: &mut Sheet,
+        range: Range,
+        by: usize,
+        descending: bool,
+    ) -> Result<Edit, Error> {
+        let before = sheet
+            .cells_in(range)
+            .map(|(at, cell)| (at, cell.clone()))
+            .collect();
+        let formats = formats(sheet);
+        sheet.sort_rows(range, by, descending)?;
+        Ok(Edit::Sort {
+            range,
+            by,
+            descending,
+            before,
+            formats,
+        })
+    }
+
+    pub fn format(sheet: &mut Sheet, range: Range, format: Format) -> Edit {
+        let before = formats(sheet);
+        sheet.format_range(range, format.clone());
+        Edit::Format {
+            range,
+            before,
+            after: format,
+        }
+    }
+
+    pub fn replace(sheet: &mut Sheet, new: Sheet) -> Edit {
+        let before = std::mem::replace(sheet, new.clone());
+        Edit::Replace {
+            before: Box::new(before),
+            after: Box::new(new),
+        }
+    }
+
+    // Put the sheet back the way it was before the edit
+    pub fn undo(&self, sheet: &mut Sheet) {
+        // the edit succeeded once on this same sheet, so these can't fail
+        let undone = match self {
+            Edit::Set { at, before, .. } => {
+                sheet.set(*at, before.clone());
+                Ok(())
+            }
+            Edit::InsertRow(at) => sheet.delete_row(*at).map(|_| ()),
+            Edit::InsertCol(at) => sheet.delete_col(*at).map(|_| ()),
+            Edit::DeleteRow {
+                at,
+                cells,
+                formulas,
+                formats,
+            } => sheet.insert_row(*at).map(|_| {
+                let row = cells
+                    .iter()
+                    .map(|(col, cell)| (CellRef::new(*at, *col), cell.clone()));
+                sheet.set_many(row.chain(formulas.iter().cloned()));
+                sheet.set_formats(formats.clone());
+            }),
+            Edit::DeleteCol {
+                at,
+                cells,
+                formulas,
+                formats,
+            } => sheet.insert_col(*at).map(|_| {
+                let col = cells
+                    .iter()
+                    .map(|(row, cell)| (CellRef::new(*row, *at), cell.clone()));
+                sheet.set_many(col.chain(formulas.iter().cloned()));
+                sheet.set_formats(formats.clone());
+            }),
+            Edit::Sort {
+                range,
+                before,
+                formats,
+                ..
+            } => {
+                let sorted: Vec<CellRef> = sheet.cells_in(*range).map(|(at, _)| at).collect();
+                let cleared = sorted.into_iter().map(|at| (at, SpreadsheetCell::Empty));
+                sheet.set_many(cleared.chain(before.iter().cloned()));
+                sheet.set_formats(formats.clone());
+                Ok(())
+            }
+            Edit::Format { before, .. } => {
+                sheet.set_formats(before.clone());
+                Ok(())
+            }
+            Edit::Replace { before, .. } => {
+                *sheet = (**before).clone();
+                Ok(())
+            }
+        };
+        undone.expect("undoing an edit that was made on this sheet");
+    }
+
+    // Make the edit again after it was undone
+    pub fn redo(&self, sheet: &mut Sheet) {
+        let redone = match self {
+            Edit::Set { at, after, .. } => {
+                sheet.set(*at, after.clone());
+                Ok(())
+            }
+            Edit::InsertRow(at) => sheet.insert_row(*at),
+            Edit::InsertCol(at) => sheet.insert_col(*at),
+            Edit::DeleteRow { at, .. } => sheet.delete_row(*at).map(|_| ()),
+            Edit::DeleteCol { at, .. } => sheet.delete_col(*at).map(|_| ()),
+            Edit::Sort {
+                range,
+                by,
+                descending,
+                ..
+            } => sheet.sort_rows(*range, *by, *descending),
+            Edit::Format { range, after, .. } => {
+                Edit::format(sheet, *range, after.clone());
+                Ok(())
+            }
+            Edit::Replace { after, .. } => {
+                *sheet = (**after).clone();
+                Ok(())
+            }
+        };
+        redone.expect("redoing an edit that was undone on this sheet");
+    }
+}
+
+impl fmt::Display for Edit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Edit::Set {
+                at,
+                after: SpreadsheetCell::Empty,
+                ..
+            } => write!(f, "clear {at}"),
+            Edit::Set {
+                at,
+                after: SpreadsheetCell::Formula(formula),
+                ..
+            } => write!(f, "set {at} {}", formula.source()),
+            Edit::Set { at, after, .. } => write!(f, "set {at} {after}"),
+            Edit::InsertRow(at) => write!(f, "insert row {}", at + 1),
+            Edit::InsertCol(at) => write!(f, "insert col {}", column_name(*at)),
+            Edit::DeleteRow { at, .. } => write!(f, "delete row {}", at + 1),
+            Edit::DeleteCol { at, .. } => write!(f, "delete col {}", column_name(*at)),
+            Edit::Sort {
+                range,
+                by,
+                descending,
+                ..
+            } => {
+                let order = if *descending { "desc" } else { "asc" };
+                write!(f, "sort {range} by {} {order}", column_name(*by))
+            }
+            Edit::Format { range, after, .. } => write!(f, "format {range} {after}"),
+            Edit::Replace { .. } => write!(f, "replace the sheet"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transaction {
+    pub name: Option<String>, // <-- single edits don't have one
+    pub edits: Vec<Edit>,
+}
+
+impl fmt::Display for Transaction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match (&self.name, self.edits.as_slice()) {
+            (None, [edit]) => write!(f, "{edit}"),
+            (name, edits) => {
+                let name = name.as_deref().unwrap_or("transaction");
+                let edits: Vec<String> = edits.iter().map(|edit| edit.to_string()).collect();
+                write!(f, "{name} ({})", edits.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct History {
+    done: Vec<Transaction>,
+    undone: Vec<Transaction>, // <-- newest last, what redo works through
+    open: Option<Transaction>,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    // Keep an edit that was just made. Anything that was undone can't be
+    // redone any more, the sheet has gone a different way.
+    pub fn record(&mut self, edit: Edit) {
+        self.undone.clear();
+        match &mut self.open {
+            Some(transaction) => transaction.edits.push(edit),
+            None => self.done.push(Transaction {
+                name: None,
+                edits: vec![edit],
+            }),
+        }
+    }
+
+    // Group the edits from here to commit() so they're undone together
+    pub fn begin(&mut self, name: Option<String>) -> Result<(), Error> {
+        if self.open.is_some() {
+            return Err(Error::Transaction(
+                "a transaction is already open, commit it first",
+            ));
+        }
+        self.open = Some(Transaction {
+            name,
+            edits: Vec::new(),
+        });
+        Ok(())
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.open.is_some()
+    }
+
+    pub fn commit(&mut self) -> Result<(), Error> {
+        let transaction = self
+            .open
+            .take()
+            .ok_or(Error::Transaction("there's no transaction to commit"))?;
+        if !transaction.edits.is_empty() {
+            self.done.push(transaction);
+        }
+        Ok(())
+    }
+
+    // Undo everything since begin() and forget the transaction
+    pub fn rollback(&mut self, sheet: &mut Sheet) -> Result<(), Error> {
+        let transaction = self
+            .open
+            .take()
+            .ok_or(Error::Transaction("there's no transaction to roll back"))?;
+        for edit in transaction.edits.iter().rev() {
+            edit.undo(sheet);
+        }
+        Ok(())
+    }
+
+    // Undo the last transaction, newest edit first, and say what it was
+    pub fn undo(&mut self, sheet: &mut Sheet) -> Result<String, Error> {
+        if self.open.is_some() {
+            return Err(Error::Transaction(
+                "commit or roll back the open transaction first",
+            ));
+        }
+        let transaction = self.done.pop().ok_or(Error::NothingToUndo)?;
+        for edit in transaction.edits.iter().rev() {
+            edit.undo(sheet);
+        }
+        let description = transaction.to_string();
+        self.undone.push(transaction);
+        Ok(description)
+    }
+
+    pub fn redo(&mut self, sheet: &mut Sheet) -> Result<String, Error> {
+        if self.open.is_some() {
+            return Err(Error::Transaction(
+                "commit or roll back the open transaction first",
+            ));
+        }
+        let transaction = self.undone.pop().ok_or(Error::NothingToRedo)?;
+        for edit in &transaction.edits {
+            edit.redo(sheet);
+        }
+        let description = transaction.to_string();
+        self.done.push(transaction);
+        Ok(description)
+    }
+
+    // Everything that can be undone, oldest first, followed by everything
+    // that can be redone, next one first
+    pub fn entries(&self) -> (&[Transaction], Vec<&Transaction>) {
+        (&self.done, self.undone.iter().rev().collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::spreadsheet::Value;
+
+    type Snapshot = (Vec<(CellRef, SpreadsheetCell)>, Vec<(Range, Format)>);
+
+    fn at(address: &str) -> CellRef {
+        address.parse().unwrap()
+    }
+
+    fn snapshot(sheet: &Sheet) -> Snapshot {
+        let cells = sheet.cells().map(|(at, cell)| (at, cell.clone()));
+        (cells.collect(), formats(sheet))
+    }
+
+    // Three rows of numbers with formulas reading them and a format
+    fn sheet() -> Sheet {
+        let mut sheet = Sheet::new();
+        for (address, cell) in [
+            ("A1", "3"),
+            ("A2", "1"),
+            ("A3", "2"),
+            ("B1", "=A1*10"),
+            ("B2", "=A2*10"),
+            ("B3", "=A3*10"),
+            ("C1", "=SUM(B1:B3)"),
+            ("D2", "=A2+A3"),
+        ] {
+            sheet.set(at(address), cell.parse::<SpreadsheetCell>().unwrap());
+        }
+        sheet.set_format(at("A2"), "0.00".parse().unwrap());
+        sheet
+    }
+
+    // Every kind of edit, undone and redone on its own
+    fn range() -> Range {
+        Range::new(at("A1"), at("B3"))
+    }
+
+    #[test]
+    fn undo_and_redo_are_inverses() {
+        let edits: Vec<fn(&mut Sheet) -> Edit> = vec![
+            |sheet| Edit::set(sheet, at("A1"), SpreadsheetCell::Int(7)),
+            |sheet| Edit::set(sheet, at("B1"), SpreadsheetCell::Empty),
+            |sheet| Edit::insert_row(sheet, 1).unwrap(),
+            |sheet| Edit::insert_col(sheet, 0).unwrap(),
+            |sheet| Edit::delete_row(sheet, 1).unwrap(),
+            |sheet| Edit::delete_col(sheet, 0).unwrap(),
+            |sheet| Edit::sort(sheet, range(), 0, false).unwrap(),
+            |sheet| Edit::sort(sheet, range(), 1, true).unwrap(),
+            |sheet| Edit::format(sheet, range(), "0%".parse().unwrap()),
+            |sheet| Edit::replace(sheet, Sheet::new()),
+        ];
+        for make in edits {
+            let mut sheet = sheet();
+            let before = snapshot(&sheet);
+            let edit = make(&mut sheet);
+            let after = snapshot(&sheet);
+            edit.undo(&mut sheet);
+            assert_eq!(snapshot(&sheet), before, "undoing {edit}");
+            edit.redo(&mut sheet);
+            assert_eq!(snapshot(&sheet), after, "redoing {edit}");
+        }
+    }
+
+    #[test]
+    fn deleted_references_come_back() {
+        let source = |sheet: &Sheet, address| match sheet.get(at(address)) {
+            SpreadsheetCell::Formula(formula) => formula.source().to_owned(),
+            other => panic!("{address} holds {other:?}"),
+        };
+        let mut sheet = sheet();
+        let edit = Edit::delete_col(&mut sheet, 0).unwrap();
+        assert_eq!(source(&sheet, "A1"), "=#REF!*10");
+        assert_eq!(source(&sheet, "C2"), "=#REF!+#REF!");
+        edit.undo(&mut sheet);
+        assert_eq!(source(&sheet, "B1"), "=A1*10");
+        assert_eq!(source(&sheet, "D2"), "=A2+A3");
+        assert_eq!(sheet.value(at("C1")), Value::Int(60));
+    }
+
+    #[test]
+    fn transactions() {
+        let mut sheet = sheet();
+        let mut history = History::new();
+        let before = snapshot(&sheet);
+        history.begin(Some(String::from("two"))).unwrap();
+        history.record(Edit::set(&mut sheet, at("A1"), SpreadsheetCell::Int(5)));
+        history.record(Edit::delete_col(&mut sheet, 3).unwrap());
+        assert!(history.undo(&mut sheet).is_err());
+        history.commit().unwrap();
+        let after = snapshot(&sheet);
+
+        assert_eq!(
+            history.undo(&mut sheet).unwrap(),
+            "two (set A1 5, delete col D)"
+        );
+        assert_eq!(snapshot(&sheet), before);
+        history.redo(&mut sheet).unwrap();
+        assert_eq!(snapshot(&sheet), after);
+        assert_eq!(history.redo(&mut sheet), Err(Error::NothingToRedo));
+
+        history.begin(None).unwrap();
+        history.record(Edit::set(&mut sheet, at("A3"), SpreadsheetCell::Int(0)));
+        history.rollback(&mut sheet).unwrap();
+        assert_eq!(snapshot(&sheet), after);
+
+        // a new edit after an undo can't be redone past
+        history.undo(&mut sheet).unwrap();
+        history.record(Edit::set(&mut sheet, at("A1"), SpreadsheetCell::Int(1)));
+        assert_eq!(history.redo(&mut sheet), Err(Error::NothingToRedo));
+        history.undo(&mut sheet).unwrap();
+        assert_eq!(snapshot(&sheet), before);
+        assert_eq!(history.undo(&mut sheet), Err(Error::NothingToUndo));
+    }
+}
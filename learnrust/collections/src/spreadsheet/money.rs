@@ -0,0 +1,140 @@
+// Amounts of money, kept as a whole number of cents (or pence, or euro
+// cents) so that adding them up never goes through floats and picks up a
+// stray 0.000000001. Formulas get them as money too and keep the cents
+// exact through sums and the like, see value.rs.
+use super::{
+    format::{group, ungroup},
+    Error,
+};
+use std::{fmt, str::FromStr};
+
+// The currencies that are written with a symbol up front, all of them
+// with two decimal places
+const SYMBOLS: [char; 3] = ['$', '€', '£'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    minor: i64, // <-- in hundredths
+    symbol: char,
+}
+
+impl Money {
+    pub fn new(minor: i64, symbol: char) -> Money {
+        Money { minor, symbol }
+    }
+
+    pub fn minor(self) -> i64 {
+        self.minor
+    }
+
+    pub fn symbol(self) -> char {
+        self.symbol
+    }
+
+    pub fn to_f64(self) -> f64 {
+        self.minor as f64 / 100.0
+    }
+
+    // Written with `decimals` places, rounded half away from zero when
+    // that's fewer than two, without a float in sight
+    pub fn format(self, decimals: usize, thousands: bool) -> String {
+        let cents = self.minor.unsigned_abs() as u128;
+        let places = decimals.min(2);
+        let scale = 10u128.pow(2 - places as u32);
+        let rounded = (cents + scale / 2) / scale;
+        let unit = 10u128.pow(places as u32);
+        let whole = (rounded / unit).to_string();
+        let whole = if thousands { group(&whole) } else { whole };
+        let sign = if self.minor < 0 && rounded > 0 {
+            "-"
+        } else {
+            ""
+        };
+        let mut out = format!("{sign}{}{whole}", self.symbol);
+        if decimals > 0 {
+            let fraction = rounded % unit;
+            out.push_str(&format!(
+                ".{fraction:0places$}{}",
+                "0".repeat(decimals - places)
+            ));
+        }
+        out
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.format(2, true))
+    }
+}
+
+// "$1,234.50", "-€3" or "£-0.5": a symbol, a sign before or after it, and
+// at most two decimal places. Thousands separators are optional but have
+// to be in the right places.
+impl FromStr for Money {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Money, Error> {
+        let bad = || Error::BadValue {
+            expected: "currency amount like $1,234.50",
+            text: s.to_owned(),
+        };
+        let s = s.trim();
+        let (negative, rest) = match s.strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let symbol = rest.chars().next().filter(|c| SYMBOLS.contains(c));
+        let symbol = symbol.ok_or_else(bad)?;
+        let rest = &rest[symbol.len_utf8()..];
+        let (negative, rest) = match rest.strip_prefix('-') {
+            Some(rest) if !negative => (true, rest),
+            _ => (negative, rest),
+        };
+        let number = ungroup(rest).ok_or_else(bad)?;
+        let (whole, fraction) = number.split_once('.').unwrap_or((&number, ""));
+        let digits = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if whole.is_empty() || !digits(whole) || !digits(fraction) || fraction.len() > 2 {
+            return Err(bad());
+        }
+        let cents = format!("{whole}{fraction:0<2}");
+        let minor: i64 = cents.parse().map_err(|_| bad())?;
+        Ok(Money::new(if negative { -minor } else { minor }, symbol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_the_ways_its_written() {
+        for (text, minor, symbol) in [
+            ("$1,234.50", 123450, '$'),
+            ("-€3", -300, '€'),
+            ("£-0.5", -50, '£'),
+            ("$0.07", 7, '$'),
+            (" $12 ", 1200, '$'),
+        ] {
+            assert_eq!(text.parse(), Ok(Money::new(minor, symbol)), "{text}");
+        }
+        for text in ["1.50", "$", "$1.234", "$12,34", "--$1", "¥100", "$1e3"] {
+            assert!(text.parse::<Money>().is_err(), "{text}");
+        }
+    }
+
+    #[test]
+    fn shows_whole_cents() {
+        let money = Money::new(-123456, '€');
+        assert_eq!(money.to_string(), "-€1,234.56");
+        assert_eq!(money.format(0, false), "-€1235");
+        assert_eq!(money.format(1, true), "-€1,234.6");
+        assert_eq!(money.format(4, false), "-€1234.5600");
+        // rounding to nothing leaves no sign
+        assert_eq!(Money::new(-4, '$').format(0, false), "$0");
+        assert_eq!(
+            Money::new(i64::MIN, '$').format(0, false),
+            "-$92233720368547758"
+        );
+    }
+}
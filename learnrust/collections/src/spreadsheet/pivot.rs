@@ -0,0 +1,396 @@
+// Pivot tables: the rows of a sheet grouped by the values in some key
+// columns, with value columns summed, counted, averaged and so on for each
+// group. As a query (see query.rs) it reads
+//
+//   PIVOT SUM(qty), AVG(price) BY region, product ACROSS quarter SUBTOTALS TOTALS
+//
+// BY groups down the side, one row for each distinct key, ACROSS spreads
+// the groups over the columns, one column for each distinct key (and
+// aggregate). Either one can be left out. SUBTOTALS adds a row after each
+// group of an outer BY column, TOTALS a grand total row, and a column too
+// with ACROSS. Totals are worked out from the rows themselves rather than
+// from the groups, so the total of an AVG is the average of every row.
+//
+// Keys are grouped and ordered the way ORDER BY sorts, text ignoring case
+// and blanks last, and are shown as the cells they came from, so a date
+// is still a date. A sum with a Float in it stays a Float, one of money
+// stays money, and an average is only an Int when it comes out whole. MIN
+// and MAX are the cell they picked.
+use super::{
+    column_name,
+    functions::{extreme, sum},
+    query::{data_rows, headers, plain},
+    value::{arithmetic, Number},
+    CellRef, Error, Sheet, SpreadsheetCell, Value,
+};
+use std::{cmp::Ordering, fmt, ops::Range};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Aggregate {
+    Sum,
+    Count, // <-- counts whatever isn't blank, text included
+    Average,
+    Min,
+    Max,
+}
+
+impl fmt::Display for Aggregate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Aggregate::Sum => "SUM",
+            Aggregate::Count => "COUNT",
+            Aggregate::Average => "AVG",
+            Aggregate::Min => "MIN",
+            Aggregate::Max => "MAX",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Aggregate {
+    pub fn from_name(name: &str) -> Option<Aggregate> {
+        match name.to_uppercase().as_str() {
+            "SUM" => Some(Aggregate::Sum),
+            "COUNT" => Some(Aggregate::Count),
+            "AVG" | "AVERAGE" => Some(Aggregate::Average),
+            "MIN" => Some(Aggregate::Min),
+            "MAX" => Some(Aggregate::Max),
+            _ => None,
+        }
+    }
+
+    // Like the functions of the same name over a range: text and blanks are
+    // skipped and an error in any of the values is the answer. No values at
+    // all is a hole in the table and stays blank.
+    fn apply(self, cells: &[&SpreadsheetCell]) -> SpreadsheetCell {
+        if cells.is_empty() {
+            return SpreadsheetCell::Empty;
+        }
+        if self == Aggregate::Count {
+            let count = cells.iter().filter(|cell| !cell.is_empty()).count();
+            return SpreadsheetCell::Int(count as i64);
+        }
+        let mut numbers = Vec::new();
+        let mut picked = Vec::new(); // <-- the cells the numbers came from
+        for cell in cells {
+            match cell.value() {
+                Value::Error(err) => return Value::Error(err).into(),
+                value if value.is_number() => {
+                    numbers.push(value.to_number().unwrap());
+                    picked.push(*cell);
+                }
+                _ => {}
+            }
+        }
+        let extreme = |keep| {
+            let n = extreme(numbers.clone(), keep);
+            let cell = picked.iter().zip(&numbers).find(|(_, m)| **m == n);
+            cell.map_or_else(|| Value::from(n).into(), |(cell, _)| plain(cell))
+        };
+        match self {
+            Aggregate::Sum => Value::from(sum(&numbers)).into(),
+            Aggregate::Average => Value::from(
+                sum(&numbers)
+                    .and_then(|total| arithmetic('/', total, Number::Int(numbers.len() as i64))),
+            )
+            .into(),
+            Aggregate::Min => extreme(Ordering::Less),
+            _ => extreme(Ordering::Greater),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Pivot {
+    pub values: Vec<(Aggregate, usize)>, // <-- what to work out, from which column
+    pub by: Vec<usize>,
+    pub across: Vec<usize>,
+    pub subtotals: bool,
+    pub totals: bool,
+}
+
+// A row's cells in some of its columns
+type Key<'a> = Vec<&'a SpreadsheetCell>;
+
+// Keys compare column by column, blanks last
+fn compare_keys(a: &[&SpreadsheetCell], b: &[&SpreadsheetCell]) -> Ordering {
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| match (a.is_empty(), b.is_empty()) {
+            (false, false) => a.value().sort_cmp(&b.value()),
+            (a, b) => a.cmp(&b),
+        })
+        .find(|ordering| *ordering != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+fn same(a: &[&SpreadsheetCell], b: &[&SpreadsheetCell]) -> bool {
+    compare_keys(a, b) == Ordering::Equal
+}
+
+// Every key once, in order. "North" and "north" are the same group, shown
+// the way it's first spelled after sorting.
+fn distinct(mut keys: Vec<Key>) -> Vec<Key> {
+    keys.sort_by(|a, b| compare_keys(a, b));
+    keys.dedup_by(|a, b| same(a, b));
+    keys
+}
+
+fn key<'a>(row: &[&'a SpreadsheetCell], cols: &[usize]) -> Key<'a> {
+    static BLANK: SpreadsheetCell = SpreadsheetCell::Empty;
+    cols.iter()
+        .map(|col| row.get(*col).copied().unwrap_or(&BLANK))
+        .collect()
+}
+
+fn label(key: &[&SpreadsheetCell]) -> String {
+    let parts: Vec<String> = key.iter().map(|cell| cell.to_string()).collect();
+    parts.join(" ")
+}
+
+impl Sheet {
+    // Build the pivot table as a new sheet. Its first row always names the
+    // columns, from the names in this sheet's first row when `header` is
+    // set and by letter otherwise.
+    pub fn pivot(&self, pivot: &Pivot, header: bool) -> Result<Sheet, Error> {
+        let names = headers(self, header);
+        let name = |col: usize| names.get(col).cloned().unwrap_or_else(|| column_name(col));
+        let rows = data_rows(self, header);
+
+        let down = distinct(rows.iter().map(|row| key(row, &pivot.by)).collect());
+        let across = match pivot.across.is_empty() {
+            true => vec![Vec::new()],
+            false => distinct(rows.iter().map(|row| key(row, &pivot.across)).collect()),
+        };
+
+        // which BY group and ACROSS key each row falls in, worked out once
+        // so each cell of the table only looks at its own rows
+        let place = |keys: &[Key], row: &[&SpreadsheetCell], cols: &[usize]| {
+            let row = key(row, cols);
+            keys.binary_search_by(|key| compare_keys(key, &row))
+                .unwrap_or_default()
+        };
+        let mut buckets = vec![vec![Vec::new(); across.len()]; down.len()];
+        for (r, row) in rows.iter().enumerate() {
+            buckets[place(&down, row, &pivot.by)][place(&across, row, &pivot.across)].push(r);
+        }
+
+        // the table's columns: an ACROSS key (None for the totals) and an
+        // aggregate of a column
+        let mut columns: Vec<(Option<usize>, Aggregate, usize)> = Vec::new();
+        for a in 0..across.len() {
+            for (aggregate, col) in &pivot.values {
+                columns.push((Some(a), *aggregate, *col));
+            }
+        }
+        if pivot.totals && !pivot.across.is_empty() {
+            for (aggregate, col) in &pivot.values {
+                columns.push((None, *aggregate, *col));
+            }
+        }
+
+        // the table's rows: what's written in the key columns and the BY
+        // groups that are counted, a subtotal's groups are the ones next to
+        // each other that share the outer columns
+        let total = || SpreadsheetCell::Text(String::from("Total"));
+        let labels = |key: &[&SpreadsheetCell]| -> Vec<SpreadsheetCell> {
+            key.iter().map(|cell| plain(cell)).collect()
+        };
+        let mut lines: Vec<(Vec<SpreadsheetCell>, Range<usize>)> = Vec::new();
+        for (i, group) in down.iter().enumerate() {
+            lines.push((labels(group), i..i + 1));
+            if pivot.subtotals {
+                // close every outer group that ends here, innermost first
+                for level in (1..group.len()).rev() {
+                    let outer = &group[..level];
+                    let ends = down
+                        .get(i + 1)
+                        .is_none_or(|next| !same(&next[..level], outer));
+                    if ends {
+                        let first = down[..i]
+                            .iter()
+                            .rposition(|before| !same(&before[..level], outer))
+                            .map_or(0, |before| before + 1);
+                        let mut labels = labels(outer);
+                        labels.push(total());
+                        lines.push((labels, first..i + 1));
+                    }
+                }
+            }
+        }
+        if pivot.totals && !pivot.by.is_empty() {
+            lines.push((vec![total()], 0..down.len()));
+        }
+
+        let mut cells = Vec::new();
+        for (i, col) in pivot.by.iter().enumerate() {
+            cells.push((CellRef::new(0, i), SpreadsheetCell::Text(name(*col))));
+        }
+        for (i, (a, aggregate, col)) in columns.iter().enumerate() {
+            let value = format!("{aggregate}({})", name(*col));
+            let title = match a.map(|a| &across[a]) {
+                Some(key) if key.is_empty() => value,
+                Some(key) if pivot.values.len() == 1 => label(key),
+                Some(key) => format!("{} {value}", label(key)),
+                None if pivot.values.len() == 1 => String::from("Total"),
+                None => format!("Total {value}"),
+            };
+            cells.push((
+                CellRef::new(0, pivot.by.len() + i),
+                SpreadsheetCell::Text(title),
+            ));
+        }
+        for (r, (labels, groups)) in lines.iter().enumerate() {
+            let row = r + 1;
+            for (i, label) in labels.iter().enumerate() {
+                cells.push((CellRef::new(row, i), label.clone()));
+            }
+            for (i, (a, aggregate, col)) in columns.iter().enumerate() {
+                let mut which: Vec<usize> = buckets[groups.clone()]
+                    .iter()
+                    .flat_map(|across| match a {
+                        Some(a) => &across[*a..=*a],
+                        None => &across[..],
+                    })
+                    .flatten()
+                    .copied()
+                    .collect();
+                which.sort_unstable(); // <-- in sheet order, MIN and MAX pick the first of a tie
+                let values: Vec<&SpreadsheetCell> = which
+                    .iter()
+                    .filter_map(|r| rows[*r].get(*col).copied())
+                    .collect();
+                let at = CellRef::new(row, pivot.by.len() + i);
+                cells.push((at, aggregate.apply(&values)));
+            }
+        }
+
+        let mut table = Sheet::new();
+        table.set_many(cells.into_iter().filter(|(_, cell)| !cell.is_empty()));
+        Ok(table)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sales() -> Sheet {
+        let mut sheet = Sheet::new();
+        let rows = [
+            "region,product,quarter,qty",
+            "north,apples,Q1,10",
+            "north,pears,Q2,5",
+            "South,apples,Q1,7",
+            "north,apples,Q2,3",
+            "south,pears,Q1,2",
+            "North,pears,Q1,1",
+            ",apples,Q2,4",
+        ];
+        for (row, line) in rows.iter().enumerate() {
+            for (col, input) in line.split(',').enumerate() {
+                let cell: SpreadsheetCell = input.parse().unwrap();
+                sheet.set(CellRef::new(row, col), cell);
+            }
+        }
+        sheet
+    }
+
+    // The table as it's shown, a row at a time
+    fn shown(table: &Sheet) -> Vec<Vec<String>> {
+        let used = table.used_range().unwrap();
+        (0..=used.end.row)
+            .map(|row| {
+                (0..=used.end.col)
+                    .map(|col| table.display(CellRef::new(row, col)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn across_with_subtotals_and_totals() {
+        let table = sales()
+            .query(
+                "PIVOT SUM(qty) BY region, product ACROSS quarter SUBTOTALS TOTALS",
+                true,
+            )
+            .unwrap();
+        let expected = [
+            ["region", "product", "Q1", "Q2", "Total"],
+            ["north", "apples", "10", "3", "13"],
+            ["north", "pears", "1", "5", "6"],
+            ["north", "Total", "11", "8", "19"],
+            ["South", "apples", "7", "", "7"],
+            ["south", "pears", "2", "", "2"],
+            ["south", "Total", "9", "", "9"],
+            ["", "apples", "", "4", "4"],
+            ["", "Total", "", "4", "4"],
+            ["Total", "", "20", "12", "32"],
+        ];
+        assert_eq!(shown(&table), expected);
+    }
+
+    #[test]
+    fn total_column_is_worked_out_from_every_row() {
+        let table = sales()
+            .query("PIVOT SUM(qty), AVG(qty) ACROSS quarter TOTALS", true)
+            .unwrap();
+        let expected = [
+            "Q1 SUM(qty)",
+            "Q1 AVG(qty)",
+            "Q2 SUM(qty)",
+            "Q2 AVG(qty)",
+            "Total SUM(qty)",
+            "Total AVG(qty)",
+        ];
+        assert_eq!(shown(&table)[0], expected);
+        let values: Vec<Value> = (0..6)
+            .map(|col| table.value(CellRef::new(1, col)))
+            .collect();
+        assert_eq!(
+            values,
+            [
+                Value::Int(20),
+                Value::Int(5),
+                Value::Int(12),
+                Value::Int(4),
+                Value::Int(32),
+                Value::Float(32.0 / 7.0),
+            ]
+        );
+        assert_eq!(table.used_range().unwrap().end.row, 1);
+    }
+
+    #[test]
+    fn subtotals_close_every_outer_group() {
+        let mut sheet = Sheet::new();
+        for (row, line) in ["a,x,p,1", "a,x,q,2", "a,y,p,3", "b,y,p,4"]
+            .iter()
+            .enumerate()
+        {
+            for (col, input) in line.split(',').enumerate() {
+                sheet.set(
+                    CellRef::new(row, col),
+                    input.parse::<SpreadsheetCell>().unwrap(),
+                );
+            }
+        }
+        let table = sheet
+            .query("PIVOT COUNT(D), MAX(D) BY A, B, C SUBTOTALS", false)
+            .unwrap();
+        let expected = [
+            ["A", "B", "C", "COUNT(D)", "MAX(D)"],
+            ["a", "x", "p", "1", "1"],
+            ["a", "x", "q", "1", "2"],
+            ["a", "x", "Total", "2", "2"],
+            ["a", "y", "p", "1", "3"],
+            ["a", "y", "Total", "1", "3"],
+            ["a", "Total", "", "3", "3"],
+            ["b", "y", "p", "1", "4"],
+            ["b", "y", "Total", "1", "4"],
+            ["b", "Total", "", "1", "4"],
+        ];
+        assert_eq!(shown(&table), expected);
+    }
+}
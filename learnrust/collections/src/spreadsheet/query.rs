@@ -0,0 +1,569 @@
+// A little SQL for picking rows out of a sheet:
+//
+//   SELECT A, C WHERE B > 10 AND C <> 'n/a' ORDER BY C DESC LIMIT 5
+//
+// Columns are named by letter, or by the names in the first row when the
+// sheet has one. The answer is a new sheet holding the cells of the rows
+// that matched, dates still dates and money still money, but formulas
+// replaced by their values, so it can be saved or queried again.
+//
+// Values compare the way they do in formulas: numbers by value whether
+// they're Int or Float, text case-insensitively, and across types numbers
+// come before text, then booleans. Rows where a comparison hits an error
+// don't match. ORDER BY puts blanks last whichever way it sorts.
+//
+// PIVOT is the other kind of query, it groups rows instead of picking
+// them, see pivot.rs.
+use super::{
+    column_index, column_name,
+    pivot::{Aggregate, Pivot},
+    CellRef, Error, Sheet, SpreadsheetCell, Value,
+};
+use std::cmp::Ordering;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Value(Value), // <-- a number or quoted text
+    Op(&'static str),
+    End,
+}
+
+fn tokenize(text: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let error = |column: usize, message: String| Error::Query { column, message };
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let (c, column) = (chars[i], i + 1);
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Word(chars[start..i].iter().collect()), column));
+        } else if c.is_ascii_digit()
+            || c == '.'
+            || (c == '-' && chars.get(i + 1).is_some_and(|d| d.is_ascii_digit()))
+        {
+            let start = i;
+            i += 1;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let number: String = chars[start..i].iter().collect();
+            let value = match number.parse() {
+                Ok(n) => Value::Int(n),
+                Err(_) => Value::Float(
+                    number
+                        .parse()
+                        .map_err(|_| error(column, format!("'{number}' is not a number")))?,
+                ),
+            };
+            tokens.push((Token::Value(value), column));
+        } else if c == '\'' || c == '"' {
+            // the quote is doubled to put one inside: 'it''s'
+            let mut text = String::new();
+            i += 1;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (Some(q), Some(r)) if *q == c && *r == c => {
+                        text.push(c);
+                        i += 2;
+                    }
+                    (Some(q), _) if *q == c => break,
+                    (Some(other), _) => {
+                        text.push(*other);
+                        i += 1;
+                    }
+                    (None, _) => return Err(error(column, String::from("unclosed quote"))),
+                }
+            }
+            i += 1;
+            tokens.push((Token::Value(Value::Text(text)), column));
+        } else {
+            let two: String = chars[i..chars.len().min(i + 2)].iter().collect();
+            let op = ["<=", ">=", "<>", "!="]
+                .into_iter()
+                .find(|op| *op == two)
+                .or_else(|| {
+                    ["=", "<", ">", ",", "(", ")", "*"]
+                        .into_iter()
+                        .find(|op| op.starts_with(c))
+                })
+                .ok_or_else(|| error(column, format!("unexpected character '{c}'")))?;
+            tokens.push((Token::Op(op), column));
+            i += op.len();
+        }
+    }
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Operand {
+    Column(usize),
+    Value(Value),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Compare(&'static str, Operand, Operand),
+    IsEmpty(Operand, bool), // <-- false for IS NOT EMPTY
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Query {
+    columns: Vec<usize>,
+    filter: Option<Condition>,
+    order: Vec<(usize, bool)>, // <-- column and whether it's descending
+    limit: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Statement {
+    Select(Query),
+    Pivot(Pivot),
+}
+
+struct Parser<'a> {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+    headers: &'a [String],
+    width: usize, // <-- how many columns the sheet uses, for SELECT *
+}
+
+impl Parser<'_> {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.tokens[self.pos].0.clone();
+        if token != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error(&self, message: impl Into<String>) -> Error {
+        Error::Query {
+            column: self.tokens[self.pos].1,
+            message: message.into(),
+        }
+    }
+
+    // Whether the next token is the keyword, taking it if it is
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Token::Word(word) if word.eq_ignore_ascii_case(keyword) => {
+                self.next();
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn expect_keyword(&mut self, keyword: &str) -> Result<(), Error> {
+        match self.keyword(keyword) {
+            true => Ok(()),
+            false => Err(self.error(format!("expected {keyword}"))),
+        }
+    }
+
+    fn op(&mut self, op: &'static str) -> bool {
+        if *self.peek() == Token::Op(op) {
+            self.next();
+            return true;
+        }
+        false
+    }
+
+    // A column by its name in the first row, or by its letter
+    fn column(&mut self) -> Result<usize, Error> {
+        let Token::Word(word) = self.peek().clone() else {
+            return Err(self.error("expected a column"));
+        };
+        let col = self
+            .headers
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(&word))
+            .or_else(|| column_index(&word))
+            .ok_or_else(|| self.error(format!("there's no column called {word}")))?;
+        self.next();
+        Ok(col)
+    }
+
+    // statement := query | pivot
+    fn statement(&mut self) -> Result<Statement, Error> {
+        let statement = match self.keyword("PIVOT") {
+            true => Statement::Pivot(self.pivot()?),
+            false if self.keyword("SELECT") => Statement::Select(self.query()?),
+            false => return Err(self.error("expected SELECT or PIVOT")),
+        };
+        match self.peek() {
+            Token::End => Ok(statement),
+            _ => Err(self.error("unexpected input after the query")),
+        }
+    }
+
+    // A list of columns after BY or ACROSS
+    fn columns(&mut self) -> Result<Vec<usize>, Error> {
+        let mut columns = vec![self.column()?];
+        while self.op(",") {
+            columns.push(self.column()?);
+        }
+        Ok(columns)
+    }
+
+    // pivot := PIVOT aggregate (',' aggregate)* [BY columns]
+    //          [ACROSS columns] [SUBTOTALS] [TOTALS]
+    // aggregate := (SUM | COUNT | AVG | MIN | MAX) '(' column ')'
+    fn pivot(&mut self) -> Result<Pivot, Error> {
+        let mut pivot = Pivot::default();
+        loop {
+            let aggregate = match self.peek() {
+                Token::Word(word) => Aggregate::from_name(word),
+                _ => None,
+            }
+            .ok_or_else(|| self.error("expected SUM, COUNT, AVG, MIN or MAX"))?;
+            self.next();
+            if !self.op("(") {
+                return Err(self.error("expected '('"));
+            }
+            let col = self.column()?;
+            if !self.op(")") {
+                return Err(self.error("expected ')'"));
+            }
+            pivot.values.push((aggregate, col));
+            if !self.op(",") {
+                break;
+            }
+        }
+        if self.keyword("BY") {
+            pivot.by = self.columns()?;
+        }
+        if self.keyword("ACROSS") {
+            pivot.across = self.columns()?;
+        }
+        pivot.subtotals = self.keyword("SUBTOTALS");
+        pivot.totals = self.keyword("TOTALS");
+        Ok(pivot)
+    }
+
+    // query := SELECT ('*' | column (',' column)*) [WHERE or]
+    //          [ORDER BY column [ASC | DESC] (',' ...)*] [LIMIT number]
+    fn query(&mut self) -> Result<Query, Error> {
+        let columns = match self.op("*") {
+            true => (0..self.width).collect(),
+            false => self.columns()?,
+        };
+        let filter = match self.keyword("WHERE") {
+            true => Some(self.or()?),
+            false => None,
+        };
+        let mut order = Vec::new();
+        if self.keyword("ORDER") {
+            self.expect_keyword("BY")?;
+            loop {
+                let col = self.column()?;
+                let descending = self.keyword("DESC");
+                if !descending {
+                    self.keyword("ASC"); // <-- the default, it can be left out
+                }
+                order.push((col, descending));
+                if !self.op(",") {
+                    break;
+                }
+            }
+        }
+        let limit = match self.keyword("LIMIT") {
+            true => match self.next() {
+                Token::Value(Value::Int(n)) if n >= 0 => Some(n as usize),
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("LIMIT needs a whole number"));
+                }
+            },
+            false => None,
+        };
+        Ok(Query {
+            columns,
+            filter,
+            order,
+            limit,
+        })
+    }
+
+    // or := and (OR and)*
+    fn or(&mut self) -> Result<Condition, Error> {
+        let mut left = self.and()?;
+        while self.keyword("OR") {
+            left = Condition::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    // and := not (AND not)*
+    fn and(&mut self) -> Result<Condition, Error> {
+        let mut left = self.not()?;
+        while self.keyword("AND") {
+            left = Condition::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    // not := NOT not | '(' or ')' | operand (op operand | IS [NOT] EMPTY)
+    fn not(&mut self) -> Result<Condition, Error> {
+        if self.keyword("NOT") {
+            return Ok(Condition::Not(Box::new(self.not()?)));
+        }
+        if self.op("(") {
+            let inner = self.or()?;
+            if !self.op(")") {
+                return Err(self.error("expected ')'"));
+            }
+            return Ok(inner);
+        }
+        let left = self.operand()?;
+        if self.keyword("IS") {
+            let negated = self.keyword("NOT");
+            self.expect_keyword("EMPTY")?;
+            return Ok(Condition::IsEmpty(left, !negated));
+        }
+        let op = match self.peek() {
+            Token::Op(op @ ("=" | "<>" | "!=" | "<" | "<=" | ">" | ">=")) => *op,
+            _ => return Err(self.error("expected a comparison like = or >")),
+        };
+        self.next();
+        Ok(Condition::Compare(op, left, self.operand()?))
+    }
+
+    fn operand(&mut self) -> Result<Operand, Error> {
+        match self.peek().clone() {
+            Token::Value(value) => {
+                self.next();
+                Ok(Operand::Value(value))
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("TRUE") => {
+                self.next();
+                Ok(Operand::Value(Value::Bool(true)))
+            }
+            Token::Word(word) if word.eq_ignore_ascii_case("FALSE") => {
+                self.next();
+                Ok(Operand::Value(Value::Bool(false)))
+            }
+            _ => self.column().map(Operand::Column),
+        }
+    }
+}
+
+impl Condition {
+    fn matches(&self, row: &[&SpreadsheetCell]) -> bool {
+        let value = |operand: &Operand| match operand {
+            Operand::Column(col) => row.get(*col).map_or(Value::Empty, |cell| cell.value()),
+            Operand::Value(value) => value.clone(),
+        };
+        match self {
+            Condition::Compare(op, left, right) => match value(left).compare(&value(right)) {
+                Ok(ordering) => match *op {
+                    "=" => ordering == Ordering::Equal,
+                    "<>" | "!=" => ordering != Ordering::Equal,
+                    "<" => ordering == Ordering::Less,
+                    "<=" => ordering != Ordering::Greater,
+                    ">" => ordering == Ordering::Greater,
+                    _ => ordering != Ordering::Less,
+                },
+                Err(_) => false,
+            },
+            Condition::IsEmpty(operand, empty) => (value(operand) == Value::Empty) == *empty,
+            Condition::Not(inner) => !inner.matches(row),
+            Condition::And(left, right) => left.matches(row) && right.matches(row),
+            Condition::Or(left, right) => left.matches(row) || right.matches(row),
+        }
+    }
+}
+
+// The names in the first row of the sheet, when it has them
+pub fn headers(sheet: &Sheet, header: bool) -> Vec<String> {
+    match (header, sheet.used_range()) {
+        (true, Some(used)) => (0..=used.end.col)
+            .map(|col| sheet.value(CellRef::new(0, col)).to_string())
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+static BLANK: SpreadsheetCell = SpreadsheetCell::Empty;
+
+// The cells of every row with something in it, below the names if any
+pub fn data_rows(sheet: &Sheet, header: bool) -> Vec<Vec<&SpreadsheetCell>> {
+    let Some(used) = sheet.used_range() else {
+        return Vec::new();
+    };
+    // gathered from the filled cells, so the empty rows are never visited
+    let mut rows: Vec<Vec<&SpreadsheetCell>> = Vec::new();
+    let mut last = None;
+    for (at, cell) in sheet.cells().filter(|(at, _)| at.row >= header as usize) {
+        if last != Some(at.row) {
+            rows.push(vec![&BLANK; used.end.col + 1]);
+            last = Some(at.row);
+        }
+        rows.last_mut().unwrap()[at.col] = cell;
+    }
+    rows
+}
+
+// A cell as it goes into an answer: a formula is replaced by its value,
+// anything else is copied as it is
+pub fn plain(cell: &SpreadsheetCell) -> SpreadsheetCell {
+    match cell {
+        SpreadsheetCell::Formula(formula) => SpreadsheetCell::from(formula.value().clone()),
+        cell => cell.clone(),
+    }
+}
+
+impl Sheet {
+    // Run a query over the rows of the sheet. With `header` the first row
+    // names the columns and the answer starts with the selected names.
+    pub fn query(&self, text: &str, header: bool) -> Result<Sheet, Error> {
+        let headers = headers(self, header);
+        let mut parser = Parser {
+            tokens: tokenize(text)?,
+            pos: 0,
+            headers: &headers,
+            width: self.used_range().map_or(0, |used| used.end.col + 1),
+        };
+        let query = match parser.statement()? {
+            Statement::Select(query) => query,
+            Statement::Pivot(pivot) => return self.pivot(&pivot, header),
+        };
+
+        let mut rows: Vec<Vec<&SpreadsheetCell>> = data_rows(self, header)
+            .into_iter()
+            .filter(|row| {
+                query
+                    .filter
+                    .as_ref()
+                    .is_none_or(|filter| filter.matches(row))
+            })
+            .collect();
+        rows.sort_by(|a, b| {
+            query
+                .order
+                .iter()
+                .map(|(col, descending)| {
+                    let value = |row: &[&SpreadsheetCell]| row.get(*col).map(|cell| cell.value());
+                    let (a, b) = (value(a).unwrap_or_default(), value(b).unwrap_or_default());
+                    match (a == Value::Empty, b == Value::Empty) {
+                        (false, false) if *descending => b.sort_cmp(&a),
+                        (false, false) => a.sort_cmp(&b),
+                        (a, b) => a.cmp(&b), // <-- blanks last
+                    }
+                })
+                .find(|ordering| *ordering != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        rows.truncate(query.limit.unwrap_or(rows.len()));
+
+        let mut answer = Sheet::new();
+        if header {
+            for (i, col) in query.columns.iter().enumerate() {
+                let name = headers
+                    .get(*col)
+                    .cloned()
+                    .unwrap_or_else(|| column_name(*col));
+                answer.set(CellRef::new(0, i), name);
+            }
+        }
+        let first = header as usize;
+        let cells = rows.iter().enumerate().flat_map(|(r, row)| {
+            query.columns.iter().enumerate().map(move |(i, col)| {
+                let cell = row
+                    .get(*col)
+                    .map_or(SpreadsheetCell::Empty, |cell| plain(cell));
+                (CellRef::new(first + r, i), cell)
+            })
+        });
+        answer.set_many(cells.filter(|(_, cell)| !cell.is_empty()));
+        Ok(answer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(address: &str) -> CellRef {
+        address.parse().unwrap()
+    }
+
+    // Orders with a date, an amount and a formula working out the tax
+    fn orders() -> Sheet {
+        let mut sheet = Sheet::new();
+        let rows = [
+            ["day", "region", "amount", "tax"],
+            ["2024-03-15", "North", "$10.10", "=C2/10"],
+            ["2024-03-01", "south", "$0.20", "=C3/10"],
+            ["2024-02-29", "North", "$5.05", "=C4/10"],
+        ];
+        for (r, row) in rows.iter().enumerate() {
+            for (c, text) in row.iter().enumerate() {
+                sheet.set(CellRef::new(r, c), text.parse::<SpreadsheetCell>().unwrap());
+            }
+        }
+        sheet
+    }
+
+    fn shown(sheet: &Sheet) -> Vec<String> {
+        let rows = (0..sheet.rows()).map(|row| {
+            let cells = (0..sheet.cols()).map(|col| sheet.get(CellRef::new(row, col)).to_string());
+            cells.collect::<Vec<_>>().join(" | ")
+        });
+        rows.collect()
+    }
+
+    #[test]
+    fn answers_keep_dates_and_money() {
+        let answer = orders()
+            .query(
+                "SELECT day, amount, tax WHERE amount > 1 ORDER BY day",
+                true,
+            )
+            .unwrap();
+        assert_eq!(
+            shown(&answer),
+            [
+                "day | amount | tax",
+                "2024-02-29 | $5.05 | 0.505",
+                "2024-03-15 | $10.10 | $1.01",
+            ]
+        );
+        assert_eq!(answer.get(at("A2")).type_name(), "date");
+        assert_eq!(answer.get(at("C3")).type_name(), "currency");
+        // the formulas are gone, only what they worked out is left
+        assert_eq!(answer.get(at("C2")).type_name(), "float");
+    }
+
+    #[test]
+    fn pivots_keep_dates_and_money() {
+        let table = orders()
+            .query("PIVOT SUM(amount), MIN(day) BY region TOTALS", true)
+            .unwrap();
+        assert_eq!(
+            shown(&table),
+            [
+                "region | SUM(amount) | MIN(day)",
+                "North | $15.15 | 2024-02-29",
+                "south | $0.20 | 2024-03-01",
+                "Total | $15.35 | 2024-02-29",
+            ]
+        );
+        assert_eq!(table.get(at("C2")).type_name(), "date");
+    }
+}
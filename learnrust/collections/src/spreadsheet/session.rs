@@ -0,0 +1,509 @@
+// The commands behind the interactive sheet in src/bin/sheet.rs, one per
+// line:
+//
+//   set B2 =A2*1.2
+//   format B2:B10 #,##0.00
+//   show A1:D10
+//   sort by C desc
+//   query SELECT A, C WHERE B > 10 ORDER BY C DESC LIMIT 5
+//   pivot SUM(qty) BY region ACROSS quarter TOTALS
+//   chart line A1:C13
+//   save out.csv
+//   undo
+use super::{
+    column_index, column_name, CellRef, ChartKind, CsvOptions, Edit, Error, Format, History, Range,
+    Sheet, SpreadsheetCell,
+};
+use std::str::FromStr;
+
+pub const HELP: &str = "\
+Commands:
+  set <cell> <value>        a number, text, or a formula like =A1*2 ('42 is the text 42),
+                            also TRUE, 12%, $1,234.50 or a date like 2024-03-15
+  clear <cell>
+  format <range> <format>   General, 0.00, #,##0, 0.0% or a date like DD/MM/YYYY
+  show [range]              which part of the sheet to show, like A1:D10 (all of it without one)
+  sort [range] by <column> [asc|desc]
+  query SELECT <columns> [WHERE ...] [ORDER BY ...] [LIMIT n]
+                            replace the sheet with the rows that match, undo brings it back
+  pivot <SUM|COUNT|AVG|MIN|MAX>(<column>), ... [BY <columns>] [ACROSS <columns>] [SUBTOTALS] [TOTALS]
+                            replace the sheet with a pivot table of it, undo brings it back
+  chart <bar|line|scatter> <range> [file.svg]
+                            draw the range here, or save it as an SVG image
+  insert row <n> | insert col <letter>
+  delete row <n> | delete col <letter>
+  load <file.csv>
+  save [file.csv]
+  undo | redo
+  begin [name]              group the changes until commit so they're undone together
+  commit | rollback
+  history
+  help
+  quit";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Set {
+        at: CellRef,
+        cell: SpreadsheetCell,
+    },
+    Clear {
+        at: CellRef,
+    },
+    Format {
+        range: Range,
+        format: Format,
+    },
+    Show {
+        range: Option<Range>,
+    },
+    Sort {
+        range: Option<Range>,
+        by: usize,
+        descending: bool,
+    },
+    Query {
+        text: String,
+    },
+    Chart {
+        kind: ChartKind,
+        range: Range,
+        path: Option<String>,
+    },
+    InsertRow(usize),
+    InsertCol(usize),
+    DeleteRow(usize),
+    DeleteCol(usize),
+    Load {
+        path: String,
+    },
+    Save {
+        path: Option<String>,
+    },
+    Undo,
+    Redo,
+    Begin {
+        name: Option<String>,
+    },
+    Commit,
+    Rollback,
+    History,
+    Help,
+}
+
+impl Command {
+    // Whether the sheet should be shown again after running the command
+    pub fn changes_sheet(&self) -> bool {
+        !matches!(
+            self,
+            Command::Show { .. }
+                | Command::Chart { .. }
+                | Command::Save { .. }
+                | Command::Begin { .. }
+                | Command::Commit
+                | Command::History
+                | Command::Help
+        )
+    }
+}
+
+// "row 3" or "col C" after insert and delete, as a 0-based index
+fn row_or_col(rest: &str) -> Option<(bool, usize)> {
+    let (kind, which) = rest.split_once(' ')?;
+    let which = which.trim();
+    match kind.to_lowercase().as_str() {
+        "row" => which
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .map(|n| (true, n - 1)),
+        "col" | "column" => column_index(which).map(|col| (false, col)),
+        _ => None,
+    }
+}
+
+impl FromStr for Command {
+    type Err = Error;
+
+    fn from_str(line: &str) -> Result<Command, Error> {
+        let line = line.trim();
+        let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let rest = rest.trim();
+        match verb.to_lowercase().as_str() {
+            "set" => {
+                let (at, input) = rest
+                    .split_once(' ')
+                    .ok_or(Error::Usage("set <cell> <value>"))?;
+                Ok(Command::Set {
+                    at: at.parse()?,
+                    cell: input.parse()?,
+                })
+            }
+            "clear" if !rest.is_empty() => Ok(Command::Clear { at: rest.parse()? }),
+            "clear" => Err(Error::Usage("clear <cell>")),
+            "format" => {
+                let (range, format) = rest
+                    .split_once(' ')
+                    .ok_or(Error::Usage("format <range> <format>"))?;
+                Ok(Command::Format {
+                    range: range.parse()?,
+                    format: format.parse()?,
+                })
+            }
+            "show" if rest.is_empty() => Ok(Command::Show { range: None }),
+            "show" => Ok(Command::Show {
+                range: Some(rest.parse()?),
+            }),
+            "sort" => {
+                let usage = Error::Usage("sort [range] by <column> [asc|desc]");
+                let words: Vec<&str> = rest.split_whitespace().collect();
+                let (range, words) = match words.first() {
+                    Some(word) if !word.eq_ignore_ascii_case("by") => {
+                        (Some(word.parse()?), &words[1..])
+                    }
+                    _ => (None, &words[..]),
+                };
+                let descending = match words {
+                    [by, _] | [by, _, _] if !by.eq_ignore_ascii_case("by") => return Err(usage),
+                    [_, _] => false,
+                    [_, _, order] if order.eq_ignore_ascii_case("asc") => false,
+                    [_, _, order] if order.eq_ignore_ascii_case("desc") => true,
+                    _ => return Err(usage),
+                };
+                let by = column_index(words[1]).ok_or(usage)?;
+                Ok(Command::Sort {
+                    range,
+                    by,
+                    descending,
+                })
+            }
+            "query" if !rest.is_empty() => Ok(Command::Query {
+                text: rest.to_owned(),
+            }),
+            "query" => Err(Error::Usage(
+                "query SELECT <columns> [WHERE ...] [ORDER BY ...] [LIMIT n]",
+            )),
+            // a pivot is a query too, this saves typing it as one
+            "pivot" if !rest.is_empty() => Ok(Command::Query {
+                text: format!("PIVOT {rest}"),
+            }),
+            "pivot" => Err(Error::Usage(
+                "pivot SUM(<column>), ... [BY <columns>] [ACROSS <columns>] [SUBTOTALS] [TOTALS]",
+            )),
+            "chart" => {
+                let usage = Error::Usage("chart <bar|line|scatter> <range> [file.svg]");
+                match rest.split_whitespace().collect::<Vec<&str>>()[..] {
+                    [kind, range] => Ok(Command::Chart {
+                        kind: kind.parse()?,
+                        range: range.parse()?,
+                        path: None,
+                    }),
+                    [kind, range, path] => Ok(Command::Chart {
+                        kind: kind.parse()?,
+                        range: range.parse()?,
+                        path: Some(path.to_owned()),
+                    }),
+                    _ => Err(usage),
+                }
+            }
+            "insert" => match row_or_col(rest) {
+                Some((true, row)) => Ok(Command::InsertRow(row)),
+                Some((false, col)) => Ok(Command::InsertCol(col)),
+                None => Err(Error::Usage("insert row <number> | insert col <letter>")),
+            },
+            "delete" => match row_or_col(rest) {
+                Some((true, row)) => Ok(Command::DeleteRow(row)),
+                Some((false, col)) => Ok(Command::DeleteCol(col)),
+                None => Err(Error::Usage("delete row <number> | delete col <letter>")),
+            },
+            "load" if !rest.is_empty() => Ok(Command::Load {
+                path: rest.to_owned(),
+            }),
+            "load" => Err(Error::Usage("load <file.csv>")),
+            "save" => Ok(Command::Save {
+                path: Some(rest).filter(|p| !p.is_empty()).map(String::from),
+            }),
+            "undo" => Ok(Command::Undo),
+            "redo" => Ok(Command::Redo),
+            "begin" => Ok(Command::Begin {
+                name: Some(rest).filter(|n| !n.is_empty()).map(String::from),
+            }),
+            "commit" => Ok(Command::Commit),
+            "rollback" => Ok(Command::Rollback),
+            "history" => Ok(Command::History),
+            "help" => Ok(Command::Help),
+            _ => Err(Error::UnknownCommand(verb.to_owned())),
+        }
+    }
+}
+
+// How big charts are drawn: characters at the prompt, pixels in a file
+const CHART_TEXT: (usize, usize) = (60, 15);
+const CHART_SVG: (usize, usize) = (640, 400);
+
+// A sheet being worked on: the part of it on screen, the file it came
+// from and every change made to it
+#[derive(Debug, Default)]
+pub struct Session {
+    sheet: Sheet,
+    view: Option<Range>,
+    path: Option<String>,
+    history: History,
+}
+
+// Files are read and written with formulas kept as formulas
+fn csv_options() -> CsvOptions {
+    CsvOptions {
+        formulas: true,
+        ..CsvOptions::default()
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        Session::default()
+    }
+
+    pub fn load(path: &str) -> Result<Session, Error> {
+        Ok(Session {
+            sheet: Sheet::load_csv(path, &csv_options())?,
+            path: Some(path.to_owned()),
+            ..Session::default()
+        })
+    }
+
+    pub fn sheet(&self) -> &Sheet {
+        &self.sheet
+    }
+
+    // The part of the sheet on screen, everything from A1 unless `show`
+    // picked a range
+    pub fn render(&self) -> String {
+        match (self.view, self.sheet.used_range()) {
+            (Some(view), _) => self.sheet.render(view),
+            (None, Some(used)) => self.sheet.render(Range::new(CellRef::new(0, 0), used.end)),
+            (None, None) => String::from("(empty sheet)"),
+        }
+    }
+
+    // Numbered oldest first, with what redo would bring back after it
+    fn history_listing(&self) -> String {
+        let (done, undone) = self.history.entries();
+        let mut lines: Vec<String> = done
+            .iter()
+            .enumerate()
+            .map(|(i, transaction)| format!("{:>3}. {transaction}", i + 1))
+            .collect();
+        lines.extend(
+            undone
+                .iter()
+                .map(|transaction| format!("     {transaction} (undone)")),
+        );
+        if self.history.in_transaction() {
+            lines.push(String::from("     ... a transaction is open"));
+        }
+        match lines.is_empty() {
+            true => String::from("Nothing has changed yet"),
+            false => lines.join("\n"),
+        }
+    }
+
+    // A first row that's all text (blanks aside) is taken to be column
+    // names, sorting leaves it at the top and queries can use the names
+    fn has_header(&self) -> bool {
+        let Some(used) = self.sheet.used_range() else {
+            return false;
+        };
+        let mut first_row = (used.start.col..=used.end.col)
+            .map(|col| self.sheet.get(CellRef::new(0, col)))
+            .filter(|cell| !cell.is_empty())
+            .peekable();
+        first_row.peek().is_some() && first_row.all(|cell| matches!(cell, SpreadsheetCell::Text(_)))
+    }
+
+    // Every row with something in it, below the column names if any
+    fn all_rows(&self) -> Result<Range, Error> {
+        let used = self
+            .sheet
+            .used_range()
+            .ok_or_else(|| Error::OutOfRange(String::from("sorting an empty sheet")))?;
+        Ok(Range::new(
+            CellRef::new(self.has_header() as usize, used.start.col),
+            CellRef::new(used.end.row, used.end.col),
+        ))
+    }
+
+    // Make a change and describe it, along with the edit for the history
+    fn change(&mut self, command: Command) -> Result<(String, Edit), Error> {
+        let sheet = &mut self.sheet;
+        match command {
+            Command::Set { at, cell } => {
+                let edit = Edit::set(sheet, at, cell);
+                Ok((format!("{at} = {}", sheet.display(at)), edit))
+            }
+            Command::Clear { at } => {
+                let edit = Edit::set(sheet, at, SpreadsheetCell::Empty);
+                Ok((format!("Cleared {at}"), edit))
+            }
+            Command::Format { range, format } => {
+                let message = format!("Formatted {range} as {format}");
+                let edit = Edit::format(sheet, range, format);
+                Ok((message, edit))
+            }
+            Command::Sort {
+                range,
+                by,
+                descending,
+            } => {
+                let range = match range {
+                    Some(range) => range,
+                    None => self.all_rows()?,
+                };
+                let edit = Edit::sort(&mut self.sheet, range, by, descending)?;
+                Ok((format!("Sorted {range}"), edit))
+            }
+            Command::Query { text } => {
+                let header = self.has_header();
+                let answer = self.sheet.query(&text, header)?;
+                // a pivot table names its columns whether this sheet does or not
+                let pivot = text
+                    .split_whitespace()
+                    .next()
+                    .is_some_and(|word| word.eq_ignore_ascii_case("pivot"));
+                let names = (header || pivot) as usize;
+                let rows = answer
+                    .used_range()
+                    .map_or(0, |used| used.end.row + 1)
+                    .saturating_sub(names);
+                let edit = Edit::replace(&mut self.sheet, answer);
+                self.view = None;
+                Ok((format!("{rows} row(s), undo to go back"), edit))
+            }
+            Command::InsertRow(row) => {
+                let edit = Edit::insert_row(sheet, row)?;
+                Ok((format!("Inserted row {}", row + 1), edit))
+            }
+            Command::InsertCol(col) => {
+                let edit = Edit::insert_col(sheet, col)?;
+                Ok((format!("Inserted column {}", column_name(col)), edit))
+            }
+            Command::DeleteRow(row) => {
+                let edit = Edit::delete_row(sheet, row)?;
+                Ok((format!("Deleted row {}", row + 1), edit))
+            }
+            Command::DeleteCol(col) => {
+                let edit = Edit::delete_col(sheet, col)?;
+                Ok((format!("Deleted column {}", column_name(col)), edit))
+            }
+            Command::Load { path } => {
+                let loaded = Sheet::load_csv(&path, &csv_options())?;
+                let edit = Edit::replace(sheet, loaded);
+                self.view = None;
+                let message = format!("Loaded {path}");
+                self.path = Some(path);
+                Ok((message, edit))
+            }
+            _ => unreachable!("only commands that change the sheet get here"),
+        }
+    }
+
+    // Run a command and describe what happened
+    pub fn execute(&mut self, command: Command) -> Result<String, Error> {
+        match command {
+            Command::Show { range } => {
+                self.view = range;
+                Ok(self.render())
+            }
+            Command::Chart { kind, range, path } => {
+                let chart = self.sheet.chart(kind, range)?;
+                match path {
+                    Some(path) => {
+                        chart.save_svg(&path, CHART_SVG.0, CHART_SVG.1)?;
+                        Ok(format!("Saved the chart as {path}"))
+                    }
+                    None => Ok(chart.to_ascii(CHART_TEXT.0, CHART_TEXT.1)),
+                }
+            }
+            Command::Save { path } => {
+                let path = path
+                    .or_else(|| self.path.clone())
+                    .ok_or(Error::Usage("save <file.csv>"))?;
+                self.sheet.save_csv(&path, &csv_options())?;
+                let message = format!("Saved {path}");
+                self.path = Some(path);
+                Ok(message)
+            }
+            Command::Undo => {
+                let undone = self.history.undo(&mut self.sheet)?;
+                Ok(format!("Undid {undone}"))
+            }
+            Command::Redo => {
+                let redone = self.history.redo(&mut self.sheet)?;
+                Ok(format!("Redid {redone}"))
+            }
+            Command::Begin { name } => {
+                self.history.begin(name)?;
+                Ok(String::from(
+                    "Started a transaction, commit or rollback to finish it",
+                ))
+            }
+            Command::Commit => {
+                self.history.commit()?;
+                Ok(String::from("Committed"))
+            }
+            Command::Rollback => {
+                self.history.rollback(&mut self.sheet)?;
+                Ok(String::from("Rolled back"))
+            }
+            Command::History => Ok(self.history_listing()),
+            Command::Help => Ok(String::from(HELP)),
+            command => {
+                let (message, edit) = self.change(command)?;
+                self.history.record(edit);
+                Ok(message)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(session: &mut Session, line: &str) -> String {
+        session.execute(line.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn queries_count_their_rows() {
+        let mut session = Session::new();
+        for line in [
+            "set A1 north",
+            "set B1 5",
+            "set A2 south",
+            "set B2 7",
+            "set A3 north",
+            "set B3 1",
+        ] {
+            run(&mut session, line);
+        }
+        assert_eq!(
+            run(&mut session, "query SELECT A WHERE B > 2"),
+            "2 row(s), undo to go back"
+        );
+        run(&mut session, "undo");
+        // no column names on the sheet, but the pivot table still has them
+        assert_eq!(
+            run(&mut session, "pivot SUM(B) BY A"),
+            "2 row(s), undo to go back"
+        );
+        run(&mut session, "undo");
+        run(&mut session, "insert row 1");
+        run(&mut session, "set A1 region");
+        run(&mut session, "set B1 qty");
+        assert_eq!(
+            run(&mut session, "query PIVOT SUM(qty) BY region TOTALS"),
+            "3 row(s), undo to go back"
+        );
+    }
+}
@@ -0,0 +1,793 @@
+// A sheet is a grid of cells that grows as cells are set. The cells are
+// kept densely or sparsely (see storage.rs) depending on how much of the
+// grid is filled, which the sheet keeps an eye on as it changes.
+//
+// Formulas are recomputed as soon as anything they read changes, the
+// dependency graph says which ones and in what order. A big change can be
+// recomputed on several threads instead: the graph splits the formulas
+// into levels, none of which read each other, and each level is shared
+// out between the threads while the sheet is only being read. The values
+// come out the same as one after the other, only sooner.
+//
+// Formats (see format.rs) belong to places rather than to what's in them,
+// so a cell keeps its format when it's cleared and set again. They're kept
+// as layers, a range and its format, and the newest layer holding a cell
+// decides how it's shown, so formatting a whole column is one layer
+// rather than a million cells. They move along with rows and columns that
+// are inserted, deleted or sorted, and stretch like a formula's ranges do
+// when a row or column is inserted inside of them.
+use super::{
+    column_name,
+    csv::CsvLayout,
+    format::Format,
+    formula::{Context, Shift},
+    graph::Graph,
+    storage::Cells,
+    CellError, CellRef, Error, Formula, Range, SpreadsheetCell, Value,
+};
+use std::{collections::HashSet, fmt, thread};
+
+// Longer text is cut short when printed so one cell can't blow up the table
+const MAX_WIDTH: usize = 24;
+
+// and so is a range bigger than this, more wouldn't fit on a screen anyway
+const SHOWN_ROWS: usize = 100;
+const SHOWN_COLS: usize = 26;
+
+// Starting threads costs more than computing a few formulas, so smaller
+// levels than this are done on the spot
+const PARALLEL_MIN: usize = 512;
+
+// Where two ranges cross, if they do
+fn overlap(a: Range, b: Range) -> Option<Range> {
+    let start = CellRef::new(a.start.row.max(b.start.row), a.start.col.max(b.start.col));
+    let end = CellRef::new(a.end.row.min(b.end.row), a.end.col.min(b.end.col));
+    (start.row <= end.row && start.col <= end.col).then(|| Range::new(start, end))
+}
+
+static EMPTY: SpreadsheetCell = SpreadsheetCell::Empty;
+static GENERAL: Format = Format::General;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Recalculation {
+    #[default]
+    Sequential,
+    Parallel(usize), // <-- how many threads, 0 for one per core
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Sheet {
+    cells: Cells,
+    graph: Graph,
+    formats: Vec<(Range, Format)>, // <-- oldest first
+    recalculation: Recalculation,
+    csv: CsvLayout, // <-- how the CSV file it came from was laid out
+}
+
+impl Sheet {
+    pub fn new() -> Sheet {
+        Sheet::default()
+    }
+
+    pub fn with_size(rows: usize, cols: usize) -> Sheet {
+        let mut sheet = Sheet::new();
+        sheet.grow(rows, cols);
+        sheet
+    }
+
+    pub fn rows(&self) -> usize {
+        self.cells.size().0
+    }
+
+    pub fn cols(&self) -> usize {
+        self.cells.size().1
+    }
+
+    // Whether the cells are kept sparsely, only the filled ones
+    pub fn is_sparse(&self) -> bool {
+        self.cells.is_sparse()
+    }
+
+    // Cells outside of the grid are simply empty
+    pub fn get(&self, at: CellRef) -> &SpreadsheetCell {
+        self.cells.get(at).unwrap_or(&EMPTY)
+    }
+
+    // The cells in a range that have something in them, row by row. On a
+    // sparse sheet the blanks aren't even looked at.
+    pub fn cells_in(&self, range: Range) -> impl Iterator<Item = (CellRef, &SpreadsheetCell)> {
+        self.cells.cells_in(range)
+    }
+
+    // Every cell that has something in it, row by row
+    pub fn cells(&self) -> impl Iterator<Item = (CellRef, &SpreadsheetCell)> {
+        self.cells.cells()
+    }
+
+    // What a cell holds, or for a formula what it computed
+    pub fn value(&self, at: CellRef) -> Value {
+        self.get(at).value()
+    }
+
+    pub fn format(&self, at: CellRef) -> &Format {
+        self.formats
+            .iter()
+            .rev()
+            .find(|(range, _)| range.contains(at))
+            .map_or(&GENERAL, |(_, format)| format)
+    }
+
+    // Set how a cell is shown and hand back how it was
+    pub fn set_format(&mut self, at: CellRef, format: Format) -> Format {
+        let old = self.format(at).clone();
+        self.format_range(Range::new(at, at), format);
+        old
+    }
+
+    // Set how every cell in a range is shown, as one new layer. Older
+    // layers the range covers completely can't show through any more and
+    // are dropped, so formatting the same cells over and over doesn't
+    // pile them up.
+    pub fn format_range(&mut self, range: Range, format: Format) {
+        let covers = |layer: &Range| range.contains(layer.start) && range.contains(layer.end);
+        self.formats.retain(|(layer, _)| !covers(layer));
+        let overlaps = self
+            .formats
+            .iter()
+            .any(|(layer, _)| overlap(*layer, range).is_some());
+        if format != Format::General || overlaps {
+            self.formats.push((range, format));
+        }
+    }
+
+    // The format layers, oldest first
+    pub fn formats(&self) -> impl Iterator<Item = (Range, &Format)> {
+        self.formats.iter().map(|(range, format)| (*range, format))
+    }
+
+    // Put back layers that formats() handed out, replacing all of them
+    pub fn set_formats(&mut self, formats: Vec<(Range, Format)>) {
+        self.formats = formats;
+    }
+
+    // A cell the way it's shown, with its format
+    pub fn display(&self, at: CellRef) -> String {
+        self.format(at).apply(self.get(at))
+    }
+
+    // Sorting moves rows within the range's columns, so a layer over part
+    // of the range is cut into the bits outside it, which stay put, and a
+    // piece for each of its rows inside, which go where the row went. The
+    // pieces are joined up again where their rows ended up next to each
+    // other.
+    fn sort_formats(&mut self, range: Range, moved_to: &[usize]) {
+        let mut sorted = Vec::new();
+        for (layer, format) in std::mem::take(&mut self.formats) {
+            let Some(inside) = overlap(layer, range) else {
+                sorted.push((layer, format));
+                continue;
+            };
+            let piece = |rows: (usize, usize), cols: (usize, usize)| {
+                Range::new(CellRef::new(rows.0, cols.0), CellRef::new(rows.1, cols.1))
+            };
+            let (rows, cols) = (
+                (inside.start.row, inside.end.row),
+                (inside.start.col, inside.end.col),
+            );
+            let all_cols = (layer.start.col, layer.end.col);
+            let mut pieces = Vec::new();
+            if layer.start.row < rows.0 {
+                pieces.push(piece((layer.start.row, rows.0 - 1), all_cols));
+            }
+            if layer.end.row > rows.1 {
+                pieces.push(piece((rows.1 + 1, layer.end.row), all_cols));
+            }
+            if layer.start.col < cols.0 {
+                pieces.push(piece(rows, (layer.start.col, cols.0 - 1)));
+            }
+            if layer.end.col > cols.1 {
+                pieces.push(piece(rows, (cols.1 + 1, layer.end.col)));
+            }
+            let mut to: Vec<usize> = (rows.0..=rows.1)
+                .map(|row| moved_to[row - range.start.row])
+                .collect();
+            to.sort_unstable();
+            let mut runs: Vec<(usize, usize)> = Vec::new();
+            for row in to {
+                match runs.last_mut() {
+                    Some(run) if run.1 + 1 == row => run.1 = row,
+                    _ => runs.push((row, row)),
+                }
+            }
+            pieces.extend(runs.into_iter().map(|run| piece(run, cols)));
+            sorted.extend(pieces.into_iter().map(|piece| (piece, format.clone())));
+        }
+        self.formats = sorted;
+    }
+
+    // Read a cell as a specific type: sheet.get_as::<f64>(at)
+    pub fn get_as<'a, T>(&'a self, at: CellRef) -> Result<T, Error>
+    where
+        T: TryFrom<&'a SpreadsheetCell, Error = Error>,
+    {
+        T::try_from(self.get(at))
+    }
+
+    fn grow(&mut self, rows: usize, cols: usize) {
+        self.cells.adapt(rows, cols);
+        self.cells.grow(rows, cols);
+    }
+
+    // Put a cell in place, switching storage first if the grid gets too
+    // big for how full it is
+    fn put(&mut self, at: CellRef, cell: SpreadsheetCell) -> SpreadsheetCell {
+        self.cells.adapt(at.row + 1, at.col + 1);
+        self.cells.put(at, cell)
+    }
+
+    // Set a cell, growing the grid if needed, and hand back what was there.
+    // Formulas reading the cell (directly or not) are recomputed.
+    pub fn set(&mut self, at: CellRef, cell: impl Into<SpreadsheetCell>) -> SpreadsheetCell {
+        let cell = cell.into();
+        match &cell {
+            SpreadsheetCell::Formula(formula) => {
+                let (cells, ranges) = formula.references();
+                self.graph.set(at, cells, ranges);
+            }
+            _ => self.graph.remove(at),
+        }
+        let old = self.put(at, cell);
+        self.recalculate(self.graph.affected(at));
+        old
+    }
+
+    // Put several cells in place and recompute once at the end, rather
+    // than after every one of them
+    pub fn set_many(&mut self, cells: impl IntoIterator<Item = (CellRef, SpreadsheetCell)>) {
+        for (at, cell) in cells {
+            self.put(at, cell);
+        }
+        self.rebuild();
+    }
+
+    pub fn clear(&mut self, at: CellRef) -> SpreadsheetCell {
+        match self.get(at) {
+            SpreadsheetCell::Empty => SpreadsheetCell::Empty,
+            _ => self.set(at, SpreadsheetCell::Empty),
+        }
+    }
+
+    // Compute the formulas in dependency order. The ones caught in a cycle
+    // can't be computed and get #CYCLE instead.
+    fn recalculate(&mut self, formulas: HashSet<CellRef>) {
+        let threads = match self.recalculation {
+            Recalculation::Parallel(0) => thread::available_parallelism().map_or(1, |n| n.get()),
+            Recalculation::Parallel(threads) => threads,
+            Recalculation::Sequential => 1,
+        };
+        if threads > 1 && formulas.len() >= PARALLEL_MIN {
+            return self.recalculate_levels(formulas, threads);
+        }
+        let (order, cyclic) = self.graph.order(&formulas);
+        for at in cyclic {
+            self.set_value(at, Value::Error(CellError::Cycle));
+        }
+        for at in order {
+            if let SpreadsheetCell::Formula(formula) = self.get(at) {
+                let value = formula.evaluate(self);
+                self.set_value(at, value);
+            }
+        }
+    }
+
+    // Work out the formulas at some cells from the sheet as it is now
+    fn evaluate(&self, formulas: &[CellRef]) -> Vec<(CellRef, Value)> {
+        formulas
+            .iter()
+            .filter_map(|at| match self.get(*at) {
+                SpreadsheetCell::Formula(formula) => Some((*at, formula.evaluate(self))),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // A level at a time, each one split between the threads. The values
+    // are only put in once the whole level is done, nothing in it reads
+    // them.
+    //
+    // The threads are started again for every level rather than kept in a
+    // pool. They borrow the sheet to read it, and the next level can only
+    // be evaluated after this one's values are written with &mut self, so
+    // the borrow has to end in between. Keeping them would mean a lock
+    // around the sheet or copying it to them, and starting a thread costs
+    // far less than evaluating a level big enough to be split up
+    // (PARALLEL_MIN).
+    fn recalculate_levels(&mut self, formulas: HashSet<CellRef>, threads: usize) {
+        let (levels, cyclic) = self.graph.levels(&formulas);
+        for at in cyclic {
+            self.set_value(at, Value::Error(CellError::Cycle));
+        }
+        for level in levels {
+            let values = match level.len() < PARALLEL_MIN {
+                true => self.evaluate(&level),
+                false => {
+                    let sheet = &*self;
+                    let chunk = level.len().div_ceil(threads);
+                    thread::scope(|scope| {
+                        let parts: Vec<_> = level
+                            .chunks(chunk)
+                            .map(|part| scope.spawn(move || sheet.evaluate(part)))
+                            .collect();
+                        parts
+                            .into_iter()
+                            .flat_map(|part| part.join().expect("a formula panicked"))
+                            .collect()
+                    })
+                }
+            };
+            for (at, value) in values {
+                self.set_value(at, value);
+            }
+        }
+    }
+
+    pub fn recalculation(&self) -> Recalculation {
+        self.recalculation
+    }
+
+    // How formulas are recomputed from now on
+    pub fn set_recalculation(&mut self, recalculation: Recalculation) {
+        self.recalculation = recalculation;
+    }
+
+    // Recompute every formula on the sheet, which is never needed to keep
+    // it right but is handy for timing
+    pub fn recalculate_all(&mut self) {
+        self.recalculate(self.graph.formulas().collect());
+    }
+
+    pub(super) fn set_value(&mut self, at: CellRef, value: Value) {
+        if let Some(SpreadsheetCell::Formula(formula)) = self.cells.get_mut(at) {
+            formula.set_value(value);
+        }
+    }
+
+    pub(super) fn csv_layout(&self) -> &CsvLayout {
+        &self.csv
+    }
+
+    pub(super) fn set_csv_layout(&mut self, layout: CsvLayout) {
+        self.csv = layout;
+    }
+
+    // Every formula on the sheet and where it is, for a workbook to keep
+    // track of the ones reading other sheets
+    pub(super) fn formulas(&self) -> impl Iterator<Item = (CellRef, &Formula)> {
+        self.cells.cells().filter_map(|(at, cell)| match cell {
+            SpreadsheetCell::Formula(formula) => Some((at, formula)),
+            _ => None,
+        })
+    }
+
+    pub(super) fn formulas_mut(&mut self) -> impl Iterator<Item = &mut Formula> {
+        self.cells.cells_mut().filter_map(|(_, cell)| match cell {
+            SpreadsheetCell::Formula(formula) => Some(formula),
+            _ => None,
+        })
+    }
+
+    // After cells have moved around the graph is rebuilt from scratch and
+    // every formula recomputed
+    fn rebuild(&mut self) {
+        self.graph.clear();
+        for (at, cell) in self.cells.cells() {
+            if let SpreadsheetCell::Formula(formula) = cell {
+                let (cells, ranges) = formula.references();
+                self.graph.set(at, cells, ranges);
+            }
+        }
+        self.recalculate(self.graph.formulas().collect());
+    }
+
+    // After rows or columns move every formula is pointed at where its
+    // cells went
+    fn shift(&mut self, shift: Shift) {
+        for formula in self.formulas_mut() {
+            formula.shift(shift);
+        }
+        let formats = std::mem::take(&mut self.formats);
+        self.formats = formats
+            .into_iter()
+            .filter_map(|(range, format)| shift.range(range).map(|range| (range, format)))
+            .collect();
+        self.rebuild();
+    }
+
+    // Insert an empty row before `at`, everything below moves down one
+    pub fn insert_row(&mut self, at: usize) -> Result<(), Error> {
+        if at > self.rows() {
+            return Err(Error::OutOfRange(format!("row {}", at + 1)));
+        }
+        self.cells.insert_row(at);
+        if at < self.csv.height {
+            self.csv.height += 1;
+        }
+        self.shift(Shift::InsertRow(at));
+        Ok(())
+    }
+
+    // Delete a row, everything below moves up one. Returns the cells that
+    // were filled, by column.
+    pub fn delete_row(&mut self, at: usize) -> Result<Vec<(usize, SpreadsheetCell)>, Error> {
+        if at >= self.rows() {
+            return Err(Error::OutOfRange(format!("row {}", at + 1)));
+        }
+        let removed = self.cells.delete_row(at);
+        self.cells.adapt(0, 0);
+        if at < self.csv.height {
+            self.csv.height -= 1;
+        }
+        self.shift(Shift::DeleteRow(at));
+        Ok(removed)
+    }
+
+    pub fn insert_col(&mut self, at: usize) -> Result<(), Error> {
+        if at > self.cols() {
+            return Err(Error::OutOfRange(format!("column {}", column_name(at))));
+        }
+        self.cells.insert_col(at);
+        if at < self.csv.width {
+            self.csv.width += 1;
+        }
+        self.shift(Shift::InsertCol(at));
+        Ok(())
+    }
+
+    pub fn delete_col(&mut self, at: usize) -> Result<Vec<(usize, SpreadsheetCell)>, Error> {
+        if at >= self.cols() {
+            return Err(Error::OutOfRange(format!("column {}", column_name(at))));
+        }
+        let removed = self.cells.delete_col(at);
+        self.cells.adapt(0, 0);
+        if at < self.csv.width {
+            self.csv.width -= 1;
+        }
+        self.shift(Shift::DeleteCol(at));
+        Ok(removed)
+    }
+
+    // Sort the rows of a range by the values in one of its columns, cells
+    // outside of the range stay put. Blanks go last either way, and rows
+    // that compare equal keep their order. A formula's references move
+    // with its row, so =B2*C2 on row 2 is =B5*C5 once it's sorted to row 5.
+    pub fn sort_rows(&mut self, range: Range, by: usize, descending: bool) -> Result<(), Error> {
+        if !(range.start.col..=range.end.col).contains(&by) {
+            return Err(Error::OutOfRange(format!("column {}", column_name(by))));
+        }
+        self.grow(range.end.row + 1, range.end.col + 1);
+        // take the range's cells out, only the filled ones are visited
+        let mut rows: Vec<(usize, Vec<SpreadsheetCell>)> = (0..range.rows())
+            .map(|i| {
+                (
+                    range.start.row + i,
+                    vec![SpreadsheetCell::Empty; range.cols()],
+                )
+            })
+            .collect();
+        let filled: Vec<CellRef> = self.cells.cells_in(range).map(|(at, _)| at).collect();
+        for at in filled {
+            let cell = self.cells.put(at, SpreadsheetCell::Empty);
+            rows[at.row - range.start.row].1[at.col - range.start.col] = cell;
+        }
+        let key = by - range.start.col;
+        rows.sort_by(|(_, a), (_, b)| {
+            let (a, b) = (a[key].value(), b[key].value());
+            match (a == Value::Empty, b == Value::Empty) {
+                (false, false) if descending => b.sort_cmp(&a),
+                (false, false) => a.sort_cmp(&b),
+                (a, b) => a.cmp(&b), // <-- blanks last
+            }
+        });
+        let mut moved_to = vec![0; range.rows()];
+        for (i, (from, _)) in rows.iter().enumerate() {
+            moved_to[from - range.start.row] = range.start.row + i;
+        }
+        self.sort_formats(range, &moved_to);
+        for (i, (from, sorted)) in rows.into_iter().enumerate() {
+            let to = range.start.row + i;
+            let shift = Shift::Move {
+                rows: to as isize - from as isize,
+                cols: 0,
+            };
+            for (i, mut moved) in sorted.into_iter().enumerate() {
+                if let SpreadsheetCell::Formula(formula) = &mut moved {
+                    formula.shift(shift);
+                }
+                if !moved.is_empty() {
+                    self.cells.put(CellRef::new(to, range.start.col + i), moved);
+                }
+            }
+        }
+        self.rebuild();
+        Ok(())
+    }
+
+    // The smallest range holding every non-empty cell
+    pub fn used_range(&self) -> Option<Range> {
+        let mut used: Option<Range> = None;
+        for (at, _) in self.cells.cells() {
+            used = Some(match used {
+                Some(range) => Range::new(
+                    CellRef::new(range.start.row.min(at.row), range.start.col.min(at.col)),
+                    CellRef::new(range.end.row.max(at.row), range.end.col.max(at.col)),
+                ),
+                None => Range::new(at, at),
+            });
+        }
+        used
+    }
+
+    // A text table of the range with column letters and row numbers. Rows
+    // and columns past the last used cell are left out, and a range that's
+    // still too big is cut short with a note saying so.
+    pub fn render(&self, range: Range) -> String {
+        let used = self.used_range().map_or(range.start, |used| used.end);
+        let wanted = Range::new(
+            range.start,
+            CellRef::new(
+                range.end.row.min(used.row.max(range.start.row)),
+                range.end.col.min(used.col.max(range.start.col)),
+            ),
+        );
+        let range = Range::new(
+            range.start,
+            CellRef::new(
+                wanted.end.row.min(range.start.row + SHOWN_ROWS - 1),
+                wanted.end.col.min(range.start.col + SHOWN_COLS - 1),
+            ),
+        );
+        let shorten = |text: String| {
+            // line breaks (from CSV files) would tear the table apart
+            let text = text.replace("\r\n", "↵").replace(['\n', '\r'], "↵");
+            if text.chars().count() > MAX_WIDTH {
+                text.chars().take(MAX_WIDTH - 1).collect::<String>() + "…"
+            } else {
+                text
+            }
+        };
+        let texts: Vec<Vec<String>> = (range.start.row..=range.end.row)
+            .map(|row| {
+                (range.start.col..=range.end.col)
+                    .map(|col| shorten(self.display(CellRef::new(row, col))))
+                    .collect()
+            })
+            .collect();
+
+        let label_width = (range.end.row + 1).to_string().len();
+        let widths: Vec<usize> = (0..range.cols())
+            .map(|i| {
+                let header = column_name(range.start.col + i).len();
+                texts
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .fold(header, usize::max)
+            })
+            .collect();
+
+        let mut out = format!("{:label_width$} |", "");
+        for (i, width) in widths.iter().enumerate() {
+            out.push_str(&format!(" {:^width$} |", column_name(range.start.col + i)));
+        }
+        out.push('\n');
+        out.push_str(&"-".repeat(label_width + 1));
+        for width in &widths {
+            out.push_str(&format!("+{}", "-".repeat(width + 2)));
+        }
+        out.push('|');
+        for (r, row) in texts.iter().enumerate() {
+            let row_number = range.start.row + r + 1;
+            out.push_str(&format!("\n{row_number:>label_width$} |"));
+            for (i, text) in row.iter().enumerate() {
+                let width = widths[i];
+                let at = CellRef::new(range.start.row + r, range.start.col + i);
+                if self.get(at).is_number() {
+                    out.push_str(&format!(" {text:>width$} |"));
+                } else {
+                    out.push_str(&format!(" {text:<width$} |"));
+                }
+            }
+        }
+        if range != wanted {
+            out.push_str(&format!(
+                "\n(showing {range} of {wanted}, pick a smaller range to see the rest)"
+            ));
+        }
+        out
+    }
+}
+
+impl Context for Sheet {
+    fn value(&self, at: CellRef) -> Value {
+        Sheet::value(self, at)
+    }
+
+    fn filled(&self, range: Range) -> Vec<(CellRef, Value)> {
+        self.cells_in(range)
+            .map(|(at, cell)| (at, cell.value()))
+            .collect()
+    }
+}
+
+// Prints everything from A1 to the last used cell
+impl fmt::Display for Sheet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.used_range() {
+            Some(used) => write!(
+                f,
+                "{}",
+                self.render(Range::new(CellRef::new(0, 0), used.end))
+            ),
+            None => write!(f, "(empty sheet)"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(text: &str) -> Range {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn render_stops_at_the_used_cells() {
+        let mut sheet = Sheet::new();
+        sheet.set(CellRef::new(0, 0), SpreadsheetCell::Int(1));
+        sheet.set(CellRef::new(2, 2), SpreadsheetCell::Int(9));
+        let huge = sheet.render(range("A1:XFD1048576"));
+        assert_eq!(huge, sheet.render(range("A1:C3")));
+        assert_eq!(huge.lines().count(), 5);
+        // past the used cells there's still the one asked for
+        assert_eq!(sheet.render(range("E5:F6")).lines().count(), 3);
+    }
+
+    #[test]
+    fn render_says_when_it_cuts_a_range_short() {
+        let mut sheet = Sheet::new();
+        sheet.set(CellRef::new(499, 99), SpreadsheetCell::Int(1));
+        let shown = sheet.render(range("A1:XFD1048576"));
+        assert_eq!(shown.lines().count(), 2 + SHOWN_ROWS + 1);
+        assert!(
+            shown.ends_with("(showing A1:Z100 of A1:CV500, pick a smaller range to see the rest)")
+        );
+    }
+
+    // Blanks aren't visited, so sums down whole columns of a sparse sheet
+    // cost what's in them. Done one at a time that would be 200 million
+    // cells.
+    #[test]
+    fn whole_columns_cost_whats_in_them() {
+        let mut sheet = Sheet::new();
+        sheet.set(CellRef::new(0, 0), SpreadsheetCell::Int(1));
+        sheet.set(CellRef::new(999_999, 0), SpreadsheetCell::Int(2));
+        sheet.set(CellRef::new(500_000, 0), SpreadsheetCell::Text("x".into()));
+        let cells = (0..200).map(|row| {
+            let sum = SpreadsheetCell::formula("=SUM(A1:A1048576)").unwrap();
+            (CellRef::new(row, 1), sum)
+        });
+        sheet.set_many(cells);
+        assert!(sheet.is_sparse());
+        assert_eq!(sheet.value(CellRef::new(199, 1)), Value::Int(3));
+    }
+
+    #[test]
+    fn a_whole_column_is_one_format() {
+        let mut sheet = Sheet::new();
+        let money: Format = "#,##0.00".parse().unwrap();
+        sheet.format_range(range("C1:C1048576"), money.clone());
+        sheet.format_range(range("C1:C1048576"), money.clone());
+        assert_eq!(sheet.formats().count(), 1);
+        assert_eq!(sheet.format(CellRef::new(700_000, 2)), &money);
+        assert_eq!(sheet.format(CellRef::new(700_000, 3)), &Format::General);
+
+        // General over part of it has to be kept to hide what's under it,
+        // over all of it there's nothing left to hide
+        assert_eq!(sheet.set_format(CellRef::new(4, 2), Format::General), money);
+        assert_eq!(sheet.format(CellRef::new(4, 2)), &Format::General);
+        assert_eq!(sheet.formats().count(), 2);
+        sheet.format_range(range("A1:D1048576"), Format::General);
+        assert_eq!(sheet.formats().count(), 0);
+    }
+
+    #[test]
+    fn formats_move_with_their_rows() {
+        let mut sheet = Sheet::new();
+        for (row, n) in [3, 1, 4, 2].into_iter().enumerate() {
+            sheet.set(CellRef::new(row, 0), SpreadsheetCell::Int(n));
+        }
+        let money: Format = "0.00".parse().unwrap();
+        sheet.format_range(range("A1:B2"), money.clone());
+        let formatted = |sheet: &Sheet| -> Vec<String> {
+            let layers = sheet.formats().map(|(range, _)| range.to_string());
+            layers.collect()
+        };
+
+        // inserting inside stretches it, deleting shrinks it
+        sheet.insert_row(1).unwrap();
+        assert_eq!(formatted(&sheet), ["A1:B3"]);
+        sheet.delete_row(0).unwrap();
+        sheet.delete_row(0).unwrap();
+        assert_eq!(formatted(&sheet), ["A1:B1"]);
+        sheet.insert_row(0).unwrap();
+        sheet.set(CellRef::new(0, 0), SpreadsheetCell::Int(3));
+        sheet.format_range(range("A1:B2"), money.clone());
+
+        // sorting only column A takes its part along with the values and
+        // leaves column B's where it was
+        sheet.sort_rows(range("A1:A4"), 0, false).unwrap();
+        assert_eq!(formatted(&sheet), ["B1:B2", "A1:A1", "A3:A3"]);
+        let shown: Vec<String> = (0..4)
+            .map(|row| sheet.display(CellRef::new(row, 0)))
+            .collect();
+        assert_eq!(shown, ["1.00", "2", "3.00", "4"]);
+    }
+
+    // What every cell holds, formulas by the value they worked out
+    fn values(sheet: &Sheet) -> Vec<(CellRef, Value)> {
+        sheet.cells().map(|(at, cell)| (at, cell.value())).collect()
+    }
+
+    // Enough independent formulas to be split between threads, chains of
+    // them a few levels deep, errors, and cycles with formulas reading them
+    #[test]
+    fn parallel_comes_out_the_same() {
+        let rows = 2 * PARALLEL_MIN;
+        let mut cells = vec![
+            (CellRef::new(0, 6), SpreadsheetCell::Int(3)),
+            (
+                CellRef::new(0, 7),
+                SpreadsheetCell::formula("=H2+1").unwrap(),
+            ),
+            (
+                CellRef::new(1, 7),
+                SpreadsheetCell::formula("=H1+1").unwrap(),
+            ),
+        ];
+        for row in 0..rows {
+            let n = row + 1;
+            let formulas = [
+                format!("=A{n}*2+G1"),
+                format!("=B{n}+C{}", n.max(2) - 1), // <-- C1 reads itself
+                format!("=IF(A{n}<100, B{n}/A{n}, A{n}/0)"),
+                format!("=CONCAT(\"row \", A{n}, \" \", H1)"),
+            ];
+            cells.push((CellRef::new(row, 0), SpreadsheetCell::Int(row as i64)));
+            for (col, formula) in formulas.iter().enumerate() {
+                let cell = SpreadsheetCell::formula(formula).unwrap();
+                cells.push((CellRef::new(row, col + 1), cell));
+            }
+        }
+        let mut sequential = Sheet::new();
+        sequential.set_many(cells.clone());
+        let mut parallel = Sheet::new();
+        parallel.set_recalculation(Recalculation::Parallel(4));
+        parallel.set_many(cells);
+        assert_eq!(values(&parallel), values(&sequential));
+        assert_eq!(
+            parallel.value(CellRef::new(0, 7)),
+            Value::Error(CellError::Cycle)
+        );
+        assert_eq!(
+            parallel.value(CellRef::new(200, 3)),
+            Value::Error(CellError::Div0)
+        );
+
+        // and again for an edit every formula in column B reads
+        sequential.set(CellRef::new(0, 6), SpreadsheetCell::Float(0.5));
+        parallel.set(CellRef::new(0, 6), SpreadsheetCell::Float(0.5));
+        assert_eq!(values(&parallel), values(&sequential));
+        parallel.recalculate_all();
+        assert_eq!(values(&parallel), values(&sequential));
+    }
+}
@@ -0,0 +1,499 @@
+// Where a sheet keeps its cells. A grid of rows is quick and small when
+// most cells have something in them, but a sheet with a few cells a
+// million rows down would be a million rows of blanks, so there's a
+// sparse kind too that only keeps the cells that are filled, in a B-tree
+// ordered row by row.
+//
+// A sheet starts out dense and switches between the two as it fills up or
+// empties, going by the share of its grid that's filled. The thresholds
+// are apart so a sheet near one of them doesn't keep switching back and
+// forth.
+use super::{CellRef, Range, SpreadsheetCell};
+use std::{
+    collections::BTreeMap,
+    fmt,
+    ops::{Deref, DerefMut},
+};
+
+// Dense storage spends a whole cell on every blank, sparse storage a few
+// times that on each filled cell but nothing on blanks
+const SPARSE_BELOW: f64 = 0.25;
+const DENSE_ABOVE: f64 = 0.5;
+
+// Grids this small stay dense whatever's in them, there's nothing to save
+const SMALL: usize = 4096;
+
+pub trait Storage: fmt::Debug {
+    // How big the grid is, rows and columns. Cells can be empty right up
+    // to the edge, it's as big as the cells set so far have made it.
+    fn size(&self) -> (usize, usize);
+
+    // Make the grid at least this big
+    fn grow(&mut self, rows: usize, cols: usize);
+
+    // None for an empty cell
+    fn get(&self, at: CellRef) -> Option<&SpreadsheetCell>;
+
+    fn get_mut(&mut self, at: CellRef) -> Option<&mut SpreadsheetCell>;
+
+    // Put a cell in, growing the grid to fit, and hand back what was there.
+    // Putting in an empty cell clears it.
+    fn put(&mut self, at: CellRef, cell: SpreadsheetCell) -> SpreadsheetCell;
+
+    // How many cells have something in them
+    fn filled(&self) -> usize;
+
+    // The cells in a range that have something in them, row by row
+    fn cells_in(&self, range: Range) -> Box<dyn Iterator<Item = (CellRef, &SpreadsheetCell)> + '_>;
+
+    fn cells_mut(&mut self) -> Box<dyn Iterator<Item = (CellRef, &mut SpreadsheetCell)> + '_>;
+
+    // Insert an empty row or column before `at`, moving the rest along
+    fn insert_row(&mut self, at: usize);
+    fn insert_col(&mut self, at: usize);
+
+    // Take a row or column out and hand back the cells that had something
+    // in them, with the column or row each one was in
+    fn delete_row(&mut self, at: usize) -> Vec<(usize, SpreadsheetCell)>;
+    fn delete_col(&mut self, at: usize) -> Vec<(usize, SpreadsheetCell)>;
+
+    // Every cell that has something in it
+    fn cells(&self) -> Box<dyn Iterator<Item = (CellRef, &SpreadsheetCell)> + '_> {
+        match self.size() {
+            (0, _) | (_, 0) => Box::new(std::iter::empty()),
+            (rows, cols) => self.cells_in(Range::new(
+                CellRef::new(0, 0),
+                CellRef::new(rows - 1, cols - 1),
+            )),
+        }
+    }
+}
+
+// Rows of cells, every row as long as the widest
+#[derive(Debug, Clone, Default)]
+pub struct Dense {
+    rows: Vec<Vec<SpreadsheetCell>>,
+    cols: usize,
+    filled: usize,
+}
+
+impl Storage for Dense {
+    fn size(&self) -> (usize, usize) {
+        (self.rows.len(), self.cols)
+    }
+
+    fn grow(&mut self, rows: usize, cols: usize) {
+        if cols > self.cols {
+            self.cols = cols;
+            for row in &mut self.rows {
+                row.resize(cols, SpreadsheetCell::Empty);
+            }
+        }
+        while self.rows.len() < rows {
+            self.rows.push(vec![SpreadsheetCell::Empty; self.cols]);
+        }
+    }
+
+    fn get(&self, at: CellRef) -> Option<&SpreadsheetCell> {
+        self.rows
+            .get(at.row)
+            .and_then(|row| row.get(at.col))
+            .filter(|cell| !cell.is_empty())
+    }
+
+    fn get_mut(&mut self, at: CellRef) -> Option<&mut SpreadsheetCell> {
+        self.rows
+            .get_mut(at.row)
+            .and_then(|row| row.get_mut(at.col))
+            .filter(|cell| !cell.is_empty())
+    }
+
+    fn put(&mut self, at: CellRef, cell: SpreadsheetCell) -> SpreadsheetCell {
+        self.grow(at.row + 1, at.col + 1);
+        self.filled += !cell.is_empty() as usize;
+        let old = std::mem::replace(&mut self.rows[at.row][at.col], cell);
+        self.filled -= !old.is_empty() as usize;
+        old
+    }
+
+    fn filled(&self) -> usize {
+        self.filled
+    }
+
+    fn cells_in(&self, range: Range) -> Box<dyn Iterator<Item = (CellRef, &SpreadsheetCell)> + '_> {
+        // both ends clamped, a range right of the grid has no columns in it
+        let cols = range.start.col.min(self.cols)..(range.end.col + 1).min(self.cols);
+        let rows = self.rows.iter().enumerate();
+        Box::new(
+            rows.skip(range.start.row)
+                .take(range.rows())
+                .flat_map(move |(r, row)| {
+                    let cells = row[cols.clone()].iter().enumerate();
+                    let start = cols.start;
+                    cells.map(move |(i, cell)| (CellRef::new(r, start + i), cell))
+                })
+                .filter(|(_, cell)| !cell.is_empty()),
+        )
+    }
+
+    fn cells_mut(&mut self) -> Box<dyn Iterator<Item = (CellRef, &mut SpreadsheetCell)> + '_> {
+        Box::new(
+            self.rows
+                .iter_mut()
+                .enumerate()
+                .flat_map(|(r, row)| {
+                    let cells = row.iter_mut().enumerate();
+                    cells.map(move |(c, cell)| (CellRef::new(r, c), cell))
+                })
+                .filter(|(_, cell)| !cell.is_empty()),
+        )
+    }
+
+    fn insert_row(&mut self, at: usize) {
+        self.rows
+            .insert(at, vec![SpreadsheetCell::Empty; self.cols]);
+    }
+
+    fn insert_col(&mut self, at: usize) {
+        self.cols += 1;
+        for row in &mut self.rows {
+            row.insert(at, SpreadsheetCell::Empty);
+        }
+    }
+
+    fn delete_row(&mut self, at: usize) -> Vec<(usize, SpreadsheetCell)> {
+        let removed: Vec<(usize, SpreadsheetCell)> = self
+            .rows
+            .remove(at)
+            .into_iter()
+            .enumerate()
+            .filter(|(_, cell)| !cell.is_empty())
+            .collect();
+        self.filled -= removed.len();
+        removed
+    }
+
+    fn delete_col(&mut self, at: usize) -> Vec<(usize, SpreadsheetCell)> {
+        self.cols -= 1;
+        let removed: Vec<(usize, SpreadsheetCell)> = self
+            .rows
+            .iter_mut()
+            .map(|row| row.remove(at))
+            .enumerate()
+            .filter(|(_, cell)| !cell.is_empty())
+            .collect();
+        self.filled -= removed.len();
+        removed
+    }
+}
+
+// Only the filled cells, by address. CellRef orders by row and then
+// column, so the B-tree holds them row by row and a run of rows is one
+// range of it.
+#[derive(Debug, Clone, Default)]
+pub struct Sparse {
+    cells: BTreeMap<CellRef, SpreadsheetCell>,
+    rows: usize,
+    cols: usize,
+}
+
+impl Sparse {
+    // Move every cell matching `moves` with `to`
+    fn rekey(&mut self, moves: impl Fn(CellRef) -> bool, to: impl Fn(CellRef) -> CellRef) {
+        let moving: Vec<CellRef> = self.cells.keys().copied().filter(|at| moves(*at)).collect();
+        let moved: Vec<(CellRef, SpreadsheetCell)> = moving
+            .into_iter()
+            .map(|at| (to(at), self.cells.remove(&at).unwrap()))
+            .collect();
+        self.cells.extend(moved);
+    }
+}
+
+impl Storage for Sparse {
+    fn size(&self) -> (usize, usize) {
+        (self.rows, self.cols)
+    }
+
+    fn grow(&mut self, rows: usize, cols: usize) {
+        self.rows = self.rows.max(rows);
+        self.cols = self.cols.max(cols);
+    }
+
+    fn get(&self, at: CellRef) -> Option<&SpreadsheetCell> {
+        self.cells.get(&at)
+    }
+
+    fn get_mut(&mut self, at: CellRef) -> Option<&mut SpreadsheetCell> {
+        self.cells.get_mut(&at)
+    }
+
+    fn put(&mut self, at: CellRef, cell: SpreadsheetCell) -> SpreadsheetCell {
+        self.grow(at.row + 1, at.col + 1);
+        let old = match cell.is_empty() {
+            true => self.cells.remove(&at),
+            false => self.cells.insert(at, cell),
+        };
+        old.unwrap_or_default()
+    }
+
+    fn filled(&self) -> usize {
+        self.cells.len()
+    }
+
+    // Only the filled cells in the range's rows are looked at, however
+    // many blanks there are around them
+    fn cells_in(&self, range: Range) -> Box<dyn Iterator<Item = (CellRef, &SpreadsheetCell)> + '_> {
+        let first = CellRef::new(range.start.row, 0);
+        let last = CellRef::new(range.end.row, usize::MAX);
+        Box::new(
+            self.cells
+                .range(first..=last)
+                .filter(move |(at, _)| range.contains(**at))
+                .map(|(at, cell)| (*at, cell)),
+        )
+    }
+
+    fn cells_mut(&mut self) -> Box<dyn Iterator<Item = (CellRef, &mut SpreadsheetCell)> + '_> {
+        Box::new(self.cells.iter_mut().map(|(at, cell)| (*at, cell)))
+    }
+
+    fn insert_row(&mut self, at: usize) {
+        self.rows += 1;
+        self.rekey(
+            |cell| cell.row >= at,
+            |cell| CellRef::new(cell.row + 1, cell.col),
+        );
+    }
+
+    fn insert_col(&mut self, at: usize) {
+        self.cols += 1;
+        self.rekey(
+            |cell| cell.col >= at,
+            |cell| CellRef::new(cell.row, cell.col + 1),
+        );
+    }
+
+    fn delete_row(&mut self, at: usize) -> Vec<(usize, SpreadsheetCell)> {
+        let row = CellRef::new(at, 0)..=CellRef::new(at, usize::MAX);
+        let filled: Vec<CellRef> = self.cells.range(row).map(|(at, _)| *at).collect();
+        let removed = filled
+            .into_iter()
+            .map(|at| (at.col, self.cells.remove(&at).unwrap()))
+            .collect();
+        self.rows -= 1;
+        self.rekey(
+            |cell| cell.row > at,
+            |cell| CellRef::new(cell.row - 1, cell.col),
+        );
+        removed
+    }
+
+    fn delete_col(&mut self, at: usize) -> Vec<(usize, SpreadsheetCell)> {
+        let filled: Vec<CellRef> = self
+            .cells
+            .keys()
+            .copied()
+            .filter(|cell| cell.col == at)
+            .collect();
+        let removed = filled
+            .into_iter()
+            .map(|at| (at.row, self.cells.remove(&at).unwrap()))
+            .collect();
+        self.cols -= 1;
+        self.rekey(
+            |cell| cell.col > at,
+            |cell| CellRef::new(cell.row, cell.col - 1),
+        );
+        removed
+    }
+}
+
+// The storage a sheet has, one kind or the other. It derefs to the trait
+// so the sheet doesn't have to care which.
+#[derive(Debug, Clone)]
+pub enum Cells {
+    Dense(Dense),
+    Sparse(Sparse),
+}
+
+impl Default for Cells {
+    fn default() -> Cells {
+        Cells::Dense(Dense::default())
+    }
+}
+
+impl Deref for Cells {
+    type Target = dyn Storage;
+
+    fn deref(&self) -> &(dyn Storage + 'static) {
+        match self {
+            Cells::Dense(dense) => dense,
+            Cells::Sparse(sparse) => sparse,
+        }
+    }
+}
+
+impl DerefMut for Cells {
+    fn deref_mut(&mut self) -> &mut (dyn Storage + 'static) {
+        match self {
+            Cells::Dense(dense) => dense,
+            Cells::Sparse(sparse) => sparse,
+        }
+    }
+}
+
+// Two sheets with the same cells are equal whichever way they keep them,
+// and however far blanks have stretched the grid
+impl PartialEq for Cells {
+    fn eq(&self, other: &Cells) -> bool {
+        self.filled() == other.filled() && self.cells().eq(other.cells())
+    }
+}
+
+impl Cells {
+    pub fn is_sparse(&self) -> bool {
+        matches!(self, Cells::Sparse(_))
+    }
+
+    // Switch to the kind of storage that suits a grid of `rows` by `cols`
+    // with the cells there are now. Called before the grid grows, so a
+    // cell set far out doesn't first fill a dense grid with blanks.
+    pub fn adapt(&mut self, rows: usize, cols: usize) {
+        let (rows, cols) = (rows.max(self.size().0), cols.max(self.size().1));
+        let area = rows.saturating_mul(cols);
+        let fill = self.filled() as f64 / area.max(1) as f64;
+        let sparse = match self {
+            _ if area <= SMALL => false,
+            Cells::Dense(_) => fill < SPARSE_BELOW,
+            Cells::Sparse(_) => fill <= DENSE_ABOVE,
+        };
+        if sparse == self.is_sparse() {
+            return;
+        }
+        let mut new = match sparse {
+            true => Cells::Sparse(Sparse::default()),
+            false => Cells::Dense(Dense::default()),
+        };
+        let (old_rows, old_cols) = self.size();
+        new.grow(old_rows, old_cols);
+        for (at, cell) in self.cells_mut() {
+            new.put(at, std::mem::take(cell));
+        }
+        *self = new;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(address: &str) -> CellRef {
+        address.parse().unwrap()
+    }
+
+    fn range(range: &str) -> Range {
+        range.parse().unwrap()
+    }
+
+    // Fill the first `count` cells of a 100 by 100 grid, row by row
+    fn filled(mut cells: Cells, count: usize) -> Cells {
+        cells.adapt(100, 100);
+        cells.grow(100, 100);
+        for i in 0..count {
+            cells.put(
+                CellRef::new(i / 100, i % 100),
+                SpreadsheetCell::Int(i as i64),
+            );
+        }
+        cells
+    }
+
+    #[test]
+    fn deleting_hands_back_the_filled_cells() {
+        for mut cells in [
+            Cells::Dense(Dense::default()),
+            Cells::Sparse(Sparse::default()),
+        ] {
+            cells.put(at("B2"), SpreadsheetCell::Int(1));
+            cells.put(at("D2"), SpreadsheetCell::Text(String::from("x")));
+            cells.put(at("B5"), SpreadsheetCell::Int(3));
+            cells.put(at("J10"), SpreadsheetCell::Int(4));
+            assert_eq!(
+                cells.delete_row(1),
+                [
+                    (1, SpreadsheetCell::Int(1)),
+                    (3, SpreadsheetCell::Text(String::from("x")))
+                ]
+            );
+            assert_eq!(cells.delete_col(1), [(3, SpreadsheetCell::Int(3))]);
+            assert_eq!(cells.delete_row(0), []);
+            assert_eq!(cells.filled(), 1);
+            assert_eq!(cells.size(), (8, 9));
+        }
+        // a blank row of a huge sheet is nothing to keep
+        let mut cells = Cells::Sparse(Sparse::default());
+        cells.grow(1_000_000, 16_384);
+        assert_eq!(cells.delete_row(5), []);
+        assert_eq!(cells.delete_col(5), []);
+    }
+
+    #[test]
+    fn switches_by_how_full_it_is() {
+        let mut cells = filled(Cells::default(), 10);
+        assert!(cells.is_sparse());
+
+        // between the thresholds it stays whatever it was
+        let before = cells.clone();
+        cells = filled(cells, 4000);
+        cells.adapt(0, 0);
+        assert!(cells.is_sparse());
+        cells = filled(cells, 6000);
+        cells.adapt(0, 0);
+        assert!(!cells.is_sparse());
+        cells = filled(cells, 4000);
+        for i in 4000..6000 {
+            cells.put(CellRef::new(i / 100, i % 100), SpreadsheetCell::Empty);
+        }
+        cells.adapt(0, 0);
+        assert!(!cells.is_sparse());
+        assert_eq!(cells.filled(), 4000);
+
+        // and what's in it never changes on the way
+        for i in 10..4000 {
+            cells.put(CellRef::new(i / 100, i % 100), SpreadsheetCell::Empty);
+        }
+        cells.adapt(0, 0);
+        assert!(cells.is_sparse());
+        assert_eq!(cells, before);
+    }
+
+    #[test]
+    fn small_grids_stay_dense() {
+        let mut cells = Cells::default();
+        cells.adapt(64, 64);
+        assert!(!cells.is_sparse());
+        cells.adapt(65, 64);
+        assert!(cells.is_sparse());
+    }
+
+    #[test]
+    fn cells_in_only_has_filled_cells() {
+        for sparse in [false, true] {
+            let mut cells = match sparse {
+                true => Cells::Sparse(Sparse::default()),
+                false => Cells::Dense(Dense::default()),
+            };
+            cells.grow(3, 2);
+            cells.put(at("A1"), SpreadsheetCell::Int(1));
+            cells.put(at("B3"), SpreadsheetCell::Int(2));
+
+            let found =
+                |r: &str| -> Vec<CellRef> { cells.cells_in(range(r)).map(|(at, _)| at).collect() };
+            assert_eq!(found("A1:Z100"), [at("A1"), at("B3")], "sparse: {sparse}");
+            assert_eq!(found("B1:B2"), [], "sparse: {sparse}");
+            // right of the grid and below it
+            assert_eq!(found("D1:E2"), [], "sparse: {sparse}");
+            assert_eq!(found("A4:B9"), [], "sparse: {sparse}");
+        }
+    }
+}
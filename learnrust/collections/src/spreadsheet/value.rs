@@ -0,0 +1,330 @@
+// What a cell evaluates to. Plain cells are their own value, a formula's
+// value is whatever it last computed, which may be an error like #CYCLE.
+//
+// The coercion rules, used by operators and functions alike:
+// - Empty is 0 in arithmetic, "" in text and FALSE as a condition
+// - TRUE and FALSE are 1 and 0 in arithmetic
+// - text is a number only if it reads as one ("12", " 1.5 "), anything
+//   else is #VALUE!, and a condition only if it's "TRUE" or "FALSE"
+// - numbers and booleans become text the way they're displayed
+// - an error is never coerced, it's passed along to whatever used it
+//
+// Money is a number too, but kept in whole cents (see money.rs) for as
+// long as the arithmetic allows, see arithmetic() at the bottom.
+use super::money::Money;
+use std::{cmp::Ordering, fmt};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CellError {
+    Cycle,
+    Ref,
+    Name,
+    Value,
+    Div0,
+    NotAvailable, // <-- a lookup that found nothing
+}
+
+impl fmt::Display for CellError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text = match self {
+            CellError::Cycle => "#CYCLE",
+            CellError::Ref => "#REF!",
+            CellError::Name => "#NAME?",
+            CellError::Value => "#VALUE!",
+            CellError::Div0 => "#DIV/0!",
+            CellError::NotAvailable => "#N/A",
+        };
+        write!(f, "{text}")
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    #[default]
+    Empty,
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Bool(bool),
+    Money(Money),
+    Error(CellError),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Empty => Ok(()),
+            Value::Int(n) => write!(f, "{n}"),
+            Value::Float(n) => write!(f, "{n}"),
+            Value::Text(s) => write!(f, "{s}"),
+            Value::Bool(true) => write!(f, "TRUE"),
+            Value::Bool(false) => write!(f, "FALSE"),
+            Value::Money(money) => write!(f, "{money}"),
+            Value::Error(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+// A number taking part in arithmetic, Ints stay Ints as long as they can
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Int(i64),
+    Float(f64),
+    Money(Money),
+}
+
+impl Number {
+    pub fn as_f64(self) -> f64 {
+        match self {
+            Number::Int(n) => n as f64,
+            Number::Float(n) => n,
+            Number::Money(money) => money.to_f64(),
+        }
+    }
+}
+
+impl From<Number> for Value {
+    fn from(n: Number) -> Value {
+        match n {
+            Number::Int(n) => Value::Int(n),
+            Number::Float(n) if n.is_finite() => Value::Float(n),
+            Number::Float(_) => Value::Error(CellError::Value), // <-- overflowed to infinity
+            Number::Money(money) => Value::Money(money),
+        }
+    }
+}
+
+// The result of some arithmetic, which is either a number or an error
+impl From<Result<Number, CellError>> for Value {
+    fn from(result: Result<Number, CellError>) -> Value {
+        match result {
+            Ok(n) => n.into(),
+            Err(err) => Value::Error(err),
+        }
+    }
+}
+
+impl Value {
+    pub fn is_number(&self) -> bool {
+        matches!(self, Value::Int(_) | Value::Float(_) | Value::Money(_))
+    }
+
+    pub fn to_number(&self) -> Result<Number, CellError> {
+        match self {
+            Value::Empty => Ok(Number::Int(0)),
+            Value::Int(n) => Ok(Number::Int(*n)),
+            Value::Float(n) => Ok(Number::Float(*n)),
+            Value::Money(money) => Ok(Number::Money(*money)),
+            Value::Bool(b) => Ok(Number::Int(*b as i64)),
+            Value::Text(s) => {
+                let s = s.trim();
+                if let Ok(n) = s.parse() {
+                    return Ok(Number::Int(n));
+                }
+                // "inf" and "NaN" parse as floats but aren't numbers to anyone
+                match s.parse::<f64>() {
+                    Ok(n) if n.is_finite() => Ok(Number::Float(n)),
+                    _ => Err(CellError::Value),
+                }
+            }
+            Value::Error(err) => Err(*err),
+        }
+    }
+
+    pub fn to_bool(&self) -> Result<bool, CellError> {
+        match self {
+            Value::Empty => Ok(false),
+            Value::Bool(b) => Ok(*b),
+            Value::Int(n) => Ok(*n != 0),
+            Value::Float(n) => Ok(*n != 0.0),
+            Value::Money(money) => Ok(money.minor() != 0),
+            Value::Text(s) if s.trim().eq_ignore_ascii_case("true") => Ok(true),
+            Value::Text(s) if s.trim().eq_ignore_ascii_case("false") => Ok(false),
+            Value::Text(_) => Err(CellError::Value),
+            Value::Error(err) => Err(*err),
+        }
+    }
+
+    pub fn to_text(&self) -> Result<String, CellError> {
+        match self {
+            Value::Error(err) => Err(*err),
+            value => Ok(value.to_string()),
+        }
+    }
+
+    // Where a value sorts among values of other types: numbers, then text,
+    // then booleans (the order other spreadsheets use), blanks go last
+    fn rank(&self) -> u8 {
+        match self {
+            Value::Int(_) | Value::Float(_) | Value::Money(_) => 0,
+            Value::Text(_) => 1,
+            Value::Bool(_) => 2,
+            Value::Error(_) => 3,
+            Value::Empty => 4,
+        }
+    }
+
+    // A total order over every value so mixed columns can be sorted. Text
+    // compares case-insensitively, numbers by value whether Int or Float.
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Text(a), Value::Text(b)) => a.to_lowercase().cmp(&b.to_lowercase()),
+            (Value::Bool(a), Value::Bool(b)) => a.cmp(b),
+            (Value::Int(a), Value::Int(b)) => a.cmp(b),
+            (Value::Money(a), Value::Money(b)) if a.symbol() == b.symbol() => {
+                a.minor().cmp(&b.minor())
+            }
+            (a, b) if a.is_number() && b.is_number() => {
+                let (a, b) = (a.to_number().unwrap(), b.to_number().unwrap());
+                a.as_f64().total_cmp(&b.as_f64())
+            }
+            (a, b) => a.rank().cmp(&b.rank()),
+        }
+    }
+
+    // What the comparison operators use. A blank stands in for 0, "" or
+    // FALSE depending on what it's compared with, errors are passed along.
+    pub fn compare(&self, other: &Value) -> Result<Ordering, CellError> {
+        let blank_as = |other: &Value| match other {
+            Value::Text(_) => Value::Text(String::new()),
+            Value::Bool(_) => Value::Bool(false),
+            _ => Value::Int(0),
+        };
+        match (self, other) {
+            (Value::Error(err), _) | (_, Value::Error(err)) => Err(*err),
+            (Value::Empty, Value::Empty) => Ok(Ordering::Equal),
+            (Value::Empty, b) => Ok(blank_as(b).sort_cmp(b)),
+            (a, Value::Empty) => Ok(a.sort_cmp(&blank_as(a))),
+            (a, b) => Ok(a.sort_cmp(b)),
+        }
+    }
+}
+
+// Money stays in whole cents when it's added to or taken from money in
+// the same currency or a whole number, and when it's multiplied by a whole
+// number or divided by one that leaves whole cents. None for anything
+// else, which is worked out the way plain numbers are.
+fn money(op: char, left: Number, right: Number) -> Option<Number> {
+    use Number::Int;
+    let (minor, symbol) = match (left, right) {
+        (Number::Money(l), Number::Money(r)) if l.symbol() == r.symbol() => match op {
+            '+' => (l.minor().checked_add(r.minor())?, l.symbol()),
+            '-' => (l.minor().checked_sub(r.minor())?, l.symbol()),
+            _ => return None,
+        },
+        (Number::Money(m), Int(n)) | (Int(n), Number::Money(m)) if op == '*' => {
+            (m.minor().checked_mul(n)?, m.symbol())
+        }
+        (Number::Money(m), Int(n)) if op == '/' => {
+            let rem = m.minor().checked_rem(n).filter(|rem| *rem == 0);
+            (rem.and(m.minor().checked_div(n))?, m.symbol())
+        }
+        (Number::Money(m), Int(n)) | (Int(n), Number::Money(m)) => {
+            let n = n.checked_mul(100)?; // <-- in cents
+            let minor = match (op, left) {
+                ('+', _) => m.minor().checked_add(n)?,
+                ('-', Number::Money(_)) => m.minor().checked_sub(n)?,
+                ('-', _) => n.checked_sub(m.minor())?,
+                _ => return None,
+            };
+            (minor, m.symbol())
+        }
+        _ => return None,
+    };
+    Some(Number::Money(Money::new(minor, symbol)))
+}
+
+// Int arithmetic that overflows falls back to floats rather than wrapping
+pub fn arithmetic(op: char, left: Number, right: Number) -> Result<Number, CellError> {
+    use Number::{Float, Int};
+    let float = |l: f64, r: f64| match op {
+        '+' => Ok(Float(l + r)),
+        '-' => Ok(Float(l - r)),
+        '*' => Ok(Float(l * r)),
+        '/' if r == 0.0 => Err(CellError::Div0),
+        '/' => Ok(Float(l / r)),
+        '^' => Ok(Float(l.powf(r))),
+        _ => Err(CellError::Value),
+    };
+    match (left, right) {
+        // money over money in the same currency is how many times it goes
+        (Number::Money(l), Number::Money(r)) if op == '/' && l.symbol() == r.symbol() => {
+            arithmetic('/', Int(l.minor()), Int(r.minor()))
+        }
+        (Number::Money(_), _) | (_, Number::Money(_)) => match money(op, left, right) {
+            Some(n) => Ok(n),
+            None => float(left.as_f64(), right.as_f64()),
+        },
+        (Int(l), Int(r)) => {
+            let exact = match op {
+                '+' => l.checked_add(r),
+                '-' => l.checked_sub(r),
+                '*' => l.checked_mul(r),
+                '/' if r == 0 => return Err(CellError::Div0),
+                // 6/3 stays an Int, 7/2 becomes a Float
+                '/' => l
+                    .checked_rem(r)
+                    .filter(|rem| *rem == 0)
+                    .and(l.checked_div(r)),
+                '^' => u32::try_from(r).ok().and_then(|r| l.checked_pow(r)),
+                _ => None,
+            };
+            match exact {
+                Some(n) => Ok(Int(n)),
+                None => float(l as f64, r as f64),
+            }
+        }
+        (l, r) => float(l.as_f64(), r.as_f64()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dollars(minor: i64) -> Number {
+        Number::Money(Money::new(minor, '$'))
+    }
+
+    #[test]
+    fn money_stays_in_cents() {
+        use Number::Int;
+        // ten dimes are a dollar, which 0.1 added up ten times isn't
+        let dimes = (0..10).try_fold(Int(0), |total, _| arithmetic('+', total, dollars(10)));
+        assert_eq!(dimes, Ok(dollars(100)));
+        assert_eq!(arithmetic('-', Int(2), dollars(50)), Ok(dollars(150)));
+        assert_eq!(arithmetic('*', Int(-3), dollars(50)), Ok(dollars(-150)));
+        assert_eq!(arithmetic('/', dollars(150), Int(3)), Ok(dollars(50)));
+        // money over money is a plain number
+        assert_eq!(arithmetic('/', dollars(150), dollars(50)), Ok(Int(3)));
+        assert_eq!(
+            arithmetic('/', dollars(100), dollars(0)),
+            Err(CellError::Div0)
+        );
+    }
+
+    #[test]
+    fn money_that_cant_stay_exact_is_a_float() {
+        use Number::{Float, Int};
+        let euros = Number::Money(Money::new(100, '€'));
+        assert_eq!(arithmetic('+', dollars(100), euros), Ok(Float(2.0)));
+        assert_eq!(arithmetic('/', dollars(100), Int(3)), Ok(Float(1.0 / 3.0)));
+        assert_eq!(arithmetic('*', dollars(150), Float(0.5)), Ok(Float(0.75)));
+        assert_eq!(
+            arithmetic('*', dollars(i64::MAX), Int(2)).map(|n| n.as_f64() > 1e17),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn money_as_a_value() {
+        let money = Value::Money(Money::new(-5, '£'));
+        assert_eq!(money.to_string(), "-£0.05");
+        assert!(money.is_number());
+        assert_eq!(money.to_bool(), Ok(true));
+        assert_eq!(money.compare(&Value::Empty), Ok(Ordering::Less));
+        let more = Value::Money(Money::new(5, '£'));
+        assert_eq!(money.sort_cmp(&more), Ordering::Less);
+        assert_eq!(more.sort_cmp(&Value::Int(1)), Ordering::Less);
+    }
+}
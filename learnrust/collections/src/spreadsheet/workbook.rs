@@ -0,0 +1,609 @@
+// A workbook is a set of named sheets whose formulas can read each other's
+// cells, like =Sales!B2 * Rates!A1. Sheet names are matched ignoring case
+// the way cell addresses are.
+//
+// Each sheet still keeps its own graph and recomputes its formulas when
+// one of its cells changes, but a sheet on its own can't see the others,
+// so a reference to another sheet comes out as #REF! there. The workbook
+// keeps a second graph over the cells of every sheet and recomputes what
+// a change affects across all of them, which puts the right values back.
+//
+// Renaming a sheet rewrites the formulas naming it, deleting one turns
+// them into #REF!. Inserting or deleting rows moves references from other
+// sheets along, the same as the sheet's own.
+//
+// A workbook is saved as one file of CSV records, a header and then the
+// sheets in order, each cell with its type so nothing is guessed, and
+// then the formats, oldest first since a newer one wins where they
+// overlap:
+//
+//   workbook,1
+//   sheet,Sales
+//   A1,text,qty
+//   A2,int,12
+//   B2,formula,=A2*Rates!A1
+//   C2,date,2024-03-15
+//   format,B2:B40,"#,##0.00"
+//   format,C2,DD/MM/YYYY
+//   sheet,Rates
+//   A1,float,1.2
+use super::{
+    csv::{field, records, Field},
+    formula::{Context, Shift},
+    graph::{Graph, Node},
+    CellError, CellRef, Date, Error, Format, Formula, Money, Range, Sheet, SpreadsheetCell, Value,
+};
+use std::{collections::HashSet, fs};
+
+const VERSION: i64 = 1;
+
+// A sheet as it's read from a file: its name, cells and formats
+type SheetRecords = (
+    String,
+    Vec<(CellRef, SpreadsheetCell)>,
+    Vec<(Range, Format)>,
+);
+
+// A cell of one of the sheets, by the sheet's position in the workbook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+struct Address {
+    sheet: usize,
+    at: CellRef,
+}
+
+impl Node for Address {
+    type Range = (usize, Range);
+    type Column = (usize, usize);
+
+    fn within(self, (sheet, range): &(usize, Range)) -> bool {
+        self.sheet == *sheet && range.contains(self.at)
+    }
+
+    fn column(self) -> (usize, usize) {
+        (self.sheet, self.at.col)
+    }
+
+    fn columns((sheet, range): &(usize, Range)) -> impl Iterator<Item = (usize, usize)> {
+        (range.start.col..=range.end.col).map(move |col| (*sheet, col))
+    }
+}
+
+pub fn same_sheet(a: &str, b: &str) -> bool {
+    a.to_lowercase() == b.to_lowercase()
+}
+
+// How a sheet is named in a formula, in quotes unless it's a single word
+// that can't be mistaken for anything else
+pub fn quote_sheet_name(name: &str) -> String {
+    let mut chars = name.chars();
+    let word = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    match word {
+        true => name.to_owned(),
+        false => format!("'{name}'"),
+    }
+}
+
+// The quote and the '!' mark where a name ends in a formula, so they
+// can't be in one
+pub fn check_name(name: &str) -> Result<(), Error> {
+    let bad = name.trim().is_empty()
+        || name.trim() != name
+        || name.contains(|c: char| c == '!' || c == '\'' || c.is_control());
+    match bad {
+        true => Err(Error::BadSheetName(name.to_owned())),
+        false => Ok(()),
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Workbook {
+    sheets: Vec<(String, Sheet)>,
+    graph: Graph<Address>,
+}
+
+// Formulas being worked out for one of the workbook's sheets
+struct Within<'a> {
+    book: &'a Workbook,
+    sheet: usize,
+}
+
+impl Context for Within<'_> {
+    fn value(&self, at: CellRef) -> Value {
+        self.book.sheets[self.sheet].1.value(at)
+    }
+
+    fn filled(&self, range: Range) -> Vec<(CellRef, Value)> {
+        self.book.sheets[self.sheet].1.filled(range)
+    }
+
+    fn sheet_value(&self, sheet: &str, at: CellRef) -> Value {
+        match self.book.index(sheet) {
+            Some(i) => self.book.sheets[i].1.value(at),
+            None => Value::Error(CellError::Ref),
+        }
+    }
+
+    fn sheet_filled(&self, sheet: &str, range: Range) -> Vec<(CellRef, Value)> {
+        match self.book.index(sheet) {
+            Some(i) => self.book.sheets[i].1.filled(range),
+            None => vec![(range.start, Value::Error(CellError::Ref))],
+        }
+    }
+}
+
+impl Workbook {
+    pub fn new() -> Workbook {
+        Workbook::default()
+    }
+
+    // The sheets' names, in order
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.sheets.iter().map(|(name, _)| name.as_str())
+    }
+
+    pub fn sheets(&self) -> impl Iterator<Item = (&str, &Sheet)> {
+        self.sheets
+            .iter()
+            .map(|(name, sheet)| (name.as_str(), sheet))
+    }
+
+    // Put together a workbook read from a file, whose sheet names have
+    // already been checked
+    pub(super) fn from_sheets(sheets: Vec<(String, Sheet)>) -> Workbook {
+        let mut book = Workbook {
+            sheets,
+            graph: Graph::new(),
+        };
+        book.rebuild();
+        book
+    }
+
+    fn index(&self, name: &str) -> Option<usize> {
+        self.sheets.iter().position(|(n, _)| same_sheet(n, name))
+    }
+
+    fn find(&self, name: &str) -> Result<usize, Error> {
+        self.index(name)
+            .ok_or_else(|| Error::NoSuchSheet(name.to_owned()))
+    }
+
+    pub fn sheet(&self, name: &str) -> Result<&Sheet, Error> {
+        Ok(&self.sheets[self.find(name)?].1)
+    }
+
+    pub fn value(&self, sheet: &str, at: CellRef) -> Result<Value, Error> {
+        Ok(self.sheet(sheet)?.value(at))
+    }
+
+    // Add a sheet at the end. Formulas anywhere that were waiting for a
+    // sheet by this name start reading it.
+    pub fn add_sheet(&mut self, name: &str, sheet: Sheet) -> Result<(), Error> {
+        check_name(name)?;
+        if self.index(name).is_some() {
+            return Err(Error::SheetExists(name.to_owned()));
+        }
+        self.sheets.push((name.to_owned(), sheet));
+        self.rebuild();
+        Ok(())
+    }
+
+    pub fn rename_sheet(&mut self, from: &str, to: &str) -> Result<(), Error> {
+        let i = self.find(from)?;
+        check_name(to)?;
+        if self.index(to).is_some_and(|other| other != i) {
+            return Err(Error::SheetExists(to.to_owned()));
+        }
+        let old = std::mem::replace(&mut self.sheets[i].0, to.to_owned());
+        self.rewrite(|formula| formula.rename_sheet(&old, Some(to)));
+        self.rebuild();
+        Ok(())
+    }
+
+    // Take a sheet out of the workbook, references to it become #REF!
+    pub fn delete_sheet(&mut self, name: &str) -> Result<Sheet, Error> {
+        let i = self.find(name)?;
+        let (name, sheet) = self.sheets.remove(i);
+        self.rewrite(|formula| formula.rename_sheet(&name, None));
+        self.rebuild();
+        Ok(sheet)
+    }
+
+    // Set a cell on one of the sheets and hand back what was there
+    pub fn set(
+        &mut self,
+        sheet: &str,
+        at: CellRef,
+        cell: impl Into<SpreadsheetCell>,
+    ) -> Result<SpreadsheetCell, Error> {
+        let i = self.find(sheet)?;
+        let old = self.sheets[i].1.set(at, cell);
+        let address = Address { sheet: i, at };
+        match self.sheets[i].1.get(at) {
+            SpreadsheetCell::Formula(formula) => {
+                let (cells, ranges) = self.precedents(i, formula);
+                self.graph.set(address, cells, ranges);
+            }
+            _ => self.graph.remove(address),
+        }
+        self.recalculate(self.graph.affected(address));
+        Ok(old)
+    }
+
+    // Formats don't change any values, so there's nothing to recompute
+    pub fn set_format(
+        &mut self,
+        sheet: &str,
+        at: CellRef,
+        format: Format,
+    ) -> Result<Format, Error> {
+        let i = self.find(sheet)?;
+        Ok(self.sheets[i].1.set_format(at, format))
+    }
+
+    pub fn format_range(&mut self, sheet: &str, range: Range, format: Format) -> Result<(), Error> {
+        let i = self.find(sheet)?;
+        self.sheets[i].1.format_range(range, format);
+        Ok(())
+    }
+
+    pub fn insert_row(&mut self, sheet: &str, at: usize) -> Result<(), Error> {
+        self.reshape(sheet, Some(Shift::InsertRow(at)), |s| s.insert_row(at))
+    }
+
+    pub fn delete_row(
+        &mut self,
+        sheet: &str,
+        at: usize,
+    ) -> Result<Vec<(usize, SpreadsheetCell)>, Error> {
+        self.reshape(sheet, Some(Shift::DeleteRow(at)), |s| s.delete_row(at))
+    }
+
+    pub fn insert_col(&mut self, sheet: &str, at: usize) -> Result<(), Error> {
+        self.reshape(sheet, Some(Shift::InsertCol(at)), |s| s.insert_col(at))
+    }
+
+    pub fn delete_col(
+        &mut self,
+        sheet: &str,
+        at: usize,
+    ) -> Result<Vec<(usize, SpreadsheetCell)>, Error> {
+        self.reshape(sheet, Some(Shift::DeleteCol(at)), |s| s.delete_col(at))
+    }
+
+    // References from other sheets into the range stay where they are,
+    // they read whatever is sorted into their cells
+    pub fn sort_rows(
+        &mut self,
+        sheet: &str,
+        range: Range,
+        by: usize,
+        descending: bool,
+    ) -> Result<(), Error> {
+        self.reshape(sheet, None, |s| s.sort_rows(range, by, descending))
+    }
+
+    // Change the layout of a sheet, point the references to it from every
+    // sheet at where its cells went and recompute the lot
+    fn reshape<T>(
+        &mut self,
+        sheet: &str,
+        shift: Option<Shift>,
+        change: impl FnOnce(&mut Sheet) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let i = self.find(sheet)?;
+        let changed = change(&mut self.sheets[i].1)?;
+        if let Some(shift) = shift {
+            let name = self.sheets[i].0.clone();
+            self.rewrite(|formula| formula.shift_sheet(&name, shift));
+        }
+        self.rebuild();
+        Ok(changed)
+    }
+
+    fn rewrite(&mut self, mut change: impl FnMut(&mut Formula)) {
+        for (_, sheet) in &mut self.sheets {
+            sheet.formulas_mut().for_each(&mut change);
+        }
+    }
+
+    // What a formula on sheet `sheet` reads, as cells of the workbook.
+    // References to sheets that don't exist don't read anything.
+    fn precedents(&self, sheet: usize, formula: &Formula) -> (Vec<Address>, Vec<(usize, Range)>) {
+        let (cells, ranges) = formula.references();
+        let (other_cells, other_ranges) = formula.sheet_references();
+        let mut cells: Vec<Address> = cells.into_iter().map(|at| Address { sheet, at }).collect();
+        let mut ranges: Vec<(usize, Range)> =
+            ranges.into_iter().map(|range| (sheet, range)).collect();
+        cells.extend(
+            other_cells
+                .into_iter()
+                .filter_map(|(name, at)| self.index(name).map(|sheet| Address { sheet, at })),
+        );
+        ranges.extend(
+            other_ranges
+                .into_iter()
+                .filter_map(|(name, range)| self.index(name).map(|sheet| (sheet, range))),
+        );
+        (cells, ranges)
+    }
+
+    // After sheets come and go (or are renamed, or change shape) the
+    // graph is built again and every formula recomputed
+    fn rebuild(&mut self) {
+        let mut links = Vec::new();
+        for (i, (_, sheet)) in self.sheets.iter().enumerate() {
+            for (at, formula) in sheet.formulas() {
+                let (cells, ranges) = self.precedents(i, formula);
+                links.push((Address { sheet: i, at }, cells, ranges));
+            }
+        }
+        self.graph.clear();
+        for (address, cells, ranges) in links {
+            self.graph.set(address, cells, ranges);
+        }
+        self.recalculate(self.graph.formulas().collect());
+    }
+
+    fn recalculate(&mut self, formulas: HashSet<Address>) {
+        let (order, cyclic) = self.graph.order(&formulas);
+        for address in cyclic {
+            let sheet = &mut self.sheets[address.sheet].1;
+            sheet.set_value(address.at, Value::Error(CellError::Cycle));
+        }
+        for address in order {
+            let sheet = &self.sheets[address.sheet].1;
+            if let SpreadsheetCell::Formula(formula) = sheet.get(address.at) {
+                let within = Within {
+                    book: self,
+                    sheet: address.sheet,
+                };
+                let value = formula.evaluate(&within);
+                self.sheets[address.sheet].1.set_value(address.at, value);
+            }
+        }
+    }
+
+    pub fn to_text(&self) -> String {
+        // quoted only where CSV needs it, the type says what the text is
+        let record = |fields: &[&SpreadsheetCell]| {
+            let fields: Vec<String> = fields
+                .iter()
+                .map(|cell| field(cell, &Default::default()))
+                .collect();
+            fields.join(",") + "\r\n"
+        };
+        let text = |text: &str| SpreadsheetCell::from(text);
+        let mut out = record(&[&text("workbook"), &SpreadsheetCell::Int(VERSION)]);
+        for (name, sheet) in &self.sheets {
+            out.push_str(&record(&[&text("sheet"), &text(name)]));
+            for (at, cell) in sheet.cells() {
+                let (at, kind) = (text(&at.to_string()), text(cell.type_name()));
+                out.push_str(&record(&[&at, &kind, cell]));
+            }
+            for (range, format) in sheet.formats() {
+                let range = match range.start == range.end {
+                    true => range.start.to_string(),
+                    false => range.to_string(),
+                };
+                let (range, format) = (text(&range), text(&format.to_string()));
+                out.push_str(&record(&[&text("format"), &range, &format]));
+            }
+        }
+        out
+    }
+
+    pub fn from_text(text: &str) -> Result<Workbook, Error> {
+        let mut records = records(text, ',')?.into_iter();
+        let error = |line: usize, message: String| Error::Csv { line, message };
+        let texts = |fields: Vec<Field>| -> Vec<String> {
+            fields.into_iter().map(|field| field.text).collect()
+        };
+        match records.next().map(|(line, fields)| (line, texts(fields))) {
+            Some((_, header))
+                if header.len() == 2
+                    && header[0] == "workbook"
+                    && header[1].parse() == Ok(VERSION) => {}
+            Some((line, header)) if header.first().is_some_and(|tag| tag == "workbook") => {
+                let version = header.get(1).cloned().unwrap_or_default();
+                return Err(error(
+                    line,
+                    format!(
+                        "this is a version {version} workbook, only version {VERSION} can be read"
+                    ),
+                ));
+            }
+            _ => return Err(error(1, String::from("this isn't a workbook file"))),
+        }
+
+        let mut sheets: Vec<SheetRecords> = Vec::new();
+        for (line, fields) in records {
+            let fields = texts(fields);
+            match fields.as_slice() {
+                [tag, name] if tag == "sheet" => {
+                    check_name(name).map_err(|err| error(line, err.to_string()))?;
+                    if sheets.iter().any(|(other, _, _)| same_sheet(other, name)) {
+                        return Err(error(line, Error::SheetExists(name.clone()).to_string()));
+                    }
+                    sheets.push((name.clone(), Vec::new(), Vec::new()));
+                }
+                [tag, range, format] if tag == "format" => {
+                    let range: Range = range
+                        .parse()
+                        .map_err(|err: Error| error(line, err.to_string()))?;
+                    let format: Format = format
+                        .parse()
+                        .map_err(|err: Error| error(line, err.to_string()))?;
+                    let (_, _, formats) = sheets
+                        .last_mut()
+                        .ok_or_else(|| error(line, String::from("a format before any sheet")))?;
+                    formats.push((range, format));
+                }
+                [at, kind, text] => {
+                    let at: CellRef = at
+                        .parse()
+                        .map_err(|err: Error| error(line, err.to_string()))?;
+                    let bad = || error(line, format!("'{text}' isn't a {kind} cell"));
+                    let cell = match kind.as_str() {
+                        "int" => SpreadsheetCell::Int(text.parse().map_err(|_| bad())?),
+                        "float" => SpreadsheetCell::Float(text.parse().map_err(|_| bad())?),
+                        "text" => SpreadsheetCell::Text(text.clone()),
+                        "formula" => SpreadsheetCell::formula(text).map_err(|_| bad())?,
+                        "bool" if text == "TRUE" || text == "FALSE" => {
+                            SpreadsheetCell::Bool(text == "TRUE")
+                        }
+                        "bool" => return Err(bad()),
+                        "date" => SpreadsheetCell::Date(text.parse::<Date>().map_err(|_| bad())?),
+                        "currency" => {
+                            SpreadsheetCell::Currency(text.parse::<Money>().map_err(|_| bad())?)
+                        }
+                        _ => return Err(error(line, format!("unknown cell type '{kind}'"))),
+                    };
+                    let (_, cells, _) = sheets
+                        .last_mut()
+                        .ok_or_else(|| error(line, String::from("a cell before any sheet")))?;
+                    cells.push((at, cell));
+                }
+                _ => return Err(error(line, String::from("expected a sheet or a cell"))),
+            }
+        }
+
+        let sheets = sheets.into_iter().map(|(name, cells, formats)| {
+            let mut sheet = Sheet::new();
+            sheet.set_many(cells);
+            sheet.set_formats(formats);
+            (name, sheet)
+        });
+        Ok(Workbook::from_sheets(sheets.collect()))
+    }
+
+    pub fn load(path: &str) -> Result<Workbook, Error> {
+        let text = fs::read_to_string(path).map_err(|err| Error::Io(err.to_string()))?;
+        Workbook::from_text(&text)
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Error> {
+        fs::write(path, self.to_text()).map_err(|err| Error::Io(err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(address: &str) -> CellRef {
+        address.parse().unwrap()
+    }
+
+    fn source(workbook: &Workbook, sheet: &str, address: &str) -> String {
+        match workbook.sheet(sheet).unwrap().get(at(address)) {
+            SpreadsheetCell::Formula(formula) => formula.source().to_owned(),
+            other => panic!("{sheet}!{address} holds {other:?}"),
+        }
+    }
+
+    fn workbook() -> Workbook {
+        let mut workbook = Workbook::new();
+        workbook.add_sheet("Sales", Sheet::new()).unwrap();
+        workbook.add_sheet("Rates", Sheet::new()).unwrap();
+        let cell = |text: &str| text.parse::<SpreadsheetCell>().unwrap();
+        workbook.set("Rates", at("A1"), cell("1.5")).unwrap();
+        workbook.set("Sales", at("A2"), cell("12")).unwrap();
+        workbook
+            .set("Sales", at("B2"), cell("=A2*Rates!A1"))
+            .unwrap();
+        workbook
+            .set("Sales", at("C2"), cell("=SUM(rates!A1:A3)"))
+            .unwrap();
+        workbook
+            .set("Rates", at("B1"), cell("=Sales!B2+A1"))
+            .unwrap();
+        workbook
+    }
+
+    #[test]
+    fn values_cross_sheets() {
+        let mut workbook = workbook();
+        assert_eq!(workbook.value("Sales", at("B2")), Ok(Value::Float(18.0)));
+        assert_eq!(workbook.value("rates", at("B1")), Ok(Value::Float(19.5)));
+        workbook
+            .set("Rates", at("A1"), SpreadsheetCell::Int(2))
+            .unwrap();
+        assert_eq!(workbook.value("Sales", at("B2")), Ok(Value::Int(24)));
+        assert_eq!(workbook.value("Rates", at("B1")), Ok(Value::Int(26)));
+    }
+
+    #[test]
+    fn renaming_rewrites_references() {
+        let mut workbook = workbook();
+        workbook.rename_sheet("rates", "Tax Rates").unwrap();
+        assert_eq!(source(&workbook, "Sales", "B2"), "=A2*'Tax Rates'!A1");
+        assert_eq!(source(&workbook, "Sales", "C2"), "=SUM('Tax Rates'!A1:A3)");
+        assert_eq!(workbook.value("Sales", at("B2")), Ok(Value::Float(18.0)));
+        assert_eq!(
+            workbook.value("Tax Rates", at("B1")),
+            Ok(Value::Float(19.5))
+        );
+
+        assert_eq!(
+            workbook.rename_sheet("Sales", "tax rates"),
+            Err(Error::SheetExists(String::from("tax rates")))
+        );
+        assert!(workbook.rename_sheet("Sales", "Bad!Name").is_err());
+        // changing only the case is fine
+        workbook.rename_sheet("Sales", "SALES").unwrap();
+        assert_eq!(source(&workbook, "Tax Rates", "B1"), "=SALES!B2+A1");
+    }
+
+    #[test]
+    fn deleting_leaves_ref_errors() {
+        let mut workbook = workbook();
+        workbook.delete_sheet("Rates").unwrap();
+        assert_eq!(source(&workbook, "Sales", "B2"), "=A2*#REF!");
+        assert_eq!(
+            workbook.value("Sales", at("B2")),
+            Ok(Value::Error(CellError::Ref))
+        );
+        // a new sheet by the old name doesn't bring the references back
+        workbook.add_sheet("Rates", Sheet::new()).unwrap();
+        assert_eq!(
+            workbook.value("Sales", at("B2")),
+            Ok(Value::Error(CellError::Ref))
+        );
+        assert_eq!(
+            workbook.delete_sheet("Nope"),
+            Err(Error::NoSuchSheet(String::from("Nope")))
+        );
+    }
+
+    #[test]
+    fn text_round_trip() {
+        let mut workbook = workbook();
+        let money: Format = "#,##0.00".parse().unwrap();
+        let column = "B1:B1048576".parse().unwrap();
+        workbook
+            .format_range("Sales", column, money.clone())
+            .unwrap();
+        workbook
+            .set_format("Sales", at("B2"), Format::General)
+            .unwrap();
+        let text = workbook.to_text();
+        assert!(text.contains("format,B1:B1048576,\"#,##0.00\""));
+        assert!(text.contains("format,B2,General"));
+        let read = Workbook::from_text(&text).unwrap();
+        assert_eq!(read.to_text(), text);
+        let sales = read.sheet("Sales").unwrap();
+        assert_eq!(sales.format(at("B3")), &money);
+        assert_eq!(sales.format(at("B2")), &Format::General);
+        assert_eq!(read.value("Rates", at("B1")), Ok(Value::Float(19.5)));
+
+        assert!(text.starts_with("workbook,1\r\n"));
+        let newer = text.replacen("workbook,1", "workbook,2", 1);
+        assert_eq!(
+            Workbook::from_text(&newer).unwrap_err().to_string(),
+            "line 1: this is a version 2 workbook, only version 1 can be read"
+        );
+    }
+}
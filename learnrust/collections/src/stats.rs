@@ -0,0 +1,201 @@
+// The Book's chapter 8 exercise: given a list of integers, use a vector to
+// return the median (the middle value when sorted) and the mode (the value
+// that occurs most often, a hash map helps here), plus the other usual
+// summary statistics.
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, PartialEq)]
+pub enum Error {
+    Empty,
+    NotANumber { line: usize, token: String },
+    BadPercentile(f64),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Empty => write!(f, "no numbers to summarize"),
+            Error::NotANumber { line, token } => {
+                write!(f, "line {line}: '{token}' is not an integer")
+            }
+            Error::BadPercentile(p) => write!(f, "percentile {p} is outside of 0-100"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// Integers separated by whitespace or commas, over any number of lines
+pub fn parse(input: &str) -> Result<Vec<i64>, Error> {
+    let mut numbers = Vec::new();
+    for (i, line) in input.lines().enumerate() {
+        for token in line.split(|c: char| c.is_whitespace() || c == ',') {
+            if token.is_empty() {
+                continue;
+            }
+            let n = token.parse().map_err(|_| Error::NotANumber {
+                line: i + 1,
+                token: token.to_owned(),
+            })?;
+            numbers.push(n);
+        }
+    }
+    Ok(numbers)
+}
+
+pub fn mean(numbers: &[i64]) -> Result<f64, Error> {
+    if numbers.is_empty() {
+        return Err(Error::Empty);
+    }
+    // summed as i128 so a long list of big numbers can't overflow
+    let sum: i128 = numbers.iter().map(|&n| n as i128).sum();
+    Ok(sum as f64 / numbers.len() as f64)
+}
+
+fn sorted(numbers: &[i64]) -> Vec<i64> {
+    let mut sorted = numbers.to_vec();
+    sorted.sort_unstable();
+    sorted
+}
+
+// With an even count there's no single middle, so it's the mean of the two
+pub fn median(numbers: &[i64]) -> Result<f64, Error> {
+    percentile(numbers, 50.0)
+}
+
+// Every value that shares the highest count, in ascending order
+pub fn modes(numbers: &[i64]) -> Result<Vec<i64>, Error> {
+    let mut counts = HashMap::new();
+    for n in numbers {
+        *counts.entry(*n).or_insert(0) += 1;
+    }
+    let highest = counts.values().copied().max().ok_or(Error::Empty)?;
+    let mut modes: Vec<i64> = counts
+        .into_iter()
+        .filter(|(_, count)| *count == highest)
+        .map(|(n, _)| n)
+        .collect();
+    modes.sort_unstable();
+    Ok(modes)
+}
+
+// Population variance: the mean squared distance from the mean
+pub fn variance(numbers: &[i64]) -> Result<f64, Error> {
+    let mean = mean(numbers)?;
+    let squares: f64 = numbers.iter().map(|&n| (n as f64 - mean).powi(2)).sum();
+    Ok(squares / numbers.len() as f64)
+}
+
+pub fn std_dev(numbers: &[i64]) -> Result<f64, Error> {
+    variance(numbers).map(f64::sqrt)
+}
+
+// The value below which p percent of the numbers fall, interpolating
+// linearly between the two closest ranks
+pub fn percentile(numbers: &[i64], p: f64) -> Result<f64, Error> {
+    if !(0.0..=100.0).contains(&p) {
+        return Err(Error::BadPercentile(p));
+    }
+    if numbers.is_empty() {
+        return Err(Error::Empty);
+    }
+    let sorted = sorted(numbers);
+    let rank = p / 100.0 * (sorted.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    let fraction = rank - lower as f64;
+    // the gap is taken in i128, i64::MAX - i64::MIN doesn't fit in an i64
+    let gap = sorted[upper] as i128 - sorted[lower] as i128;
+    Ok(sorted[lower] as f64 + gap as f64 * fraction)
+}
+
+#[derive(Debug)]
+pub struct Summary {
+    pub count: usize,
+    pub min: i64,
+    pub max: i64,
+    pub mean: f64,
+    pub median: f64,
+    pub modes: Vec<i64>,
+    pub variance: f64,
+    pub std_dev: f64,
+    pub percentiles: Vec<(f64, f64)>,
+}
+
+pub fn summarize(numbers: &[i64], percentiles: &[f64]) -> Result<Summary, Error> {
+    let sorted = sorted(numbers);
+    let (Some(&min), Some(&max)) = (sorted.first(), sorted.last()) else {
+        return Err(Error::Empty);
+    };
+    Ok(Summary {
+        count: numbers.len(),
+        min,
+        max,
+        mean: mean(numbers)?,
+        median: median(&sorted)?,
+        modes: modes(numbers)?,
+        variance: variance(numbers)?,
+        std_dev: std_dev(numbers)?,
+        percentiles: percentiles
+            .iter()
+            .map(|&p| percentile(&sorted, p).map(|value| (p, value)))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let modes: Vec<String> = self.modes.iter().map(|n| n.to_string()).collect();
+        writeln!(f, "count     {}", self.count)?;
+        writeln!(f, "min       {}", self.min)?;
+        writeln!(f, "max       {}", self.max)?;
+        writeln!(f, "mean      {:.4}", self.mean)?;
+        writeln!(f, "median    {}", self.median)?;
+        writeln!(f, "mode(s)   {}", modes.join(", "))?;
+        writeln!(f, "variance  {:.4}", self.variance)?;
+        write!(f, "std dev   {:.4}", self.std_dev)?;
+        for (p, value) in &self.percentiles {
+            write!(f, "\np{p:<8} {value}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentiles_interpolate() {
+        let numbers = [15, 20, 35, 40, 50];
+        assert_eq!(percentile(&numbers, 0.0), Ok(15.0));
+        assert_eq!(percentile(&numbers, 40.0), Ok(29.0));
+        assert_eq!(percentile(&numbers, 100.0), Ok(50.0));
+        assert_eq!(median(&[4, 1, 3, 2]), Ok(2.5));
+        assert_eq!(
+            percentile(&numbers, 101.0),
+            Err(Error::BadPercentile(101.0))
+        );
+        assert_eq!(percentile(&[], 50.0), Err(Error::Empty));
+    }
+
+    #[test]
+    fn extremes_dont_overflow() {
+        assert_eq!(percentile(&[i64::MIN, i64::MAX], 50.0), Ok(0.0));
+        assert_eq!(median(&[i64::MAX, i64::MAX]), Ok(i64::MAX as f64));
+        assert_eq!(mean(&[i64::MAX, i64::MAX]), Ok(i64::MAX as f64));
+        assert!(summarize(&[i64::MIN, 0, i64::MAX], &[25.0, 75.0]).is_ok());
+    }
+
+    #[test]
+    fn modes_and_parsing() {
+        assert_eq!(parse("1, 2\n2 3"), Ok(vec![1, 2, 2, 3]));
+        assert_eq!(
+            parse("1\n2 x"),
+            Err(Error::NotANumber {
+                line: 2,
+                token: String::from("x")
+            })
+        );
+        assert_eq!(modes(&[3, 1, 3, 1, 2]), Ok(vec![1, 3]));
+    }
+}
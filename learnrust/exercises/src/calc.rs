@@ -0,0 +1,376 @@
+// A calculator for lines like '2 * (3 + x) ^ 2' or 'let x = sqrt(16)'.
+// The line is split into tokens, parsed into an expression tree by
+// recursive descent (one function per precedence level) and evaluated.
+// Every token remembers its column so errors can point at the culprit.
+use crate::fibonacci::fibonacci;
+use std::{collections::HashMap, fmt};
+
+#[derive(Debug, PartialEq)]
+pub struct Error {
+    pub column: usize, // <-- 1-based, counted in chars
+    pub message: String,
+}
+
+impl Error {
+    fn new(column: usize, message: impl Into<String>) -> Error {
+        Error {
+            column,
+            message: message.into(),
+        }
+    }
+
+    // A '^' under the offending column, for printing below the input
+    pub fn pointer(&self, indent: usize) -> String {
+        format!("{}^", " ".repeat(indent + self.column - 1))
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "column {}: {}", self.column, self.message)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char), // <-- + - * / % ^ ( ) , =
+    End,
+}
+
+fn tokenize(line: &str) -> Result<Vec<(Token, usize)>, Error> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let column = i + 1;
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                i += 1;
+            }
+            let text: String = chars[start..i].iter().collect();
+            let number = text
+                .parse()
+                .map_err(|_| Error::new(column, format!("'{text}' is not a number")))?;
+            tokens.push((Token::Number(number), column));
+        } else if c.is_alphabetic() || c == '_' {
+            let start = i;
+            while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                i += 1;
+            }
+            tokens.push((Token::Ident(chars[start..i].iter().collect()), column));
+        } else if "+-*/%^(),=".contains(c) {
+            tokens.push((Token::Op(c), column));
+            i += 1;
+        } else {
+            return Err(Error::new(column, format!("Unexpected character '{c}'")));
+        }
+    }
+    tokens.push((Token::End, chars.len() + 1));
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    Number(f64),
+    Variable(String, usize),
+    Negate(Box<Expr>),
+    Binary(char, Box<Expr>, Box<Expr>, usize),
+    Call(String, Vec<Expr>, usize),
+}
+
+enum Statement {
+    Let(String, Expr),
+    Expr(Expr),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn column(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> (Token, usize) {
+        let token = self.tokens[self.pos].clone();
+        if token.0 != Token::End {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn unexpected(&self) -> Error {
+        match self.peek() {
+            Token::End => Error::new(self.column(), "Unexpected end of input"),
+            Token::Number(n) => Error::new(self.column(), format!("Unexpected number {n}")),
+            Token::Ident(name) => Error::new(self.column(), format!("Unexpected '{name}'")),
+            Token::Op(op) => Error::new(self.column(), format!("Unexpected '{op}'")),
+        }
+    }
+
+    fn expect(&mut self, op: char) -> Result<(), Error> {
+        if *self.peek() == Token::Op(op) {
+            self.next();
+            Ok(())
+        } else {
+            Err(Error::new(self.column(), format!("Expected '{op}'")))
+        }
+    }
+
+    fn statement(&mut self) -> Result<Statement, Error> {
+        let statement = match self.peek() {
+            Token::Ident(word) if word == "let" => {
+                self.next();
+                let name = match self.next() {
+                    (Token::Ident(name), _) => name,
+                    (_, column) => return Err(Error::new(column, "Expected a variable name")),
+                };
+                self.expect('=')?;
+                Statement::Let(name, self.expr()?)
+            }
+            _ => Statement::Expr(self.expr()?),
+        };
+        match self.peek() {
+            Token::End => Ok(statement),
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    // expr := term (('+' | '-') term)*
+    fn expr(&mut self) -> Result<Expr, Error> {
+        let mut left = self.term()?;
+        while let Token::Op(op @ ('+' | '-')) = *self.peek() {
+            let (_, column) = self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.term()?), column);
+        }
+        Ok(left)
+    }
+
+    // term := unary (('*' | '/' | '%') unary)*
+    fn term(&mut self) -> Result<Expr, Error> {
+        let mut left = self.unary()?;
+        while let Token::Op(op @ ('*' | '/' | '%')) = *self.peek() {
+            let (_, column) = self.next();
+            left = Expr::Binary(op, Box::new(left), Box::new(self.unary()?), column);
+        }
+        Ok(left)
+    }
+
+    // unary := '-' unary | power
+    fn unary(&mut self) -> Result<Expr, Error> {
+        if *self.peek() == Token::Op('-') {
+            self.next();
+            return Ok(Expr::Negate(Box::new(self.unary()?)));
+        }
+        self.power()
+    }
+
+    // power := primary ('^' unary)?, so 2^3^2 is 2^(3^2) and -2^2 is -(2^2)
+    fn power(&mut self) -> Result<Expr, Error> {
+        let base = self.primary()?;
+        if *self.peek() == Token::Op('^') {
+            let (_, column) = self.next();
+            let exponent = self.unary()?;
+            return Ok(Expr::Binary(
+                '^',
+                Box::new(base),
+                Box::new(exponent),
+                column,
+            ));
+        }
+        Ok(base)
+    }
+
+    // primary := number | name | name '(' args ')' | '(' expr ')'
+    fn primary(&mut self) -> Result<Expr, Error> {
+        match self.peek().clone() {
+            Token::Number(n) => {
+                self.next();
+                Ok(Expr::Number(n))
+            }
+            Token::Ident(name) => {
+                let (_, column) = self.next();
+                if *self.peek() != Token::Op('(') {
+                    return Ok(Expr::Variable(name, column));
+                }
+                self.next();
+                let mut args = Vec::new();
+                if *self.peek() != Token::Op(')') {
+                    args.push(self.expr()?);
+                    while *self.peek() == Token::Op(',') {
+                        self.next();
+                        args.push(self.expr()?);
+                    }
+                }
+                self.expect(')')?;
+                Ok(Expr::Call(name, args, column))
+            }
+            Token::Op('(') => {
+                self.next();
+                let inner = self.expr()?;
+                self.expect(')')?;
+                Ok(inner)
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+}
+
+pub const FUNCTIONS: [&str; 8] = ["sqrt", "abs", "fib", "ln", "exp", "floor", "round", "max"];
+
+fn call(name: &str, args: &[f64], column: usize) -> Result<f64, Error> {
+    let arity = |n: usize| {
+        if args.len() == n {
+            Ok(())
+        } else {
+            Err(Error::new(
+                column,
+                format!("{name} takes {n} argument(s), got {}", args.len()),
+            ))
+        }
+    };
+    match name {
+        "sqrt" => {
+            arity(1)?;
+            if args[0] < 0.0 {
+                return Err(Error::new(column, "sqrt of a negative number"));
+            }
+            Ok(args[0].sqrt())
+        }
+        "abs" => arity(1).map(|_| args[0].abs()),
+        "ln" => {
+            arity(1)?;
+            if args[0] <= 0.0 {
+                return Err(Error::new(column, "ln needs a positive number"));
+            }
+            Ok(args[0].ln())
+        }
+        "exp" => arity(1).map(|_| args[0].exp()),
+        "floor" => arity(1).map(|_| args[0].floor()),
+        "round" => arity(1).map(|_| args[0].round()),
+        "max" => {
+            if args.is_empty() {
+                return Err(Error::new(column, "max needs at least one argument"));
+            }
+            Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max))
+        }
+        "fib" => {
+            arity(1)?;
+            let n = args[0];
+            if n < 0.0 || n.fract() != 0.0 {
+                return Err(Error::new(column, "fib needs a whole number >= 0"));
+            }
+            fibonacci(n as u32)
+                .map(|result| result as f64)
+                .ok_or_else(|| Error::new(column, format!("fib({n}) doesn't fit in a u64")))
+        }
+        _ => Err(Error::new(column, format!("Unknown function '{name}'"))),
+    }
+}
+
+#[derive(Default)]
+pub struct Calculator {
+    variables: HashMap<String, f64>,
+    history: Vec<(String, f64)>,
+}
+
+impl Calculator {
+    pub fn new() -> Calculator {
+        Calculator::default()
+    }
+
+    fn eval(&self, expr: &Expr) -> Result<f64, Error> {
+        match expr {
+            Expr::Number(n) => Ok(*n),
+            Expr::Variable(name, column) => self
+                .variables
+                .get(name)
+                .copied()
+                .ok_or_else(|| Error::new(*column, format!("Unknown variable '{name}'"))),
+            Expr::Negate(inner) => Ok(-self.eval(inner)?),
+            Expr::Binary(op, left, right, column) => {
+                let (left, right) = (self.eval(left)?, self.eval(right)?);
+                match op {
+                    '+' => Ok(left + right),
+                    '-' => Ok(left - right),
+                    '*' => Ok(left * right),
+                    '/' | '%' if right == 0.0 => Err(Error::new(*column, "Division by zero")),
+                    '/' => Ok(left / right),
+                    '%' => Ok(left % right),
+                    '^' => Ok(left.powf(right)),
+                    _ => unreachable!("the parser only builds known operators"),
+                }
+            }
+            Expr::Call(name, args, column) => {
+                let args = args
+                    .iter()
+                    .map(|arg| self.eval(arg))
+                    .collect::<Result<Vec<f64>, Error>>()?;
+                call(name, &args, *column)
+            }
+        }
+    }
+
+    // Evaluate one line, remembering it in the history and as 'ans'
+    pub fn eval_line(&mut self, line: &str) -> Result<f64, Error> {
+        let mut parser = Parser {
+            tokens: tokenize(line)?,
+            pos: 0,
+        };
+        let value = match parser.statement()? {
+            Statement::Let(name, expr) => {
+                let value = self.eval(&expr)?;
+                self.variables.insert(name, value);
+                value
+            }
+            Statement::Expr(expr) => self.eval(&expr)?,
+        };
+        self.variables.insert(String::from("ans"), value);
+        self.history.push((line.trim().to_owned(), value));
+        Ok(value)
+    }
+
+    pub fn history(&self) -> &[(String, f64)] {
+        &self.history
+    }
+
+    pub fn variables(&self) -> Vec<(&String, &f64)> {
+        let mut variables: Vec<_> = self.variables.iter().collect();
+        variables.sort_by(|a, b| a.0.cmp(b.0));
+        variables
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn columns_count_leading_spaces() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.eval_line("1 + * 2").unwrap_err().column, 5);
+        let err = calc.eval_line("    1 + * 2").unwrap_err();
+        assert_eq!(err.column, 9);
+        assert_eq!(err.pointer(6), format!("{}^", " ".repeat(14)));
+        assert_eq!(calc.eval_line("  x").unwrap_err().column, 3);
+    }
+
+    #[test]
+    fn history_is_trimmed() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.eval_line("  let x = 2 ^ 3  "), Ok(8.0));
+        assert_eq!(calc.history(), &[(String::from("let x = 2 ^ 3"), 8.0)]);
+    }
+}
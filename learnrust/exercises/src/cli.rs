@@ -0,0 +1,342 @@
+// Every exercise can also be run straight from the command line:
+//
+//   exercises temp 30C --to F
+//   exercises fib 90
+//   exercises carol --days 12
+//   exercises song bottles --verses 98-100 --format markdown
+//   exercises --batch commands.txt --json
+//
+// A batch file holds one command per line ('#' starts a comment). Results
+// are printed as text or, with --json, as one JSON object per command.
+use crate::{fibonacci::fibonacci, song, temperature};
+use std::{fs, process::ExitCode};
+
+pub const USAGE: &str = "\
+Usage: exercises [--json] <command> [args]
+       exercises [--json] --batch <file>
+
+Commands:
+  temp <reading> [--to C|F|K]     Convert a temperature like 30C or 86F
+  fib <n>                         The nth fibonacci number (n <= 93)
+  carol [--days N] [--format plain|markdown]
+                                  The Twelve Days of Christmas up to day N
+  song <name> [--verses A-B] [--format plain|markdown]
+                                  Sing one of: carol, jack, macdonald, bottles
+  help                            Show this message
+
+Without a command the interactive menu is started.";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Output {
+    Text,
+    Json,
+}
+
+pub enum Value {
+    Number(String), // <-- already formatted, so u64 doesn't go through a float
+    Text(String),
+}
+
+// The result of one command, as readable text and as named fields for JSON
+pub struct Report {
+    pub command: &'static str,
+    pub fields: Vec<(&'static str, Value)>,
+    pub text: String,
+}
+
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_object(fields: &[(&str, &Value)]) -> String {
+    let fields: Vec<String> = fields
+        .iter()
+        .map(|(key, value)| {
+            let value = match value {
+                Value::Number(n) => n.clone(),
+                Value::Text(s) => json_string(s),
+            };
+            format!("{}:{value}", json_string(key))
+        })
+        .collect();
+    format!("{{{}}}", fields.join(","))
+}
+
+impl Report {
+    pub fn render(&self, output: Output) -> String {
+        match output {
+            Output::Text => self.text.clone(),
+            Output::Json => {
+                let command = Value::Text(self.command.to_owned());
+                let mut fields = vec![("command", &command)];
+                fields.extend(self.fields.iter().map(|(key, value)| (*key, value)));
+                json_object(&fields)
+            }
+        }
+    }
+}
+
+fn render_error(err: &str, line: Option<usize>, output: Output) -> String {
+    match (output, line) {
+        (Output::Text, Some(line)) => format!("error: line {line}: {err}"),
+        (Output::Text, None) => format!("error: {err}"),
+        (Output::Json, Some(line)) => {
+            let line = Value::Number(line.to_string());
+            let err = Value::Text(err.to_owned());
+            json_object(&[("line", &line), ("error", &err)])
+        }
+        (Output::Json, None) => json_object(&[("error", &Value::Text(err.to_owned()))]),
+    }
+}
+
+// Positional arguments and '--name value' options of one command
+struct Args<'a> {
+    positional: Vec<&'a str>,
+    options: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Args<'a> {
+    fn parse(args: &[&'a str], allowed: &[&str]) -> Result<Args<'a>, String> {
+        let mut parsed = Args {
+            positional: Vec::new(),
+            options: Vec::new(),
+        };
+        let mut args = args.iter();
+        while let Some(&arg) = args.next() {
+            match arg.strip_prefix("--") {
+                Some(name) if allowed.contains(&name) => {
+                    let value = args
+                        .next()
+                        .ok_or_else(|| format!("--{name} needs a value"))?;
+                    parsed.options.push((name, value));
+                }
+                Some(name) => return Err(format!("Unknown option --{name}")),
+                None => parsed.positional.push(arg),
+            }
+        }
+        Ok(parsed)
+    }
+
+    fn option(&self, name: &str) -> Option<&'a str> {
+        self.options
+            .iter()
+            .rev() // <-- the last one given wins
+            .find(|(option, _)| *option == name)
+            .map(|(_, value)| *value)
+    }
+
+    fn expect_positional(&self, count: usize, usage: &str) -> Result<(), String> {
+        if self.positional.len() == count {
+            Ok(())
+        } else {
+            Err(format!("Usage: {usage}"))
+        }
+    }
+}
+
+// "3" is just verse 3, "2-5" is verses 2 to 5
+fn parse_verses(verses: &str) -> Result<(usize, usize), String> {
+    let number = |n: &str| {
+        n.trim()
+            .parse::<usize>()
+            .map_err(|_| format!("'{verses}' is not a verse range like 1-3"))
+    };
+    match verses.split_once('-') {
+        Some((first, last)) => Ok((number(first)?, number(last)?)),
+        None => number(verses).map(|n| (n, n)),
+    }
+}
+
+fn temp(args: &[&str]) -> Result<Report, String> {
+    let args = Args::parse(args, &["to"])?;
+    args.expect_positional(1, "temp <reading> [--to C|F|K]")?;
+    let (value, from) = temperature::parse_reading(args.positional[0])?;
+    let to = match args.option("to") {
+        Some(to) => to.parse()?,
+        None => from.other(),
+    };
+    let converted = temperature::convert(value, from, to);
+    if !converted.is_finite() {
+        let reading = args.positional[0];
+        return Err(format!("{reading} is too far out to convert to {to}"));
+    }
+    Ok(Report {
+        command: "temp",
+        fields: vec![
+            ("from", Value::Text(from.to_string())),
+            ("value", Value::Number(value.to_string())),
+            ("to", Value::Text(to.to_string())),
+            ("result", Value::Number(converted.to_string())),
+        ],
+        text: format!("{value}{from} is {converted}{to}"),
+    })
+}
+
+fn fib(args: &[&str]) -> Result<Report, String> {
+    let args = Args::parse(args, &[])?;
+    args.expect_positional(1, "fib <n>")?;
+    let n: u32 = args.positional[0]
+        .parse()
+        .map_err(|_| format!("'{}' is not a fibonacci index", args.positional[0]))?;
+    let result = fibonacci(n).ok_or_else(|| format!("fib({n}) doesn't fit in a u64"))?;
+    Ok(Report {
+        command: "fib",
+        fields: vec![
+            ("n", Value::Number(n.to_string())),
+            ("result", Value::Number(result.to_string())),
+        ],
+        text: format!("{n}th fibonacci is {result}"),
+    })
+}
+
+// `command` is the subcommand that asked, carol or song
+fn sing(
+    command: &'static str,
+    song: &song::Song,
+    verses: (usize, usize),
+    format: &str,
+) -> Result<Report, String> {
+    let format = match format {
+        "plain" | "text" => song::Format::Plain,
+        "markdown" | "md" => song::Format::Markdown,
+        _ => return Err(format!("Unknown format '{format}' (use plain or markdown)")),
+    };
+    let (first, last) = verses;
+    let lyrics = song.render(first..=last, format)?;
+    Ok(Report {
+        command,
+        fields: vec![
+            ("song", Value::Text(song.name.to_owned())),
+            ("first", Value::Number(first.to_string())),
+            ("last", Value::Number(last.to_string())),
+            ("lyrics", Value::Text(lyrics.clone())),
+        ],
+        text: lyrics.trim_end().to_owned(),
+    })
+}
+
+fn carol(args: &[&str]) -> Result<Report, String> {
+    let args = Args::parse(args, &["days", "format"])?;
+    args.expect_positional(0, "carol [--days N] [--format plain|markdown]")?;
+    let days = match args.option("days") {
+        Some(days) => days
+            .parse()
+            .map_err(|_| format!("'{days}' is not a number of days"))?,
+        None => song::TWELVE_DAYS.verses(),
+    };
+    let format = args.option("format").unwrap_or("plain");
+    sing("carol", &song::TWELVE_DAYS, (1, days), format)
+}
+
+fn song(args: &[&str]) -> Result<Report, String> {
+    let args = Args::parse(args, &["verses", "format"])?;
+    args.expect_positional(1, "song <name> [--verses A-B] [--format plain|markdown]")?;
+    let name = args.positional[0];
+    let song = song::find(name).ok_or_else(|| format!("Unknown song '{name}'"))?;
+    let verses = match args.option("verses") {
+        Some(verses) => parse_verses(verses)?,
+        None => (1, song.verses()),
+    };
+    let format = args.option("format").unwrap_or("plain");
+    sing("song", song, verses, format)
+}
+
+// Run a single command, `args` doesn't include the program name
+pub fn run(args: &[&str]) -> Result<Report, String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Err(String::from("No command given, see 'exercises help'"));
+    };
+    match *command {
+        "temp" => temp(rest),
+        "fib" => fib(rest),
+        "carol" => carol(rest),
+        "song" => song(rest),
+        "help" | "--help" | "-h" => Ok(Report {
+            command: "help",
+            fields: vec![("usage", Value::Text(USAGE.to_owned()))],
+            text: USAGE.to_owned(),
+        }),
+        _ => Err(format!("Unknown command '{command}', see 'exercises help'")),
+    }
+}
+
+// Run every command in a batch file, carrying on past errors but
+// reporting failure if any of them went wrong
+pub fn batch(path: &str, output: Output) -> ExitCode {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            eprintln!("{}", render_error(&format!("{path}: {err}"), None, output));
+            return ExitCode::FAILURE;
+        }
+    };
+    let mut status = ExitCode::SUCCESS;
+    for (i, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let args: Vec<&str> = line.split_whitespace().collect();
+        match run(&args) {
+            Ok(report) => println!("{}", report.render(output)),
+            Err(err) => {
+                println!("{}", render_error(&err, Some(i + 1), output));
+                status = ExitCode::FAILURE;
+            }
+        }
+    }
+    status
+}
+
+// Entry point for a non-empty command line
+pub fn main(args: &[String]) -> ExitCode {
+    let mut output = Output::Text;
+    let mut batch_file = None;
+    let mut command = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--json" => output = Output::Json,
+            "--batch" => match args.next() {
+                Some(path) => batch_file = Some(path.as_str()),
+                None => {
+                    eprintln!("{}", render_error("--batch needs a file", None, output));
+                    return ExitCode::FAILURE;
+                }
+            },
+            arg => command.push(arg),
+        }
+    }
+
+    match batch_file {
+        Some(_) if !command.is_empty() => {
+            let err = "--batch can't be combined with a command";
+            eprintln!("{}", render_error(err, None, output));
+            ExitCode::FAILURE
+        }
+        Some(path) => batch(path, output),
+        None => match run(&command) {
+            Ok(report) => {
+                println!("{}", report.render(output));
+                ExitCode::SUCCESS
+            }
+            Err(err) => {
+                eprintln!("{}", render_error(&err, None, output));
+                ExitCode::FAILURE
+            }
+        },
+    }
+}
@@ -0,0 +1,13 @@
+// The nth fibonacci number, None once it no longer fits in a u64 (n > 93)
+pub fn fibonacci(n: u32) -> Option<u64> {
+    if n == 0 {
+        return Some(0);
+    }
+    let (mut first, mut second): (u64, u64) = (0, 1);
+    for _ in 1..n {
+        let temp = second;
+        second = second.checked_add(first)?;
+        first = temp;
+    }
+    Some(second)
+}
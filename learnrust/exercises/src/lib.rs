@@ -0,0 +1,11 @@
+// The exercises are also a library so other crates (like the guessing
+// game) can reuse the prompts and the song engine.
+pub mod calc;
+pub mod cli;
+pub mod fibonacci;
+pub mod menu;
+pub mod number_theory;
+pub mod prompt;
+pub mod song;
+pub mod temperature;
+pub mod words;
@@ -1,91 +1,190 @@
-use std::io;
+use exercises::{
+    calc::{self, Calculator},
+    cli,
+    fibonacci::fibonacci,
+    menu::Menu,
+    number_theory, prompt, song,
+    temperature::{self, Scale},
+    words,
+};
+use std::{env, process::ExitCode};
 
-fn main() {
-    loop {
-        println!("Choose one:");
-        println!("1. Temperature conversion");
-        println!("2. Fibonacci number");
-        println!("3. Christmas Carol");
-
-        let input = read_int();
+fn main() -> ExitCode {
+    // any arguments run a single command (or a batch) instead of the menu
+    let args: Vec<String> = env::args().skip(1).collect();
+    if !args.is_empty() {
+        return cli::main(&args);
+    }
 
-        match input {
-            1 => {
-                println!("Enter celsius temperature: ");
-                let temp = read_int();
-                convert_temp(temp);
-            }
-            2 => {
-                fibonacci();
-            }
-            3 => {
-                carol();
-            }
-            _ => {
-                println!("Please enter valid input (1-3)");
-                break;
+    let mut menu = Menu::new("Choose one:");
+    menu.register("temp", "Temperature conversion", || {
+        let temp = prompt::float("Enter celsius temperature:", None)?;
+        convert_temp(temp);
+        Ok(())
+    })
+    .register("fib", "Fibonacci number", fib)
+    .register("carol", "Christmas Carol", || sing(&song::TWELVE_DAYS))
+    .register("song", "Sing a song", || {
+        // every song gets an entry of its own in a sub menu
+        let mut songs = Menu::new("Choose a song:");
+        for song in song::SONGS {
+            songs.register(song.name, song.title, move || sing(song));
+        }
+        match songs.choose() {
+            Some(command) => command.run(),
+            None => Ok(()),
+        }
+    })
+    .register("calc", "Calculator", calculator)
+    .register("words", "Numbers in words", || {
+        let input = prompt::line("Enter a number in digits or words:")?;
+        match input.parse::<i64>() {
+            Ok(n) => {
+                println!("cardinal: {}", words::cardinal(n));
+                println!("ordinal: {}", words::ordinal(n));
             }
+            Err(_) => match words::parse_cardinal(&input) {
+                Ok(n) => println!("{n}"),
+                Err(err) => match words::parse_ordinal(&input) {
+                    Ok(n) => println!("{n} (ordinal)"),
+                    Err(_) => println!("{err}"),
+                },
+            },
         }
-    }
-}
-
-fn read_int() -> i32 {
-    let mut input = String::new();
-    io::stdin().read_line(&mut input).expect("String");
-    input.trim().parse().expect("Input not an integer")
+        Ok(())
+    })
+    .register("numbers", "Number theory", || {
+        number_theory_menu().run();
+        Ok(())
+    });
+    menu.run();
+    ExitCode::SUCCESS
 }
 
-fn convert_temp(temp: i32) {
-    const MUL_FACTOR: f32 = 9.0 / 5.0;
-    const CONST: u32 = 32;
-    let farhenheit_temp = temp as f32 * MUL_FACTOR + CONST as f32;
+fn convert_temp(temp: f64) {
+    let farhenheit_temp = temperature::convert(temp, Scale::Celsius, Scale::Fahrenheit);
     println!("The temperature in Farhenheit is {farhenheit_temp}");
 }
 
-fn fibonacci() {
-    println!("Enter the fibonacci index");
-    let n = read_int();
-    let (mut first, mut second): (u64, u64) = (0, 1);
-    for _ in 1..n {
-        let temp = second;
-        second += first;
-        first = temp;
+fn fib() -> prompt::Result<()> {
+    // the 94th fibonacci number no longer fits in a u64
+    let n = prompt::range("Enter the fibonacci index (0-93):", 0..=93, None)?;
+    match fibonacci(n) {
+        Some(result) => println!("{n}th fibonacci is {result}"),
+        None => println!("{n}th fibonacci is too big"),
     }
-    if n <= 0 {
-        println!("{n}th fibonacci is {first}");
-        return;
+    Ok(())
+}
+
+fn sing(song: &song::Song) -> prompt::Result<()> {
+    let verses = song.verses();
+    let first = prompt::range("Enter first verse:", 1..=verses, Some(1))?;
+    let last = prompt::range("Enter last verse:", first..=verses, Some(verses))?;
+    let format = match prompt::choice("Choose format:", &["Plain text", "Markdown"], Some(0))? {
+        1 => song::Format::Markdown,
+        _ => song::Format::Plain,
+    };
+
+    match song.render(first..=last, format) {
+        Ok(lyrics) => println!("\n{lyrics}"),
+        Err(err) => println!("{err}"),
     }
-    println!("{n}th fibonacci is {second}");
+    Ok(())
 }
 
-fn carol() {
-    // Ref: https://www.openmymind.net/Rust-Strings/
-    fn repeating_line(index: i32) {
-        let day = match index {
-            1 => "first",
-            2 => "second",
-            3 => "third",
-            _ => "",
-        };
-        println!("\nOn the {day} day of Christmas my true love sent to me")
+// A section of its own, it stays open until 'quit' takes you back
+fn number_theory_menu() -> Menu {
+    fn number(message: &str) -> prompt::Result<u64> {
+        prompt::parse(message, None)
     }
 
-    let mut recursive_line = String::from("A patridge in a pear tree.");
-    for i in 1..=3 {
-        repeating_line(i);
-        match i {
-            1 => {}
-            2 => {
-                recursive_line = format!("Tow turtle doves,\nAnd {}", recursive_line);
-            }
-            3 => {
-                recursive_line = "Three French hens,\n".to_owned() + &recursive_line;
+    let mut menu = Menu::new("Number theory:");
+    menu.register("prime", "Primality test", || {
+        let n = number("Enter a number:")?;
+        match number_theory::is_prime(n) {
+            true => println!("{n} is prime"),
+            false => println!("{n} is not prime"),
+        }
+        Ok(())
+    })
+    .register("sieve", "Primes up to N", || {
+        // the sieve keeps a flag per number, so keep N reasonable
+        let n: usize = prompt::range("Enter N (up to 10000000):", 0..=10_000_000, None)?;
+        let primes = number_theory::sieve(n);
+        let listed: Vec<String> = primes.iter().map(|p| p.to_string()).collect();
+        println!("{} primes up to {n}: {}", primes.len(), listed.join(", "));
+        Ok(())
+    })
+    .register("factor", "Factorization", || {
+        let n = number("Enter a number:")?;
+        let factors: Vec<String> = number_theory::factorize(n)
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        match factors.is_empty() {
+            true => println!("{n} has no prime factors"),
+            false => println!("{n} = {}", factors.join(" * ")),
+        }
+        Ok(())
+    })
+    .register("gcd", "GCD and LCM", || {
+        let a = number("Enter a:")?;
+        let b = number("Enter b:")?;
+        println!("gcd({a}, {b}) = {}", number_theory::gcd(a, b));
+        match number_theory::lcm(a, b) {
+            Some(lcm) => println!("lcm({a}, {b}) = {lcm}"),
+            None => println!("lcm({a}, {b}) doesn't fit in a u64"),
+        }
+        Ok(())
+    })
+    .register("powmod", "Modular exponentiation", || {
+        let base = number("Enter base:")?;
+        let exp = number("Enter exponent:")?;
+        let m = prompt::range("Enter modulus:", 1..=u64::MAX, None)?;
+        let result = number_theory::mod_pow(base, exp, m);
+        println!("{base}^{exp} mod {m} = {result}");
+        Ok(())
+    })
+    .register("totient", "Euler's totient", || {
+        let n = number("Enter a number:")?;
+        println!("phi({n}) = {}", number_theory::totient(n));
+        Ok(())
+    });
+    menu
+}
+
+fn calculator() -> prompt::Result<()> {
+    const PROMPT: &str = "calc>";
+    println!("Type an expression, 'let x = ...', 'history', 'vars', 'help' or 'quit'");
+    let mut calc = Calculator::new();
+    loop {
+        // untrimmed, so a caret under an error lines up with what was typed
+        let line = prompt::raw_line(PROMPT)?;
+        match line.trim() {
+            "" => continue,
+            "quit" | "exit" | "q" => return Ok(()),
+            "help" => println!(
+                "Operators: + - * / % ^ ( )  Functions: {}  Last result: ans",
+                calc::FUNCTIONS.join(", ")
+            ),
+            "history" => {
+                for (i, (line, value)) in calc.history().iter().enumerate() {
+                    println!("{:>3}: {line} = {value}", i + 1);
+                }
             }
-            _ => {
-                continue;
+            "vars" => {
+                for (name, value) in calc.variables() {
+                    println!("{name} = {value}");
+                }
             }
+            _ => match calc.eval_line(&line) {
+                Ok(value) => println!("{value}"),
+                Err(err) => {
+                    // the prompt and the space after it come before the input
+                    println!("{}", err.pointer(PROMPT.len() + 1));
+                    println!("{err}");
+                }
+            },
         }
-        println!("{recursive_line}");
     }
-    println!();
 }
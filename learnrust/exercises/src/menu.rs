@@ -0,0 +1,165 @@
+// A menu is generated from the commands registered on it, so adding an
+// exercise only needs one 'register' call instead of touching the prompt,
+// the listing and the match arms separately.
+use crate::{prompt, words::Number};
+
+pub struct Command {
+    pub name: &'static str,
+    pub description: &'static str,
+    handler: Box<dyn Fn() -> prompt::Result<()>>,
+}
+
+pub struct Menu {
+    title: &'static str,
+    commands: Vec<Command>,
+}
+
+impl Menu {
+    pub fn new(title: &'static str) -> Menu {
+        Menu {
+            title,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn register(
+        &mut self,
+        name: &'static str,
+        description: &'static str,
+        handler: impl Fn() -> prompt::Result<()> + 'static,
+    ) -> &mut Menu {
+        self.commands.push(Command {
+            name,
+            description,
+            handler: Box::new(handler),
+        });
+        self
+    }
+
+    // The title and a numbered line for every command, in the order
+    // they were registered
+    pub fn listing(&self) -> String {
+        let mut out = format!("{}\n", self.title);
+        for (i, command) in self.commands.iter().enumerate() {
+            out.push_str(&format!(
+                "{}. {} - {}\n",
+                i + 1,
+                command.name,
+                command.description
+            ));
+        }
+        out
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.listing());
+    }
+
+    fn help(&self) {
+        self.print();
+        println!("Pick an item by number or name, 'help' shows this list, 'quit' exits");
+    }
+
+    // Look a command up by its 1-based number (in digits or words) or its
+    // case insensitive name
+    pub fn find(&self, input: &str) -> Option<&Command> {
+        let input = input.trim();
+        match input.parse::<Number>() {
+            Ok(Number(n)) => usize::try_from(n)
+                .ok()
+                .and_then(|n| n.checked_sub(1))
+                .and_then(|i| self.commands.get(i)),
+            Err(_) => self
+                .commands
+                .iter()
+                .find(|command| command.name.eq_ignore_ascii_case(input)),
+        }
+    }
+
+    // Keep asking until a command is picked, None on 'quit' or end of input
+    pub fn choose(&self) -> Option<&Command> {
+        self.print();
+        loop {
+            let Ok(input) = prompt::line("") else {
+                return None; // <-- EOF, nothing more to read
+            };
+            match input.to_lowercase().as_str() {
+                "quit" | "exit" | "q" => return None,
+                "help" | "?" => self.help(),
+                choice => match self.find(choice) {
+                    Some(command) => return Some(command),
+                    None => println!(
+                        "Unknown choice '{choice}', enter 1-{} or a name ('help' for the list)",
+                        self.commands.len()
+                    ),
+                },
+            }
+        }
+    }
+
+    // Run commands until the user quits or the input runs out
+    pub fn run(&self) {
+        while let Some(command) = self.choose() {
+            if command.run().is_err() {
+                break;
+            }
+        }
+    }
+}
+
+impl Command {
+    pub fn run(&self) -> prompt::Result<()> {
+        (self.handler)()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{cell::Cell, rc::Rc};
+
+    fn menu() -> Menu {
+        let mut menu = Menu::new("Pick one:");
+        menu.register("temp", "Convert temperatures", || Ok(()))
+            .register("fib", "Fibonacci numbers", || Ok(()))
+            .register("carol", "Sing a carol", || Err(prompt::Error::Eof));
+        menu
+    }
+
+    #[test]
+    fn listed_in_the_order_registered() {
+        assert_eq!(
+            menu().listing(),
+            "Pick one:\n1. temp - Convert temperatures\n2. fib - Fibonacci numbers\n3. carol - Sing a carol\n"
+        );
+    }
+
+    #[test]
+    fn found_by_number_word_or_name() {
+        let menu = menu();
+        let name = |input: &str| menu.find(input).map(|command| command.name);
+        assert_eq!(name("2"), Some("fib"));
+        assert_eq!(name(" three "), Some("carol"));
+        assert_eq!(name("TEMP"), Some("temp"));
+        assert_eq!(name("0"), None);
+        assert_eq!(name("4"), None);
+        assert_eq!(name("-1"), None);
+        assert_eq!(name("tempo"), None);
+    }
+
+    #[test]
+    fn commands_run_their_handlers() {
+        let runs = Rc::new(Cell::new(0));
+        let counted = Rc::clone(&runs);
+        let mut menu = Menu::new("Menu");
+        menu.register("count", "Count a run", move || {
+            counted.set(counted.get() + 1);
+            Ok(())
+        });
+        let command = menu.find("count").unwrap();
+        command.run().unwrap();
+        command.run().unwrap();
+        assert_eq!(runs.get(), 2);
+        assert!(self::menu().find("carol").unwrap().run().is_err());
+    }
+}
@@ -0,0 +1,214 @@
+// A small number theory toolkit over u64. Products of two u64s are done in
+// u128 so nothing overflows halfway through a modular multiplication.
+
+pub fn gcd(mut a: u64, mut b: u64) -> u64 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+// None if the result doesn't fit in a u64
+pub fn lcm(a: u64, b: u64) -> Option<u64> {
+    if a == 0 || b == 0 {
+        return Some(0);
+    }
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+fn mul_mod(a: u64, b: u64, m: u64) -> u64 {
+    (a as u128 * b as u128 % m as u128) as u64
+}
+
+// base^exp mod m by repeated squaring
+pub fn mod_pow(base: u64, mut exp: u64, m: u64) -> u64 {
+    if m == 1 {
+        return 0;
+    }
+    let (mut base, mut result) = (base % m, 1);
+    while exp > 0 {
+        if exp & 1 == 1 {
+            result = mul_mod(result, base, m);
+        }
+        base = mul_mod(base, base, m);
+        exp >>= 1;
+    }
+    result
+}
+
+// Miller-Rabin with the first twelve primes as witnesses, which is known
+// to give the right answer for every n < 3.3 * 10^24, so all of u64
+pub fn is_prime(n: u64) -> bool {
+    const WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+    if n < 2 {
+        return false;
+    }
+    for p in WITNESSES {
+        if n.is_multiple_of(p) {
+            return n == p;
+        }
+    }
+
+    // n - 1 = d * 2^s with d odd
+    let s = (n - 1).trailing_zeros();
+    let d = (n - 1) >> s;
+    'witness: for a in WITNESSES {
+        let mut x = mod_pow(a, d, n);
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+        for _ in 1..s {
+            x = mul_mod(x, x, n);
+            if x == n - 1 {
+                continue 'witness;
+            }
+        }
+        return false; // <-- a proves n is composite
+    }
+    true
+}
+
+// Sieve of Eratosthenes, every prime <= n
+pub fn sieve(n: usize) -> Vec<usize> {
+    if n < 2 {
+        return Vec::new();
+    }
+    let mut is_prime = vec![true; n + 1];
+    is_prime[0] = false;
+    is_prime[1] = false;
+    let mut i = 2;
+    while i * i <= n {
+        if is_prime[i] {
+            for multiple in (i * i..=n).step_by(i) {
+                is_prime[multiple] = false;
+            }
+        }
+        i += 1;
+    }
+    is_prime
+        .iter()
+        .enumerate()
+        .filter(|(_, prime)| **prime)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+// Pollard's rho with Floyd's cycle detection. Returns a non trivial
+// divisor of the composite n, trying new polynomials x^2 + c until one works.
+fn pollard_rho(n: u64) -> u64 {
+    if n.is_multiple_of(2) {
+        return 2;
+    }
+    for c in 1.. {
+        let f = |x: u64| ((x as u128 * x as u128 + c as u128) % n as u128) as u64;
+        let (mut x, mut y, mut d) = (2, 2, 1);
+        while d == 1 {
+            x = f(x);
+            y = f(f(y));
+            d = gcd(x.abs_diff(y), n);
+        }
+        if d != n {
+            return d;
+        }
+    }
+    unreachable!()
+}
+
+// Prime factors of n in ascending order, with repeats (12 -> [2, 2, 3])
+pub fn factorize(n: u64) -> Vec<u64> {
+    fn split(n: u64, factors: &mut Vec<u64>) {
+        if n == 1 {
+            return;
+        }
+        if is_prime(n) {
+            factors.push(n);
+            return;
+        }
+        let d = pollard_rho(n);
+        split(d, factors);
+        split(n / d, factors);
+    }
+
+    let mut factors = Vec::new();
+    let mut n = n;
+    // small factors are quicker to divide out than to find with rho
+    for p in [2, 3, 5, 7, 11, 13] {
+        while n > 1 && n.is_multiple_of(p) {
+            factors.push(p);
+            n /= p;
+        }
+    }
+    if n > 1 {
+        split(n, &mut factors);
+    }
+    factors.sort();
+    factors
+}
+
+// Euler's totient: how many of 1..=n are coprime to n
+pub fn totient(n: u64) -> u64 {
+    if n == 0 {
+        return 0;
+    }
+    let mut factors = factorize(n);
+    factors.dedup();
+    factors.iter().fold(n, |phi, p| phi / p * (p - 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn known_primes() {
+        let small: Vec<u64> = (0..100).filter(|&n| is_prime(n)).collect();
+        let sieved: Vec<u64> = sieve(100).into_iter().map(|p| p as u64).collect();
+        assert_eq!(small, sieved);
+        assert!(is_prime(1_000_000_007));
+        assert!(is_prime(4_294_967_291)); // <-- largest prime below 2^32
+        assert!(is_prime(18_446_744_073_709_551_557)); // <-- and below 2^64
+        assert!(!is_prime(u64::MAX));
+    }
+
+    #[test]
+    fn carmichael_numbers_are_composite() {
+        // these fool Fermat's test for every base coprime to them
+        for n in [561, 1105, 1729, 2465, 2821, 6601, 8911] {
+            assert!(!is_prime(n), "{n}");
+        }
+        // and these fool Miller-Rabin for the first few witnesses
+        assert!(!is_prime(3_215_031_751)); // <-- bases 2, 3, 5, 7
+        assert!(!is_prime(3_825_123_056_546_413_051)); // <-- bases 2 to 23
+        assert_eq!(factorize(561), [3, 11, 17]);
+        assert_eq!(factorize(1729), [7, 13, 19]);
+    }
+
+    #[test]
+    fn semiprime_near_the_top() {
+        let (p, q) = (4_294_967_279, 4_294_967_291);
+        assert_eq!(factorize(p * q), [p, q]);
+        assert_eq!(totient(p * q), (p - 1) * (q - 1));
+    }
+
+    #[test]
+    fn factors_and_totients() {
+        assert_eq!(factorize(1), []);
+        assert_eq!(factorize(12), [2, 2, 3]);
+        assert_eq!(factorize(1 << 63), [2; 63]);
+        assert_eq!(totient(0), 0);
+        assert_eq!(totient(1), 1);
+        assert_eq!(totient(9), 6);
+        assert_eq!(totient(36), 12);
+        assert_eq!(totient(4_294_967_291), 4_294_967_290);
+    }
+
+    #[test]
+    fn gcd_lcm_and_powers() {
+        assert_eq!(gcd(12, 18), 6);
+        assert_eq!(gcd(0, 5), 5);
+        assert_eq!(lcm(4, 6), Some(12));
+        assert_eq!(lcm(u64::MAX, u64::MAX - 1), None);
+        assert_eq!(mod_pow(2, 10, 1000), 24);
+        assert_eq!(mod_pow(u64::MAX, u64::MAX, 1), 0);
+    }
+}
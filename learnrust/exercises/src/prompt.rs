@@ -0,0 +1,288 @@
+// Typed prompts that keep asking until the input makes sense instead of
+// panicking on the first typo. The only way out without a value is the end
+// of input (Ctrl-D or a closed pipe) which comes back as an Err.
+use crate::words::Number;
+use std::{
+    fmt::{self, Display},
+    io::{self, BufRead, StdinLock, Stdout, Write},
+    ops::RangeInclusive,
+    str::FromStr,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    Eof,
+    Io(io::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Eof => write!(f, "end of input"),
+            Error::Io(err) => write!(f, "could not read input: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Prompts read from any input and write to any output, so they can be
+// driven by a script or a test as well as a terminal. The functions below
+// the impl are the same prompts on stdin and stdout.
+pub struct Prompt<R, W> {
+    input: R,
+    output: W,
+}
+
+impl<R: BufRead, W: Write> Prompt<R, W> {
+    pub fn new(input: R, output: W) -> Prompt<R, W> {
+        Prompt { input, output }
+    }
+
+    // Print the message and read one line as it was typed, without the
+    // line break, for when the spaces matter (like pointing at a column)
+    pub fn raw_line(&mut self, message: &str) -> Result<String> {
+        if !message.is_empty() {
+            write!(self.output, "{message} ").map_err(Error::Io)?;
+            self.output.flush().map_err(Error::Io)?;
+        }
+        let mut input = String::new();
+        match self.input.read_line(&mut input) {
+            Ok(0) => Err(Error::Eof),
+            Ok(_) => {
+                let end = input.trim_end_matches(['\r', '\n']).len();
+                input.truncate(end);
+                Ok(input)
+            }
+            Err(err) => Err(Error::Io(err)),
+        }
+    }
+
+    // Print the message and read one trimmed line
+    pub fn line(&mut self, message: &str) -> Result<String> {
+        self.raw_line(message).map(|input| input.trim().to_owned())
+    }
+
+    // The loop every prompt shares: empty input picks the default if there
+    // is one, otherwise `parse` decides whether to accept it or ask again.
+    fn ask<T: Display>(
+        &mut self,
+        message: &str,
+        default: Option<T>,
+        parse: impl Fn(&str) -> std::result::Result<T, String>,
+    ) -> Result<T> {
+        let message = match (&default, message.strip_suffix(':')) {
+            (Some(default), Some(message)) => format!("{message} [{default}]:"),
+            (Some(default), None) => format!("{message} [{default}]"),
+            (None, _) => message.to_owned(),
+        };
+        let mut default = default;
+        loop {
+            let input = self.line(&message)?;
+            if input.is_empty() {
+                if let Some(default) = default.take() {
+                    return Ok(default);
+                }
+            }
+            match parse(&input) {
+                Ok(value) => return Ok(value),
+                Err(err) => writeln!(self.output, "{err}, try again").map_err(Error::Io)?,
+            }
+        }
+    }
+
+    pub fn parse<T>(&mut self, message: &str, default: Option<T>) -> Result<T>
+    where
+        T: FromStr + Display,
+    {
+        self.ask(message, default, |input| {
+            input
+                .parse()
+                .map_err(|_| format!("'{input}' is not a valid {}", std::any::type_name::<T>()))
+        })
+    }
+
+    pub fn range<T>(
+        &mut self,
+        message: &str,
+        range: RangeInclusive<T>,
+        default: Option<T>,
+    ) -> Result<T>
+    where
+        T: FromStr + Display + PartialOrd,
+    {
+        self.ask(message, default, |input| {
+            let value: T = input
+                .parse()
+                .map_err(|_| format!("'{input}' is not a number"))?;
+            if range.contains(&value) {
+                Ok(value)
+            } else {
+                Err(format!(
+                    "{value} is outside of {}-{}",
+                    range.start(),
+                    range.end()
+                ))
+            }
+        })
+    }
+
+    pub fn yes_no(&mut self, message: &str, default: Option<bool>) -> Result<bool> {
+        let hint = match default {
+            Some(true) => "[Y/n]",
+            Some(false) => "[y/N]",
+            None => "[y/n]",
+        };
+        // the default is already part of the hint, so it's checked here
+        // rather than letting 'ask' print it a second time
+        self.ask(&format!("{message} {hint}"), None, |input| {
+            match (input.to_lowercase().as_str(), default) {
+                ("", Some(default)) => Ok(default),
+                ("y" | "yes", _) => Ok(true),
+                ("n" | "no", _) => Ok(false),
+                _ => Err(String::from("Please answer yes or no")),
+            }
+        })
+    }
+
+    // Pick one of the options by number or by name, returns its index
+    pub fn choice(
+        &mut self,
+        message: &str,
+        options: &[&str],
+        default: Option<usize>,
+    ) -> Result<usize> {
+        for (i, option) in options.iter().enumerate() {
+            writeln!(self.output, "{}. {option}", i + 1).map_err(Error::Io)?;
+        }
+        let default = default.map(|i| i + 1); // <-- shown 1-based like the list
+        let picked = self.ask(message, default, |input| {
+            let by_number = input
+                .parse::<Number>()
+                .ok()
+                .and_then(|Number(n)| usize::try_from(n).ok())
+                .filter(|n| (1..=options.len()).contains(n));
+            let by_name = || {
+                options
+                    .iter()
+                    .position(|option| option.eq_ignore_ascii_case(input))
+                    .map(|i| i + 1)
+            };
+            by_number
+                .or_else(by_name)
+                .ok_or_else(|| format!("Pick 1-{} or one of the names", options.len()))
+        })?;
+        Ok(picked - 1)
+    }
+}
+
+fn stdio() -> Prompt<StdinLock<'static>, Stdout> {
+    Prompt::new(io::stdin().lock(), io::stdout())
+}
+
+pub fn raw_line(message: &str) -> Result<String> {
+    stdio().raw_line(message)
+}
+
+pub fn line(message: &str) -> Result<String> {
+    stdio().line(message)
+}
+
+pub fn parse<T>(message: &str, default: Option<T>) -> Result<T>
+where
+    T: FromStr + Display,
+{
+    stdio().parse(message, default)
+}
+
+pub fn int(message: &str, default: Option<i32>) -> Result<i32> {
+    parse(message, default)
+}
+
+pub fn float(message: &str, default: Option<f64>) -> Result<f64> {
+    parse(message, default)
+}
+
+pub fn range<T>(message: &str, range: RangeInclusive<T>, default: Option<T>) -> Result<T>
+where
+    T: FromStr + Display + PartialOrd,
+{
+    stdio().range(message, range, default)
+}
+
+pub fn yes_no(message: &str, default: Option<bool>) -> Result<bool> {
+    stdio().yes_no(message, default)
+}
+
+pub fn choice(message: &str, options: &[&str], default: Option<usize>) -> Result<usize> {
+    stdio().choice(message, options, default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Run a prompt on the typed lines, hand back its answer and what it printed
+    fn answer<T>(
+        typed: &str,
+        ask: impl FnOnce(&mut Prompt<&[u8], Vec<u8>>) -> Result<T>,
+    ) -> (Result<T>, String) {
+        let mut prompt = Prompt::new(typed.as_bytes(), Vec::new());
+        let answer = ask(&mut prompt);
+        (answer, String::from_utf8(prompt.output).unwrap())
+    }
+
+    #[test]
+    fn asks_again_until_it_makes_sense() {
+        let (n, printed) = answer("twelve\n 12 \n", |p| p.parse::<i32>("Number:", None));
+        assert_eq!(n.unwrap(), 12);
+        assert_eq!(
+            printed,
+            "Number: 'twelve' is not a valid i32, try again\nNumber: "
+        );
+
+        let (n, printed) = answer("0\n11\n7\n", |p| p.range("Pick:", 1..=10, None));
+        assert_eq!(n.unwrap(), 7);
+        assert!(printed.contains("0 is outside of 1-10, try again"));
+        assert!(printed.contains("11 is outside of 1-10, try again"));
+    }
+
+    #[test]
+    fn empty_input_takes_the_default() {
+        let (n, printed) = answer("\n", |p| p.parse("Temperature:", Some(21.5)));
+        assert_eq!(n.unwrap(), 21.5);
+        assert_eq!(printed, "Temperature [21.5]: ");
+
+        // without one an empty line is just wrong
+        let (n, _) = answer("\n3\n", |p| p.range("Verse:", 1..=12, None));
+        assert_eq!(n.unwrap(), 3);
+
+        let (yes, printed) = answer("\n", |p| p.yes_no("Again?", Some(false)));
+        assert!(!yes.unwrap());
+        assert_eq!(printed, "Again? [y/N] ");
+        let (yes, _) = answer("maybe\nYES\n", |p| p.yes_no("Again?", None));
+        assert!(yes.unwrap());
+    }
+
+    #[test]
+    fn choices_by_number_or_name() {
+        let options = ["Plain text", "Markdown"];
+        let (picked, printed) = answer("\n", |p| p.choice("Format:", &options, Some(0)));
+        assert_eq!(picked.unwrap(), 0);
+        assert_eq!(printed, "1. Plain text\n2. Markdown\nFormat [1]: ");
+        let (picked, _) = answer("markdown\n", |p| p.choice("Format:", &options, None));
+        assert_eq!(picked.unwrap(), 1);
+        let (picked, _) = answer("3\ntwo\n", |p| p.choice("Format:", &options, None));
+        assert_eq!(picked.unwrap(), 1);
+    }
+
+    #[test]
+    fn lines_end_at_the_end_of_input() {
+        let (line, _) = answer("  1 + 2\r\n", |p| p.raw_line(">"));
+        assert_eq!(line.unwrap(), "  1 + 2");
+        let (n, _) = answer("nope\n", |p| p.parse::<i32>("Number:", None));
+        assert!(matches!(n, Err(Error::Eof)));
+    }
+}
@@ -0,0 +1,449 @@
+// Songs that repeat themselves can be described as data: a cumulative song
+// keeps adding an item to every verse (The Twelve Days of Christmas,
+// The House That Jack Built) while a countdown song sings the same verse
+// with a shrinking count (99 Bottles of Beer). The engine below fills in
+// ordinal words, plurals and the accumulated lines from those templates.
+//
+// Templates use '{key}' placeholders, '{Key}' gives the capitalized value.
+use crate::words;
+use std::ops::RangeInclusive;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Plain,
+    Markdown,
+}
+
+pub struct Item {
+    pub name: &'static str,
+    pub detail: &'static str,
+}
+
+pub struct Cumulative {
+    pub items: &'static [Item],
+    // first line(s) of every verse, filled from the newest item
+    pub intro: &'static str,
+    // sung for each accumulated item, newest first
+    pub line: &'static str,
+    // how the first item is sung once it's no longer alone ("And a partridge")
+    pub first_line: Option<&'static str>,
+    // index of the oldest item that gets a line of its own
+    pub lines_from: usize,
+    pub separator: &'static str,
+    pub terminator: &'static str,
+    pub outro: Option<&'static str>,
+}
+
+pub struct Countdown {
+    pub from: u32,
+    pub singular: &'static str,
+    pub plural: &'static str,
+    // {count} {things} {next} {next_things} {it}
+    pub verse: &'static str,
+    // sung once the count hits zero, {next} starts the song over
+    pub last_verse: &'static str,
+}
+
+pub enum Kind {
+    Cumulative(Cumulative),
+    Countdown(Countdown),
+}
+
+pub struct Song {
+    pub name: &'static str,
+    pub title: &'static str,
+    pub kind: Kind,
+}
+
+pub const TWELVE_DAYS: Song = Song {
+    name: "carol",
+    title: "The Twelve Days of Christmas",
+    kind: Kind::Cumulative(Cumulative {
+        items: &[
+            Item {
+                name: "a partridge in a pear tree",
+                detail: "",
+            },
+            Item {
+                name: "two turtle doves",
+                detail: "",
+            },
+            Item {
+                name: "three French hens",
+                detail: "",
+            },
+            Item {
+                name: "four calling birds",
+                detail: "",
+            },
+            Item {
+                name: "five gold rings",
+                detail: "",
+            },
+            Item {
+                name: "six geese a-laying",
+                detail: "",
+            },
+            Item {
+                name: "seven swans a-swimming",
+                detail: "",
+            },
+            Item {
+                name: "eight maids a-milking",
+                detail: "",
+            },
+            Item {
+                name: "nine ladies dancing",
+                detail: "",
+            },
+            Item {
+                name: "ten lords a-leaping",
+                detail: "",
+            },
+            Item {
+                name: "eleven pipers piping",
+                detail: "",
+            },
+            Item {
+                name: "twelve drummers drumming",
+                detail: "",
+            },
+        ],
+        intro: "On the {ordinal} day of Christmas my true love sent to me",
+        line: "{Name}",
+        first_line: Some("And {name}"),
+        lines_from: 0,
+        separator: ",",
+        terminator: ".",
+        outro: None,
+    }),
+};
+
+pub const JACK: Song = Song {
+    name: "jack",
+    title: "The House That Jack Built",
+    kind: Kind::Cumulative(Cumulative {
+        items: &[
+            Item {
+                name: "house that Jack built",
+                detail: "",
+            },
+            Item {
+                name: "malt",
+                detail: "lay in the house that Jack built",
+            },
+            Item {
+                name: "rat",
+                detail: "ate the malt",
+            },
+            Item {
+                name: "cat",
+                detail: "killed the rat",
+            },
+            Item {
+                name: "dog",
+                detail: "worried the cat",
+            },
+            Item {
+                name: "cow with the crumpled horn",
+                detail: "tossed the dog",
+            },
+            Item {
+                name: "maiden all forlorn",
+                detail: "milked the cow with the crumpled horn",
+            },
+            Item {
+                name: "man all tattered and torn",
+                detail: "kissed the maiden all forlorn",
+            },
+            Item {
+                name: "priest all shaven and shorn",
+                detail: "married the man all tattered and torn",
+            },
+            Item {
+                name: "cock that crowed in the morn",
+                detail: "woke the priest all shaven and shorn",
+            },
+            Item {
+                name: "farmer sowing his corn",
+                detail: "kept the cock that crowed in the morn",
+            },
+            Item {
+                name: "horse and the hound and the horn",
+                detail: "belonged to the farmer sowing his corn",
+            },
+        ],
+        intro: "This is the {name}",
+        line: "That {detail}",
+        first_line: None,
+        lines_from: 1, // <-- the house is only ever sung in the intro
+        separator: "",
+        terminator: ".",
+        outro: None,
+    }),
+};
+
+pub const OLD_MACDONALD: Song = Song {
+    name: "macdonald",
+    title: "Old MacDonald Had a Farm",
+    kind: Kind::Cumulative(Cumulative {
+        items: &[
+            Item { name: "cow", detail: "moo" },
+            Item { name: "duck", detail: "quack" },
+            Item { name: "pig", detail: "oink" },
+            Item { name: "sheep", detail: "baa" },
+            Item { name: "horse", detail: "neigh" },
+            Item { name: "dog", detail: "woof" },
+        ],
+        intro: "Old MacDonald had a farm, E-I-E-I-O,\nAnd on that farm he had a {name}, E-I-E-I-O,",
+        line: "With a {detail} {detail} here and a {detail} {detail} there,\nHere a {detail}, there a {detail}, everywhere a {detail} {detail}",
+        first_line: None,
+        lines_from: 0,
+        separator: ",",
+        terminator: ".",
+        outro: Some("Old MacDonald had a farm, E-I-E-I-O."),
+    }),
+};
+
+pub const BOTTLES: Song = Song {
+    name: "bottles",
+    title: "99 Bottles of Beer",
+    kind: Kind::Countdown(Countdown {
+        from: 99,
+        singular: "bottle",
+        plural: "bottles",
+        verse: "{Count} {things} of beer on the wall, {count} {things} of beer.\nTake {it} down and pass it around, {next} {next_things} of beer on the wall.",
+        last_verse: "{Count} {things} of beer on the wall, {count} {things} of beer.\nGo to the store and buy some more, {next} {next_things} of beer on the wall.",
+    }),
+};
+
+pub const SONGS: [&Song; 4] = [&TWELVE_DAYS, &JACK, &OLD_MACDONALD, &BOTTLES];
+
+pub fn find(name: &str) -> Option<&'static Song> {
+    SONGS.into_iter().find(|song| song.name == name)
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().collect::<String>() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+// Replace every '{key}' and '{Key}' in the template with its value
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = template.to_owned();
+    for (key, value) in values {
+        out = out.replace(&format!("{{{key}}}"), value);
+        out = out.replace(&format!("{{{}}}", capitalize(key)), &capitalize(value));
+    }
+    out
+}
+
+pub fn pluralize<'a>(count: u32, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 {
+        singular
+    } else {
+        plural
+    }
+}
+
+impl Cumulative {
+    fn lines(&self, verse: usize) -> Vec<String> {
+        let current = &self.items[verse - 1];
+        let ordinal = words::ordinal(verse as i64);
+        let number = verse.to_string();
+        let intro = fill(
+            self.intro,
+            &[
+                ("ordinal", &ordinal),
+                ("number", &number),
+                ("name", current.name),
+                ("detail", current.detail),
+            ],
+        );
+
+        let mut lines = vec![intro];
+        for (i, item) in self.items[..verse].iter().enumerate().rev() {
+            if i < self.lines_from {
+                continue;
+            }
+            let template = match self.first_line {
+                Some(first_line) if i == 0 && verse > 1 => first_line,
+                _ => self.line,
+            };
+            let values = [("name", item.name), ("detail", item.detail)];
+            lines.push(fill(template, &values) + self.separator);
+        }
+
+        match self.outro {
+            Some(outro) => lines.push(outro.to_owned()),
+            None => {
+                // the last line ends the verse, swap its separator for the terminator
+                let sung_items = lines.len() > 1;
+                let last = lines.last_mut().unwrap();
+                if sung_items {
+                    last.truncate(last.len() - self.separator.len());
+                }
+                last.push_str(self.terminator);
+            }
+        }
+        lines
+    }
+}
+
+impl Countdown {
+    fn lines(&self, verse: usize) -> Vec<String> {
+        let count = self.from + 1 - verse as u32;
+        let next = if count == 0 { self.from } else { count - 1 };
+        let say = |n: u32| {
+            if n == 0 {
+                String::from("no more")
+            } else {
+                n.to_string()
+            }
+        };
+        let (count_words, next_words) = (say(count), say(next));
+        let values = [
+            ("count", count_words.as_str()),
+            ("things", pluralize(count, self.singular, self.plural)),
+            ("next", next_words.as_str()),
+            ("next_things", pluralize(next, self.singular, self.plural)),
+            ("it", if count == 1 { "it" } else { "one" }),
+        ];
+        let template = if count == 0 {
+            self.last_verse
+        } else {
+            self.verse
+        };
+        fill(template, &values).lines().map(String::from).collect()
+    }
+}
+
+impl Song {
+    pub fn verses(&self) -> usize {
+        match &self.kind {
+            Kind::Cumulative(song) => song.items.len(),
+            Kind::Countdown(song) => song.from as usize + 1, // <-- plus the "no more" verse
+        }
+    }
+
+    pub fn verse(&self, n: usize, format: Format) -> String {
+        let lines = match &self.kind {
+            Kind::Cumulative(song) => song.lines(n),
+            Kind::Countdown(song) => song.lines(n),
+        };
+        let lines: Vec<&str> = lines.iter().flat_map(|line| line.lines()).collect();
+        match format {
+            Format::Plain => lines.join("\n") + "\n",
+            // two trailing spaces make markdown keep the line breaks
+            Format::Markdown => format!("### Verse {n}\n\n{}\n", lines.join("  \n")),
+        }
+    }
+
+    // Render the verses in `verses` (1-based, inclusive) separated by blank lines
+    pub fn render(&self, verses: RangeInclusive<usize>, format: Format) -> Result<String, String> {
+        let (first, last, total) = (*verses.start(), *verses.end(), self.verses());
+        if first < 1 || last > total || first > last {
+            return Err(format!(
+                "Verse range must be within 1-{total}, got {first}-{last}"
+            ));
+        }
+        let mut out = match format {
+            Format::Plain => String::new(),
+            Format::Markdown => format!("## {}\n\n", self.title),
+        };
+        let verses: Vec<String> = verses.map(|n| self.verse(n, format)).collect();
+        out.push_str(&verses.join("\n"));
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn carol_verses_build_up() {
+        assert_eq!(TWELVE_DAYS.verses(), 12);
+        assert_eq!(
+            TWELVE_DAYS.verse(1, Format::Plain),
+            "On the first day of Christmas my true love sent to me\nA partridge in a pear tree.\n"
+        );
+        assert_eq!(
+            TWELVE_DAYS.verse(3, Format::Plain),
+            "On the third day of Christmas my true love sent to me\n\
+             Three French hens,\n\
+             Two turtle doves,\n\
+             And a partridge in a pear tree.\n"
+        );
+        let last = TWELVE_DAYS.verse(12, Format::Plain);
+        assert!(last.starts_with("On the twelfth day of Christmas"));
+        assert_eq!(last.lines().count(), 13);
+    }
+
+    #[test]
+    fn whole_carol() {
+        let carol = TWELVE_DAYS.render(1..=12, Format::Plain).unwrap();
+        assert_eq!(carol.split("\n\n").count(), 12);
+        assert_eq!(carol.matches("And a partridge").count(), 11);
+    }
+
+    #[test]
+    fn cumulative_templates() {
+        assert_eq!(
+            JACK.verse(3, Format::Plain),
+            "This is the rat\nThat ate the malt\nThat lay in the house that Jack built.\n"
+        );
+        // one item alone still ends with the terminator
+        assert_eq!(
+            JACK.verse(1, Format::Plain),
+            "This is the house that Jack built.\n"
+        );
+        // an outro takes the place of the terminator
+        let verse = OLD_MACDONALD.verse(2, Format::Plain);
+        assert!(verse.contains("had a duck, E-I-E-I-O,\nWith a quack quack here"));
+        assert!(verse.ends_with("everywhere a moo moo,\nOld MacDonald had a farm, E-I-E-I-O.\n"));
+    }
+
+    #[test]
+    fn countdown_templates() {
+        assert_eq!(BOTTLES.verses(), 100);
+        assert_eq!(
+            BOTTLES.verse(99, Format::Plain),
+            "1 bottle of beer on the wall, 1 bottle of beer.\n\
+             Take it down and pass it around, no more bottles of beer on the wall.\n"
+        );
+        assert_eq!(
+            BOTTLES.verse(100, Format::Plain),
+            "No more bottles of beer on the wall, no more bottles of beer.\n\
+             Go to the store and buy some more, 99 bottles of beer on the wall.\n"
+        );
+        assert!(BOTTLES
+            .verse(98, Format::Plain)
+            .ends_with("Take one down and pass it around, 1 bottle of beer on the wall.\n"));
+    }
+
+    #[test]
+    fn markdown_and_ranges() {
+        assert_eq!(
+            BOTTLES.render(100..=100, Format::Markdown).unwrap(),
+            "## 99 Bottles of Beer\n\n### Verse 100\n\n\
+             No more bottles of beer on the wall, no more bottles of beer.  \n\
+             Go to the store and buy some more, 99 bottles of beer on the wall.\n"
+        );
+        assert_eq!(
+            JACK.render(3..=13, Format::Plain),
+            Err(String::from("Verse range must be within 1-12, got 3-13"))
+        );
+        assert!(JACK
+            .render(RangeInclusive::new(2, 1), Format::Plain)
+            .is_err());
+        assert_eq!(
+            find("bottles").map(|song| song.title),
+            Some("99 Bottles of Beer")
+        );
+        assert!(find("Bottles").is_none());
+    }
+}
@@ -0,0 +1,88 @@
+// Conversions between the three temperature scales, everything goes
+// through celsius so each scale only needs to know about that one.
+use std::{fmt, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Scale {
+    Celsius,
+    Fahrenheit,
+    Kelvin,
+}
+
+impl Scale {
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Scale::Celsius => "C",
+            Scale::Fahrenheit => "F",
+            Scale::Kelvin => "K",
+        }
+    }
+
+    // The scale people usually want to convert to
+    pub fn other(&self) -> Scale {
+        match self {
+            Scale::Fahrenheit => Scale::Celsius,
+            _ => Scale::Fahrenheit,
+        }
+    }
+}
+
+impl fmt::Display for Scale {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+impl FromStr for Scale {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Scale, String> {
+        match s.to_lowercase().as_str() {
+            "c" | "celsius" => Ok(Scale::Celsius),
+            "f" | "fahrenheit" => Ok(Scale::Fahrenheit),
+            "k" | "kelvin" => Ok(Scale::Kelvin),
+            _ => Err(format!("Unknown temperature scale '{s}' (use C, F or K)")),
+        }
+    }
+}
+
+pub fn convert(temp: f64, from: Scale, to: Scale) -> f64 {
+    const MUL_FACTOR: f64 = 9.0 / 5.0;
+    const CONST: f64 = 32.0;
+    const ZERO_KELVIN: f64 = -273.15;
+    let celsius = match from {
+        Scale::Celsius => temp,
+        Scale::Fahrenheit => (temp - CONST) / MUL_FACTOR,
+        Scale::Kelvin => temp + ZERO_KELVIN,
+    };
+    match to {
+        Scale::Celsius => celsius,
+        Scale::Fahrenheit => celsius * MUL_FACTOR + CONST,
+        Scale::Kelvin => celsius - ZERO_KELVIN,
+    }
+}
+
+// Split a reading like "30C", "-4.5 F" or "1e5 kelvin" into its value and
+// scale. The scale is the letters at the end, so an exponent's 'e' stays
+// with the number.
+pub fn parse_reading(reading: &str) -> Result<(f64, Scale), String> {
+    let reading = reading.trim();
+    let split = reading
+        .rfind(|c: char| !c.is_alphabetic())
+        .map_or(0, |i| i + reading[i..].chars().next().unwrap().len_utf8());
+    if split == reading.len() {
+        return Err(format!("'{reading}' has no scale, try something like 30C"));
+    }
+    let (value, scale) = reading.split_at(split);
+    let value = value.trim();
+    if value.is_empty() {
+        return Err(format!("'{reading}' has no number, try something like 30C"));
+    }
+    let not_a_temperature = || format!("'{value}' is not a temperature");
+    let value: f64 = value.parse().map_err(|_| not_a_temperature())?;
+    // "inf" parses, and so does a number too big for an f64
+    if !value.is_finite() {
+        return Err(not_a_temperature());
+    }
+    Ok((value, scale.parse()?))
+}
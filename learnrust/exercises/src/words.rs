@@ -0,0 +1,249 @@
+// Numbers as English words, both ways: 123 <-> "one hundred twenty-three"
+// and 21 <-> "twenty-first". Numbers are grouped in threes, each group is
+// said on its own ("one hundred twenty-three") followed by its scale word.
+use std::{fmt, str::FromStr};
+
+const ONES: [&str; 20] = [
+    "zero",
+    "one",
+    "two",
+    "three",
+    "four",
+    "five",
+    "six",
+    "seven",
+    "eight",
+    "nine",
+    "ten",
+    "eleven",
+    "twelve",
+    "thirteen",
+    "fourteen",
+    "fifteen",
+    "sixteen",
+    "seventeen",
+    "eighteen",
+    "nineteen",
+];
+
+const TENS: [&str; 10] = [
+    "", "", "twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety",
+];
+
+// every scale is a thousand times the one before it, u64 tops out in the quintillions
+const SCALES: [&str; 7] = [
+    "",
+    "thousand",
+    "million",
+    "billion",
+    "trillion",
+    "quadrillion",
+    "quintillion",
+];
+
+// Ordinals that aren't just the cardinal with "th" on the end
+const IRREGULAR: [(&str, &str); 7] = [
+    ("one", "first"),
+    ("two", "second"),
+    ("three", "third"),
+    ("five", "fifth"),
+    ("eight", "eighth"),
+    ("nine", "ninth"),
+    ("twelve", "twelfth"),
+];
+
+// 0 < n < 1000
+fn group(n: u64) -> String {
+    let (hundreds, rest) = (n / 100, (n % 100) as usize);
+    let mut words = Vec::new();
+    if hundreds > 0 {
+        words.push(format!("{} hundred", ONES[hundreds as usize]));
+    }
+    match rest {
+        0 => {}
+        1..=19 => words.push(ONES[rest].to_owned()),
+        _ if rest % 10 == 0 => words.push(TENS[rest / 10].to_owned()),
+        _ => words.push(format!("{}-{}", TENS[rest / 10], ONES[rest % 10])),
+    }
+    words.join(" ")
+}
+
+pub fn cardinal(n: i64) -> String {
+    if n == 0 {
+        return ONES[0].to_owned();
+    }
+    let mut magnitude = n.unsigned_abs(); // <-- i64::MIN has no positive i64
+    let mut groups = Vec::new();
+    for scale in SCALES {
+        let part = magnitude % 1000;
+        if part > 0 {
+            groups.push(match scale {
+                "" => group(part),
+                _ => format!("{} {scale}", group(part)),
+            });
+        }
+        magnitude /= 1000;
+    }
+    groups.reverse();
+    let words = groups.join(" ");
+    if n < 0 {
+        format!("minus {words}")
+    } else {
+        words
+    }
+}
+
+pub fn ordinal(n: i64) -> String {
+    let words = cardinal(n);
+    // only the last word changes: "twenty-one" -> "twenty-first"
+    let split = words.rfind([' ', '-']).map_or(0, |i| i + 1);
+    let (head, last) = words.split_at(split);
+    let last = match IRREGULAR.iter().find(|(word, _)| *word == last) {
+        Some((_, ordinal)) => ordinal.to_string(),
+        None => match last.strip_suffix('y') {
+            Some(stem) => format!("{stem}ieth"), // <-- twenty -> twentieth
+            None => format!("{last}th"),
+        },
+    };
+    head.to_owned() + &last
+}
+
+// Turn an ordinal word back into its cardinal form, "twenty-first" -> "twenty-one"
+fn unordinal(word: &str) -> Option<String> {
+    if let Some((cardinal, _)) = IRREGULAR.iter().find(|(_, ordinal)| *ordinal == word) {
+        return Some(cardinal.to_string());
+    }
+    if let Some(stem) = word.strip_suffix("ieth") {
+        return Some(format!("{stem}y"));
+    }
+    word.strip_suffix("th").map(String::from)
+}
+
+fn word_value(word: &str) -> Option<u64> {
+    if let Some(i) = ONES.iter().position(|w| *w == word) {
+        return Some(i as u64);
+    }
+    TENS.iter()
+        .position(|w| !w.is_empty() && *w == word)
+        .map(|i| i as u64 * 10)
+}
+
+fn parse_words(text: &str, ordinal: bool) -> Result<i64, String> {
+    let invalid = |why: &str| format!("'{text}' is not a number in words ({why})");
+    let lowered = text.trim().to_lowercase();
+    let mut words: Vec<String> = lowered
+        .split([' ', '-'])
+        .filter(|word| !word.is_empty() && *word != "and") // <-- "one hundred and one"
+        .map(String::from)
+        .collect();
+
+    if ordinal {
+        let last = words.pop().ok_or_else(|| invalid("nothing to parse"))?;
+        words.push(unordinal(&last).ok_or_else(|| invalid("not an ordinal"))?);
+    }
+
+    let negative = matches!(
+        words.first().map(String::as_str),
+        Some("minus" | "negative")
+    );
+    if negative {
+        words.remove(0);
+    }
+    if words.is_empty() {
+        return Err(invalid("nothing to parse"));
+    }
+
+    // 'current' is the group being built, it's moved into 'total' whenever
+    // a scale word like "thousand" says how big it is
+    let (mut total, mut current): (u64, u64) = (0, 0);
+    let mut last_scale = usize::MAX;
+    for word in &words {
+        if let Some(value) = word_value(word) {
+            current += value;
+        } else if word == "hundred" {
+            current = current.checked_mul(100).ok_or_else(|| invalid("too big"))?;
+        } else if let Some(scale) = SCALES.iter().position(|s| !s.is_empty() && s == word) {
+            if scale >= last_scale {
+                return Err(invalid(&format!("'{word}' is out of order")));
+            }
+            let multiplier = 1000u64.pow(scale as u32);
+            let group = current
+                .checked_mul(multiplier)
+                .ok_or_else(|| invalid("too big"))?;
+            total = total.checked_add(group).ok_or_else(|| invalid("too big"))?;
+            current = 0;
+            last_scale = scale;
+        } else {
+            return Err(invalid(&format!("unknown word '{word}'")));
+        }
+    }
+    let magnitude = total
+        .checked_add(current)
+        .ok_or_else(|| invalid("too big"))?;
+    let n = i128::from(magnitude) * if negative { -1 } else { 1 };
+    i64::try_from(n).map_err(|_| invalid("too big"))
+}
+
+pub fn parse_cardinal(text: &str) -> Result<i64, String> {
+    parse_words(text, false)
+}
+
+pub fn parse_ordinal(text: &str) -> Result<i64, String> {
+    parse_words(text, true)
+}
+
+// A number typed either as digits or as words, so prompts can take "7" as
+// well as "seven" or "seventh"
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Number(pub i64);
+
+impl FromStr for Number {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Number, String> {
+        if let Ok(n) = s.trim().parse() {
+            return Ok(Number(n));
+        }
+        parse_cardinal(s).or_else(|_| parse_ordinal(s)).map(Number)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn irregular_ordinals() {
+        assert_eq!(ordinal(9), "ninth");
+        assert_eq!(ordinal(29), "twenty-ninth");
+        assert_eq!(ordinal(12), "twelfth");
+        assert_eq!(ordinal(40), "fortieth");
+        assert_eq!(parse_ordinal("ninth"), Ok(9));
+        assert_eq!(parse_ordinal("twenty-ninth"), Ok(29));
+    }
+
+    #[test]
+    fn round_trips() {
+        for n in 1..=1000 {
+            assert_eq!(parse_cardinal(&cardinal(n)), Ok(n), "{}", cardinal(n));
+            assert_eq!(parse_ordinal(&ordinal(n)), Ok(n), "{}", ordinal(n));
+        }
+        for n in [i64::MIN, -1, 0, i64::MAX] {
+            assert_eq!(parse_cardinal(&cardinal(n)), Ok(n));
+        }
+    }
+
+    #[test]
+    fn too_big() {
+        let hundreds = format!("one{}", " hundred".repeat(11));
+        assert!(parse_cardinal(&hundreds).unwrap_err().contains("too big"));
+        assert!(hundreds.parse::<Number>().is_err());
+        assert!(parse_cardinal("ten quintillion").is_err());
+    }
+}
@@ -0,0 +1,67 @@
+// The command line output checked against files in tests/golden. After a
+// change that's meant to alter the output, write the files again with
+//
+//   UPDATE_GOLDEN=1 cargo test --test cli
+//
+// and look over the diff before committing them.
+use std::{env, fs, path::PathBuf, process::Command};
+
+fn golden(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(name)
+}
+
+// Run the exercises binary and compare what it prints with the golden file
+fn check(args: &[&str], name: &str, succeeds: bool) {
+    let output = Command::new(env!("CARGO_BIN_EXE_exercises"))
+        .args(args)
+        .current_dir(golden(""))
+        .output()
+        .expect("the exercises binary runs");
+    assert_eq!(output.status.success(), succeeds, "exit status of {args:?}");
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let path = golden(name);
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::write(&path, &stdout).unwrap();
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|err| {
+        panic!("can't read {name} ({err}), run with UPDATE_GOLDEN=1 to write it")
+    });
+    assert_eq!(stdout, expected, "output of {args:?} differs from {name}");
+}
+
+#[test]
+fn temp() {
+    check(&["temp", "30C", "--to", "F"], "temp.txt", true);
+    check(&["--json", "temp", "1e5C", "--to", "K"], "temp.json", true);
+}
+
+#[test]
+fn temp_out_of_range() {
+    let huge = format!("1{}C", "0".repeat(309));
+    check(&["--json", "temp", &huge, "--to", "F"], "empty.txt", false);
+    check(
+        &["--json", "temp", "1e308C", "--to", "F"],
+        "empty.txt",
+        false,
+    );
+}
+
+#[test]
+fn fib() {
+    check(&["fib", "90"], "fib.txt", true);
+    check(&["--json", "fib", "90"], "fib.json", true);
+}
+
+#[test]
+fn carol() {
+    check(&["carol"], "carol.txt", true);
+    check(&["--json", "carol", "--days", "9"], "carol.json", true);
+}
+
+#[test]
+fn batch() {
+    check(&["--batch", "commands.txt"], "batch.txt", false);
+    check(&["--json", "--batch", "commands.txt"], "batch.json", false);
+}
@@ -1,5 +1,6 @@
+use exercises::{prompt, words::Number};
 use rand::Rng;
-use std::{cmp::Ordering, io};
+use std::cmp::Ordering;
 
 fn main() {
     // By default, Rust has a set of items defined in the standard library that
@@ -9,22 +10,35 @@ fn main() {
     println!("Guess the number (between 1 and 10)! ({secret_number})");
 
     loop {
-        println!("Please input your guess.");
-        // Variables are immutable by default
-        let mut guess = String::new();
-
-        // Bring module (io here) in scope with 'use' statement.
-        let _ = io::stdin()
-            // like variables, references are immutable by default so '&mut guess' and not '&guess'
-            .read_line(&mut guess)
-            // read_line returns a 'Result' enum: <Ok, Err>, 'expect' handles Err result
-            .expect("Failed to read line");
-
-        // this guess shadows the previous one! parse() converts string to other types
-        // let guess: u32 = guess.trim().parse().expect("Please type a number!");
-        let guess: u32 = match guess.trim().parse() {
-            Ok(num) => num,     // If parse results in Ok, then guess becomes num
-            Err(_) => continue, // Else all other values _ are discarded and we run it back
+        // The prompt does what this used to do by hand, asking again until
+        // it gets a number in range. It only returns an 'Err' once there's
+        // no more input to read. 'Number' takes digits as well as words, so
+        // "7" and "seven" are the same guess. The long way:
+        //
+        // println!("Please input your guess.");
+        // // Variables are immutable by default
+        // let mut guess = String::new();
+        //
+        // // Bring module (io here) in scope with 'use' statement.
+        // let _ = io::stdin()
+        //     // like variables, references are immutable by default so '&mut guess' and not '&guess'
+        //     .read_line(&mut guess)
+        //     // read_line returns a 'Result' enum: <Ok, Err>, 'expect' handles Err result
+        //     .expect("Failed to read line");
+        //
+        // // this guess shadows the previous one! parse() converts string to other types
+        // // let guess: u32 = guess.trim().parse().expect("Please type a number!");
+        // let guess: u32 = match guess.trim().parse() {
+        //     Ok(num) => num,     // If parse results in Ok, then guess becomes num
+        //     Err(_) => continue, // Else all other values _ are discarded and we run it back
+        // };
+        let range = Number(1)..=Number(10);
+        let guess = match prompt::range("Please input your guess.", range, None) {
+            Ok(Number(num)) => num,
+            Err(err) => {
+                println!("Giving up ({err})");
+                return;
+            }
         };
 
         // A ‘match’ expression is made up of ‘arms’.  An arm consists of a